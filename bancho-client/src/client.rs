@@ -0,0 +1,266 @@
+use bancho_packet::{
+    buffer::serialization::Buffer,
+    bytes::Bytes,
+    error::DecodeError,
+    packets::{
+        framer::PacketFramer,
+        packet::Packet,
+        reader::LoginData,
+        structures::{
+            BanchoMessage, ClientHashes, ClientStatus, ClientVersion, Match, ReleaseStream,
+            ReplayFrameBundle,
+        },
+    },
+};
+use md5::{Digest, Md5};
+use reqwest::header::USER_AGENT;
+use tracing::{debug, instrument, warn};
+
+use crate::{error::ClientError, event::Event};
+
+/// What the login request says about the player and their client
+#[derive(Debug, Clone)]
+pub struct Credentials {
+    pub username: String,
+    pub password_md5: String,
+    pub client_version: ClientVersion,
+    pub utc_offset: i32,
+    pub client_hashes: ClientHashes,
+}
+
+impl Credentials {
+    /// Credentials for `username`, with a current stable client and hardware hashes made up from the username
+    ///
+    /// The hashes only need to be stable per player, so multiple clients don't look like one machine.
+    pub fn new(username: &str, password: &str) -> Self {
+        let hash = |salt: &str| md5_hex(&format!("{username}:{salt}")).into();
+        Credentials {
+            username: username.to_string(),
+            password_md5: md5_hex(password),
+            client_version: ClientVersion {
+                date: 20230326,
+                revision: None,
+                stream: ReleaseStream::Stable,
+            },
+            utc_offset: 0,
+            client_hashes: ClientHashes {
+                path_md5: hash("path"),
+                adapters: Vec::new(),
+                adapters_md5: hash("adapters"),
+                uninstall_md5: hash("uninstall"),
+                disk_signature_md5: hash("disk"),
+                running_under_wine: true,
+            },
+        }
+    }
+
+    /// The login request these credentials make
+    pub fn login_data(&self) -> LoginData {
+        LoginData {
+            username: self.username.as_str().into(),
+            password_md5: self.password_md5.as_str().into(),
+            client_version: self.client_version,
+            utc_offset: self.utc_offset,
+            show_city: 0,
+            allow_pms: 1,
+            client_hashes: self.client_hashes.clone(),
+        }
+    }
+}
+
+fn md5_hex(input: &str) -> String {
+    format!("{:x}", Md5::digest(input))
+}
+
+/// A single logged in player, talking to the server over http like the real client
+///
+/// Packets are queued up and sent with the next [`Client::poll`], which also returns whatever the
+/// server had waiting for us.
+pub struct Client {
+    http: reqwest::Client,
+    url: String,
+    token: Option<String>,
+    user_id: Option<i32>,
+    queued: Buffer,
+}
+
+impl Client {
+    pub fn new(url: impl Into<String>) -> Self {
+        Client {
+            http: reqwest::Client::new(),
+            url: url.into(),
+            token: None,
+            user_id: None,
+            queued: Buffer::new(),
+        }
+    }
+
+    /// The `cho-token` from the last successful login
+    pub fn token(&self) -> Option<&str> {
+        self.token.as_deref()
+    }
+
+    pub fn user_id(&self) -> Option<i32> {
+        self.user_id
+    }
+
+    #[instrument(skip_all, fields(username = %credentials.username))]
+    pub async fn login(&mut self, credentials: &Credentials) -> Result<Vec<Event>, ClientError> {
+        let response = self
+            .http
+            .post(&self.url)
+            .header(USER_AGENT, "osu!")
+            .body(credentials.login_data().to_string())
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(ClientError::Status(response.status()));
+        }
+
+        let token = response
+            .headers()
+            .get("cho-token")
+            .and_then(|token| token.to_str().ok())
+            .map(str::to_string)
+            .ok_or(ClientError::MissingToken)?;
+        let events = decode_events(response.bytes().await?)?;
+
+        let reply = events.iter().find_map(|event| match event {
+            Event::LoginReply(reply) => Some(*reply),
+            _ => None,
+        });
+        match reply {
+            Some(user_id) if user_id >= 0 => {
+                debug!(user_id, "logged in");
+                self.token = Some(token);
+                self.user_id = Some(user_id);
+                Ok(events)
+            }
+            Some(code) => Err(ClientError::LoginFailed {
+                code,
+                reason: token,
+            }),
+            None => Err(ClientError::MissingLoginReply),
+        }
+    }
+
+    /// Queue a packet to go out with the next poll
+    pub fn queue(&mut self, packet: &Packet) {
+        packet.encode(&mut self.queued);
+    }
+
+    /// Send everything queued so far, and return what the server sent back
+    ///
+    /// Queued packets are dropped if the request fails.
+    #[instrument(skip_all, fields(user_id = self.user_id))]
+    pub async fn poll(&mut self) -> Result<Vec<Event>, ClientError> {
+        let token = self.token.as_deref().ok_or(ClientError::NotLoggedIn)?;
+        let body = self.queued.split().freeze();
+
+        let response = self
+            .http
+            .post(&self.url)
+            .header(USER_AGENT, "osu!")
+            .header("osu-token", token)
+            .body(body)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(ClientError::Status(response.status()));
+        }
+        Ok(decode_events(response.bytes().await?)?)
+    }
+
+    /// Queue a packet and poll straight away
+    pub async fn send(&mut self, packet: &Packet) -> Result<Vec<Event>, ClientError> {
+        self.queue(packet);
+        self.poll().await
+    }
+
+    pub async fn send_status(&mut self, status: ClientStatus) -> Result<Vec<Event>, ClientError> {
+        self.send(&Packet::ClientSendUserStatus(status)).await
+    }
+
+    /// Send a message to a channel, or to a player when `target` doesn't start with `#`
+    pub async fn send_message(
+        &mut self,
+        target: &str,
+        message: &str,
+    ) -> Result<Vec<Event>, ClientError> {
+        let message = BanchoMessage {
+            sending_client: "".into(),
+            message: message.into(),
+            target: target.into(),
+            sender_id: 0,
+        };
+        let packet = match target.starts_with('#') {
+            true => Packet::ClientSendIrcMessage(message),
+            false => Packet::ClientSendIrcMessagePrivate(message),
+        };
+        self.send(&packet).await
+    }
+
+    pub async fn join_channel(&mut self, channel: &str) -> Result<Vec<Event>, ClientError> {
+        self.send(&Packet::ClientChannelJoin(channel.into())).await
+    }
+
+    pub async fn leave_channel(&mut self, channel: &str) -> Result<Vec<Event>, ClientError> {
+        self.send(&Packet::ClientChannelLeave(channel.into())).await
+    }
+
+    pub async fn start_spectating(&mut self, user_id: i32) -> Result<Vec<Event>, ClientError> {
+        self.send(&Packet::ClientStartSpectating(user_id)).await
+    }
+
+    pub async fn stop_spectating(&mut self) -> Result<Vec<Event>, ClientError> {
+        self.send(&Packet::ClientStopSpectating).await
+    }
+
+    pub async fn send_frames(
+        &mut self,
+        frames: ReplayFrameBundle,
+    ) -> Result<Vec<Event>, ClientError> {
+        self.send(&Packet::ClientSpectateFrames(frames)).await
+    }
+
+    pub async fn create_match(&mut self, game: Match) -> Result<Vec<Event>, ClientError> {
+        self.send(&Packet::ClientMatchCreate(game)).await
+    }
+
+    pub async fn join_match(
+        &mut self,
+        match_id: i32,
+        password: &str,
+    ) -> Result<Vec<Event>, ClientError> {
+        self.send(&Packet::ClientMatchJoin {
+            match_id,
+            password: password.into(),
+        })
+        .await
+    }
+
+    pub async fn leave_match(&mut self) -> Result<Vec<Event>, ClientError> {
+        self.send(&Packet::ClientMatchPart).await
+    }
+
+    /// Tell the server we're closing, and forget the token
+    pub async fn logout(&mut self) -> Result<Vec<Event>, ClientError> {
+        let events = self.send(&Packet::ClientExit(0)).await;
+        self.token = None;
+        self.user_id = None;
+        events
+    }
+}
+
+/// Decode every packet in a response, skipping over ids we don't know
+pub fn decode_events(body: Bytes) -> Result<Vec<Event>, DecodeError> {
+    let mut events = Vec::new();
+    for frame in PacketFramer::new(body) {
+        match Packet::from_frame(frame?) {
+            Ok(packet) => events.push(packet.into()),
+            Err(DecodeError::UnknownPacket(id)) => warn!(id, "skipping unknown packet"),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(events)
+}
@@ -0,0 +1,27 @@
+use bancho_packet::error::DecodeError;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ClientError {
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+
+    #[error("server responded with {}", .0)]
+    Status(reqwest::StatusCode),
+
+    #[error("could not decode server response: {}", .0)]
+    Decode(#[from] DecodeError),
+
+    /// The login reply was negative, `reason` is whatever the server put in `cho-token`
+    #[error("login failed with code {}: {}", .code, .reason)]
+    LoginFailed { code: i32, reason: String },
+
+    #[error("login response had no cho-token")]
+    MissingToken,
+
+    #[error("login response had no login reply")]
+    MissingLoginReply,
+
+    #[error("not logged in")]
+    NotLoggedIn,
+}
@@ -0,0 +1,92 @@
+use bancho_packet::{
+    buffer::byte_str::ByteStr,
+    packets::{
+        packet::Packet,
+        structures::{
+            BanchoChannel, BanchoMessage, BanchoPresence, BanchoPrivileges, BanchoStats, Match,
+            ReplayFrameBundle, ScoreFrame,
+        },
+    },
+};
+
+/// Something the server told us, decoded from the packets in a response
+///
+/// Packets a client would usually act on get their own variant, anything else is kept as `Other`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    /// The login reply, a user id on success or a negative failure code
+    LoginReply(i32),
+    ProtocolVersion(i32),
+    Privileges(BanchoPrivileges),
+    Announce(ByteStr),
+    Ping,
+
+    Message(BanchoMessage),
+    ChannelAvailable(BanchoChannel),
+    ChannelJoined(ByteStr),
+    ChannelRevoked(ByteStr),
+    ChannelListingComplete,
+
+    Presence(BanchoPresence),
+    Stats(BanchoStats),
+    UserQuit(i32),
+    FriendsList(Vec<i32>),
+
+    SpectatorJoined(i32),
+    SpectatorLeft(i32),
+    FellowSpectatorJoined(i32),
+    FellowSpectatorLeft(i32),
+    SpectateFrames(ReplayFrameBundle),
+
+    MatchNew(Match),
+    MatchUpdate(Match),
+    MatchDisband(i32),
+    MatchJoined(Match),
+    MatchJoinFailed,
+    MatchStart(Match),
+    MatchScoreUpdate(ScoreFrame),
+    MatchComplete,
+
+    Other(Packet),
+}
+
+impl From<Packet> for Event {
+    fn from(packet: Packet) -> Self {
+        match packet {
+            Packet::BanchoLoginReply(reply) => Event::LoginReply(reply),
+            Packet::BanchoProtocolNegotiation(version) => Event::ProtocolVersion(version),
+            Packet::BanchoLoginPermissions(privileges) => Event::Privileges(privileges),
+            Packet::BanchoAnnounce(text) => Event::Announce(text),
+            Packet::BanchoPing => Event::Ping,
+
+            Packet::BanchoSendMessage(message) => Event::Message(message),
+            Packet::BanchoChannelAvailable(channel)
+            | Packet::BanchoChannelAvailableAutojoin(channel) => Event::ChannelAvailable(channel),
+            Packet::BanchoChannelJoinSuccess(channel) => Event::ChannelJoined(channel),
+            Packet::BanchoChannelRevoked(channel) => Event::ChannelRevoked(channel),
+            Packet::BanchoChannelListingComplete => Event::ChannelListingComplete,
+
+            Packet::BanchoUserPresence(presence) => Event::Presence(presence),
+            Packet::BanchoHandleOsuUpdate(stats) => Event::Stats(stats),
+            Packet::BanchoHandleUserQuit(user_id) => Event::UserQuit(user_id),
+            Packet::BanchoFriendsList(friends) => Event::FriendsList(friends),
+
+            Packet::BanchoSpectatorJoined(user_id) => Event::SpectatorJoined(user_id),
+            Packet::BanchoSpectatorLeft(user_id) => Event::SpectatorLeft(user_id),
+            Packet::BanchoFellowSpectatorJoined(user_id) => Event::FellowSpectatorJoined(user_id),
+            Packet::BanchoFellowSpectatorLeft(user_id) => Event::FellowSpectatorLeft(user_id),
+            Packet::BanchoSpectateFrames(frames) => Event::SpectateFrames(frames),
+
+            Packet::BanchoMatchNew(game) => Event::MatchNew(game),
+            Packet::BanchoMatchUpdate(game) => Event::MatchUpdate(game),
+            Packet::BanchoMatchDisband(match_id) => Event::MatchDisband(match_id),
+            Packet::BanchoMatchJoinSuccess(game) => Event::MatchJoined(game),
+            Packet::BanchoMatchJoinFail => Event::MatchJoinFailed,
+            Packet::BanchoMatchStart(game) => Event::MatchStart(game),
+            Packet::BanchoMatchScoreUpdate(score) => Event::MatchScoreUpdate(score),
+            Packet::BanchoMatchComplete => Event::MatchComplete,
+
+            other => Event::Other(other),
+        }
+    }
+}
@@ -0,0 +1,22 @@
+//! A headless osu! client, for driving a bancho server without a real install
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), bancho_client::ClientError> {
+//! use bancho_client::{Client, Credentials};
+//!
+//! let mut client = Client::new("http://localhost:8080");
+//! let events = client.login(&Credentials::new("peppy", "password")).await?;
+//! client.send_message("#osu", "hello").await?;
+//! for event in client.poll().await? {
+//!     println!("{event:?}");
+//! }
+//! # Ok(())
+//! # }
+//! ```
+pub mod client;
+pub mod error;
+pub mod event;
+
+pub use client::{Client, Credentials};
+pub use error::ClientError;
+pub use event::Event;
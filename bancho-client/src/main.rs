@@ -0,0 +1,82 @@
+use std::time::Duration;
+
+use bancho_client::{Client, ClientError, Credentials, Event};
+use clap::Parser;
+use tracing::info;
+use tracing_subscriber::EnvFilter;
+
+/// Log in to a bancho server as a headless client, run a few actions, and print what comes back
+#[derive(Debug, Parser)]
+struct Args {
+    /// The bancho endpoint, what osu! would reach as `c.<devserver>`
+    #[arg(long, default_value = "http://localhost:8080/")]
+    server: String,
+
+    #[arg(short, long)]
+    username: String,
+
+    #[arg(short, long)]
+    password: String,
+
+    /// Channels to join after logging in
+    #[arg(long = "join")]
+    channels: Vec<String>,
+
+    /// Messages to send, as `target:message` where the target is a channel or a username
+    #[arg(long = "say")]
+    messages: Vec<String>,
+
+    /// A user id to spectate
+    #[arg(long)]
+    spectate: Option<i32>,
+
+    /// How many times to poll before logging out
+    #[arg(long, default_value_t = 5)]
+    polls: u32,
+
+    /// Milliseconds between polls
+    #[arg(long, default_value_t = 1000)]
+    interval: u64,
+}
+
+fn print_events(events: Vec<Event>) {
+    for event in events {
+        info!("{event:?}");
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), ClientError> {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+        )
+        .init();
+    let args = Args::parse();
+
+    let mut client = Client::new(args.server);
+    print_events(
+        client
+            .login(&Credentials::new(&args.username, &args.password))
+            .await?,
+    );
+
+    for channel in &args.channels {
+        print_events(client.join_channel(channel).await?);
+    }
+    for message in &args.messages {
+        let (target, text) = message.split_once(':').unwrap_or(("#osu", message));
+        print_events(client.send_message(target, text).await?);
+    }
+    if let Some(user_id) = args.spectate {
+        print_events(client.start_spectating(user_id).await?);
+    }
+
+    for _ in 0..args.polls {
+        tokio::time::sleep(Duration::from_millis(args.interval)).await;
+        print_events(client.poll().await?);
+    }
+
+    print_events(client.logout().await?);
+    Ok(())
+}
@@ -0,0 +1,52 @@
+use bancho_client::{client::decode_events, Credentials, Event};
+use bancho_packet::{
+    buffer::serialization::{BufMutExt, Buffer, BytesMutExt},
+    bytes::Bytes,
+    packets::{
+        reader::LoginData,
+        structures::{BanchoPrivileges, ProtocolVersion},
+        writer::*,
+    },
+};
+
+#[test]
+fn login_response_decodes_into_events() {
+    let mut buf = Buffer::new();
+    bancho_login_reply(&mut buf, 1000);
+    bancho_protocol_negotiaton(&mut buf, ProtocolVersion::V19);
+    bancho_announce(&mut buf, "welcome!");
+    // ids the client doesn't know about are skipped rather than failing the whole response
+    buf.with_header(0x7fff, |buf| buf.put_i32_list(&[1, 2]));
+    bancho_login_permissions(&mut buf, BanchoPrivileges::SUPPORTER);
+    bancho_channel_join_success(&mut buf, "#osu");
+
+    assert_eq!(
+        decode_events(buf.freeze()),
+        Ok(vec![
+            Event::LoginReply(1000),
+            Event::ProtocolVersion(19),
+            Event::Announce("welcome!".into()),
+            Event::Privileges(BanchoPrivileges::SUPPORTER),
+            Event::ChannelJoined("#osu".into()),
+        ])
+    );
+}
+
+#[test]
+fn credentials_make_a_valid_login() {
+    let credentials = Credentials::new("peppy", "password");
+    assert_eq!(credentials.password_md5, "5f4dcc3b5aa765d61d8327deb882cf99");
+
+    let body = credentials.login_data().to_string();
+    let login = LoginData::from_slice(&mut Bytes::from(body)).unwrap();
+    assert_eq!(login.username, "peppy");
+    assert_eq!(login.client_version, credentials.client_version);
+    assert_eq!(login.client_hashes, credentials.client_hashes);
+
+    // a different player looks like a different machine
+    let other = Credentials::new("cookiezi", "password");
+    assert_ne!(
+        other.client_hashes.uninstall_md5,
+        login.client_hashes.uninstall_md5
+    );
+}
@@ -0,0 +1,349 @@
+//! Derives for `bancho_packet::buffer::serialization::{BanchoSerialize, BanchoDeserialize}`
+//!
+//! Fields are read and written in declaration order, so the layout of a packet is only defined once, by its struct.
+//! `BanchoSerialize::encoded_len` is derived from the same fields, so it always agrees with what gets written.
+//! Strings are always osu! strings (a 0x0b marker and a ULEB128 length), `Option<String>` is a nullable one.
+//!
+//! Container attributes:
+//! - `#[bancho(id = PacketVariant)]` implements `BanchoPacket`, tying the struct to a `PacketIDs` variant
+//!
+//! Field attributes:
+//! - `#[bancho(list = "i16")]` a `Vec` prefixed with its length, as an `i16`, `i32` or `u16`.
+//!   Serializing a list too long for its length type panics, and a negative length fails to deserialize
+//! - `#[bancho(if = "flag")]` an `Option` only present when the earlier bool field `flag` is set
+//! - `#[bancho(skip)]` never sent, filled with `Default::default()` when read
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::{
+    ext::IdentExt,
+    parse::{Parse, ParseStream},
+    parse_macro_input,
+    punctuated::Punctuated,
+    Attribute, Data, DeriveInput, Error, Fields, Ident, LitStr, Path, Result, Token,
+};
+
+#[proc_macro_derive(BanchoSerialize, attributes(bancho))]
+pub fn derive_serialize(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_serialize(&input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+#[proc_macro_derive(BanchoDeserialize, attributes(bancho))]
+pub fn derive_deserialize(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_deserialize(&input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+/// A single `key` or `key = value` inside of `#[bancho(...)]`
+struct Arg {
+    key: Ident,
+    value: Option<ArgValue>,
+}
+
+enum ArgValue {
+    Str(LitStr),
+    Path(Path),
+}
+
+impl Parse for Arg {
+    fn parse(input: ParseStream) -> Result<Self> {
+        // `if` is a keyword, so it has to be parsed as any ident
+        let key = Ident::parse_any(input)?;
+        if !input.peek(Token![=]) {
+            return Ok(Arg { key, value: None });
+        }
+
+        input.parse::<Token![=]>()?;
+        let value = if input.peek(LitStr) {
+            ArgValue::Str(input.parse()?)
+        } else {
+            ArgValue::Path(input.parse()?)
+        };
+        Ok(Arg {
+            key,
+            value: Some(value),
+        })
+    }
+}
+
+impl Arg {
+    fn string(&self) -> Result<LitStr> {
+        match &self.value {
+            Some(ArgValue::Str(lit)) => Ok(lit.clone()),
+            _ => Err(Error::new_spanned(
+                &self.key,
+                format!("expected `{} = \"...\"`", self.key),
+            )),
+        }
+    }
+
+    fn path(&self) -> Result<Path> {
+        match &self.value {
+            Some(ArgValue::Path(path)) => Ok(path.clone()),
+            Some(ArgValue::Str(lit)) => lit.parse(),
+            None => Err(Error::new_spanned(
+                &self.key,
+                format!("expected `{} = ...`", self.key),
+            )),
+        }
+    }
+}
+
+fn parse_args(attrs: &[Attribute]) -> Result<Vec<Arg>> {
+    let mut args = Vec::new();
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("bancho")) {
+        args.extend(attr.parse_args_with(Punctuated::<Arg, Token![,]>::parse_terminated)?);
+    }
+    Ok(args)
+}
+
+#[derive(Default)]
+struct ContainerAttrs {
+    id: Option<Path>,
+}
+
+impl ContainerAttrs {
+    fn from_attrs(attrs: &[Attribute]) -> Result<Self> {
+        let mut container = ContainerAttrs::default();
+        for arg in parse_args(attrs)? {
+            match arg.key.to_string().as_str() {
+                "id" => container.id = Some(arg.path()?),
+                _ => return Err(Error::new_spanned(&arg.key, "unknown bancho attribute")),
+            }
+        }
+        Ok(container)
+    }
+}
+
+enum FieldKind {
+    Plain,
+    Skip,
+    /// A `Vec` with its length sent in front of it as the given integer type
+    List(Ident),
+    /// An `Option` sent only when the named bool field is set
+    If(Ident),
+}
+
+struct FieldInfo {
+    ident: Ident,
+    kind: FieldKind,
+}
+
+impl FieldInfo {
+    fn from_field(field: &syn::Field) -> Result<Self> {
+        let ident = field
+            .ident
+            .clone()
+            .ok_or_else(|| Error::new_spanned(field, "only named fields are supported"))?;
+
+        let mut kind = FieldKind::Plain;
+        for arg in parse_args(&field.attrs)? {
+            if !matches!(kind, FieldKind::Plain) {
+                return Err(Error::new_spanned(
+                    &arg.key,
+                    "only one bancho attribute is allowed per field",
+                ));
+            }
+            kind = match arg.key.to_string().as_str() {
+                "skip" => FieldKind::Skip,
+                "list" => {
+                    let count = arg.string()?;
+                    match count.value().as_str() {
+                        "i16" | "i32" | "u16" => {
+                            FieldKind::List(Ident::new(&count.value(), count.span()))
+                        }
+                        _ => {
+                            return Err(Error::new_spanned(
+                                count,
+                                "list counts can only be `i16`, `i32` or `u16`",
+                            ))
+                        }
+                    }
+                }
+                "if" => {
+                    let flag = arg.string()?;
+                    FieldKind::If(Ident::new(&flag.value(), flag.span()))
+                }
+                _ => return Err(Error::new_spanned(&arg.key, "unknown bancho attribute")),
+            };
+        }
+
+        Ok(FieldInfo { ident, kind })
+    }
+}
+
+fn fields(input: &DeriveInput) -> Result<Vec<FieldInfo>> {
+    match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => fields.named.iter().map(FieldInfo::from_field).collect(),
+            Fields::Unit => Ok(Vec::new()),
+            Fields::Unnamed(_) => Err(Error::new_spanned(
+                &input.ident,
+                "tuple structs are not supported",
+            )),
+        },
+        _ => Err(Error::new(
+            Span::call_site(),
+            "bancho packets can only be derived for structs",
+        )),
+    }
+}
+
+/// Borrow the value of an `if` field as `value`, falling back to a default when it's missing so the flag and
+/// the layout always agree
+fn or_default(ident: &Ident) -> TokenStream {
+    quote! {
+        let default;
+        let value = match &self.#ident {
+            ::std::option::Option::Some(value) => value,
+            ::std::option::Option::None => {
+                default = ::std::default::Default::default();
+                &default
+            }
+        };
+    }
+}
+
+fn expand_serialize(input: &DeriveInput) -> Result<TokenStream> {
+    let container = ContainerAttrs::from_attrs(&input.attrs)?;
+    let fields = fields(input)?;
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let serialization = quote!(::bancho_packet::buffer::serialization);
+
+    let writes = fields.iter().map(|field| {
+        let ident = &field.ident;
+        match &field.kind {
+            FieldKind::Plain => quote! {
+                #serialization::BanchoSerialize::serialize(&self.#ident, buf);
+            },
+            FieldKind::Skip => quote!(),
+            FieldKind::List(count) => {
+                let too_long = format!("`{ident}` is too long for its {count} length");
+                quote! {
+                    let length = <#count as ::std::convert::TryFrom<usize>>::try_from(self.#ident.len())
+                        .expect(#too_long);
+                    #serialization::BanchoSerialize::serialize(&length, buf);
+                    for item in &self.#ident {
+                        #serialization::BanchoSerialize::serialize(item, buf);
+                    }
+                }
+            }
+            FieldKind::If(flag) => {
+                let value = or_default(ident);
+                quote! {
+                    if self.#flag {
+                        #value
+                        #serialization::BanchoSerialize::serialize(value, buf);
+                    }
+                }
+            }
+        }
+    });
+
+    let lens = fields.iter().map(|field| {
+        let ident = &field.ident;
+        match &field.kind {
+            FieldKind::Plain => quote! {
+                + #serialization::BanchoSerialize::encoded_len(&self.#ident)
+            },
+            FieldKind::Skip => quote!(),
+            FieldKind::List(count) => quote! {
+                + ::std::mem::size_of::<#count>()
+                + self.#ident.iter().map(#serialization::BanchoSerialize::encoded_len).sum::<usize>()
+            },
+            FieldKind::If(flag) => {
+                let value = or_default(ident);
+                quote! {
+                    + if self.#flag {
+                        #value
+                        #serialization::BanchoSerialize::encoded_len(value)
+                    } else {
+                        0
+                    }
+                }
+            }
+        }
+    });
+
+    let packet = container.id.map(|id| {
+        quote! {
+            impl #impl_generics #serialization::BanchoPacket for #name #ty_generics #where_clause {
+                const ID: ::bancho_packet::packets::packet_ids::PacketIDs =
+                    ::bancho_packet::packets::packet_ids::PacketIDs::#id;
+            }
+        }
+    });
+
+    Ok(quote! {
+        impl #impl_generics #serialization::BanchoSerialize for #name #ty_generics #where_clause {
+            fn serialize<B: ::bancho_packet::bytes::BufMut>(&self, buf: &mut B) {
+                #(#writes)*
+            }
+
+            fn encoded_len(&self) -> usize {
+                0 #(#lens)*
+            }
+        }
+
+        #packet
+    })
+}
+
+fn expand_deserialize(input: &DeriveInput) -> Result<TokenStream> {
+    // the id is only used by `BanchoSerialize`, but reject unknown attributes here as well
+    ContainerAttrs::from_attrs(&input.attrs)?;
+    let fields = fields(input)?;
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let serialization = quote!(::bancho_packet::buffer::serialization);
+
+    let reads = fields.iter().map(|field| {
+        let ident = &field.ident;
+        match &field.kind {
+            FieldKind::Plain => quote! {
+                let #ident = #serialization::BanchoDeserialize::deserialize(buf)?;
+            },
+            FieldKind::Skip => quote! {
+                let #ident = ::std::default::Default::default();
+            },
+            FieldKind::List(count) => quote! {
+                let #ident = {
+                    let length = <#count as #serialization::BanchoDeserialize>::deserialize(buf)?;
+                    let length = usize::try_from(length).map_err(|_| {
+                        ::bancho_packet::error::DecodeError::NegativeLength(::std::convert::From::from(length))
+                    })?;
+                    // don't trust the count for the allocation, every item takes at least a byte
+                    let mut list = ::std::vec::Vec::with_capacity(length.min(buf.len()));
+                    for _ in 0..length {
+                        list.push(#serialization::BanchoDeserialize::deserialize(buf)?);
+                    }
+                    list
+                };
+            },
+            FieldKind::If(flag) => quote! {
+                let #ident = if #flag {
+                    ::std::option::Option::Some(#serialization::BanchoDeserialize::deserialize(buf)?)
+                } else {
+                    ::std::option::Option::None
+                };
+            },
+        }
+    });
+    let idents = fields.iter().map(|field| &field.ident);
+
+    Ok(quote! {
+        impl #impl_generics #serialization::BanchoDeserialize for #name #ty_generics #where_clause {
+            fn deserialize(
+                buf: &mut ::bancho_packet::bytes::Bytes,
+            ) -> ::std::result::Result<Self, ::bancho_packet::error::DecodeError> {
+                #(#reads)*
+                ::std::result::Result::Ok(#name { #(#idents),* })
+            }
+        }
+    })
+}
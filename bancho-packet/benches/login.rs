@@ -0,0 +1,98 @@
+//! Counts the allocations made while decoding a login request and a typical body of packets
+//!
+//! Run with `cargo bench -p bancho-packet --bench login`.
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Instant,
+};
+
+use bancho_packet::{
+    buffer::serialization::Buffer,
+    packets::{
+        mods::Mods,
+        packet::Packet,
+        reader::LoginData,
+        structures::{Action, BanchoMessage, ClientStatus, GameMode},
+    },
+};
+use bytes::Bytes;
+
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+const ITERATIONS: usize = 100_000;
+
+const LOGIN: &[u8] = b"peppy\n5f4dcc3b5aa765d61d8327deb882cf99\nb20230101.1cuttingedge|2|0|\
+0c5d2ae2f1a5c2a5e1d6a6bd0bb4d5b7:00-15-5D-12-34-56.00-15-5D-AB-CD-EF.:\
+e4b4c8e7f1cde5e8b4b2a1f1d2c3b4a5:9d6c6d8e7f6a5b4c3d2e1f0a9b8c7d6e:a1b2c3d4e5f6a7b8c9d0e1f2a3b4c5d6:|1\n";
+
+fn body() -> Bytes {
+    let mut buf = Buffer::new();
+    Packet::ClientSendUserStatus(ClientStatus {
+        status: Action::Playing,
+        status_text: "Camellia - Exit This Earth's Atomosphere".into(),
+        beatmap_checksum: "3d8e0d9a4f2b1c6e5a7d8c9b0a1f2e3d".into(),
+        current_mods: Mods::HIDDEN | Mods::DOUBLE_TIME,
+        play_mode: GameMode::Osu,
+        beatmap_id: 2_053_405,
+    })
+    .encode(&mut buf);
+    Packet::ClientSendIrcMessage(BanchoMessage {
+        sending_client: "".into(),
+        message: "hello everyone".into(),
+        target: "#osu".into(),
+        sender_id: 0,
+    })
+    .encode(&mut buf);
+    Packet::ClientPong.encode(&mut buf);
+    buf.freeze()
+}
+
+/// Run `f` over and over, reporting the allocations and time per run
+fn measure(name: &str, mut f: impl FnMut()) {
+    let start_allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f();
+    }
+    let elapsed = start.elapsed();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - start_allocations;
+
+    println!(
+        "{name}: {} allocations, {:?} per run",
+        allocations / ITERATIONS,
+        elapsed / ITERATIONS as u32
+    );
+}
+
+fn main() {
+    let login = Bytes::from_static(LOGIN);
+    measure("login", || {
+        let login = LoginData::from_slice(&mut login.clone()).unwrap();
+        std::hint::black_box(login);
+    });
+
+    let body = body();
+    measure("body", || {
+        let mut body = body.clone();
+        while !body.is_empty() {
+            std::hint::black_box(Packet::decode(&mut body).unwrap());
+        }
+    });
+}
@@ -0,0 +1,12 @@
+//! Reading captures, since `bancho-dump` gets pointed at whatever file is lying around
+#![no_main]
+
+use bancho_packet::packets::capture::CaptureReader;
+use bytes::Bytes;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(records) = CaptureReader::new(Bytes::copy_from_slice(data)) {
+        records.for_each(drop);
+    }
+});
@@ -0,0 +1,10 @@
+//! Splitting a body into frames, which also inflates compressed payloads
+#![no_main]
+
+use bancho_packet::packets::framer::PacketFramer;
+use bytes::Bytes;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    PacketFramer::new(Bytes::copy_from_slice(data)).for_each(drop);
+});
@@ -0,0 +1,10 @@
+//! `LoginData::from_slice` on any body, which is the first thing read from a client before it has a session
+#![no_main]
+
+use bancho_packet::packets::reader::LoginData;
+use bytes::Bytes;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = LoginData::from_slice(&mut Bytes::copy_from_slice(data));
+});
@@ -0,0 +1,19 @@
+//! Every packet's payload decoder, picking the packet id from the first two bytes
+#![no_main]
+
+use bancho_packet::packets::{packet::Packet, packet_ids::PacketIDs};
+use bytes::Bytes;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Some((id, payload)) = data.split_first_chunk::<2>() else {
+        return;
+    };
+    if let Ok(id) = PacketIDs::try_from(i16::from_le_bytes(*id)) {
+        let _ = Packet::decode_payload(id, &mut Bytes::copy_from_slice(payload));
+    }
+
+    // and the whole input as a body, the way the server reads requests
+    let mut body = Bytes::copy_from_slice(data);
+    while Packet::decode(&mut body).is_ok() && !body.is_empty() {}
+});
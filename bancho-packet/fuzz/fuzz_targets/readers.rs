@@ -0,0 +1,23 @@
+//! Every structure in `reader`, each one run on the same input
+#![no_main]
+
+use bancho_packet::packets::{reader, structures::ProtocolVersion};
+use bytes::Bytes;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let data = Bytes::copy_from_slice(data);
+
+    let _ = reader::read_message(&mut data.clone());
+    let _ = reader::read_status(&mut data.clone());
+    let _ = reader::read_stats(&mut data.clone());
+    for protocol in ProtocolVersion::ALL {
+        let _ = reader::read_presence(&mut data.clone(), protocol);
+    }
+    let _ = reader::read_channel(&mut data.clone());
+    let _ = reader::read_match(&mut data.clone());
+    let _ = reader::read_frame_bundle(&mut data.clone());
+    let _ = reader::read_replay_frame(&mut data.clone());
+    let _ = reader::read_score_frame(&mut data.clone());
+    let _ = reader::read_beatmap_info_reply(&mut data.clone());
+});
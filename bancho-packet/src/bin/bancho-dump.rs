@@ -0,0 +1,223 @@
+//! Prints captures, or bodies of raw packets, one packet per line
+//!
+//! ```text
+//! bancho-dump <capture or body file>
+//! bancho-dump -                          read the file from stdin
+//! bancho-dump --hex 05 00 00 04 00 00 00 e8 03 00 00
+//! ```
+//!
+//! Unknown packet ids, payloads that fail to decompress, payloads with bytes left over after decoding, and
+//! bytes after the last whole packet are flagged with a `!`.
+use std::{
+    fmt::Write as _,
+    io::{self, Read},
+    process::ExitCode,
+};
+
+use bancho_packet::{
+    bytes::{Buf, Bytes},
+    error::DecodeError,
+    packets::{
+        capture::{CaptureReader, RecordKind, MAGIC},
+        framer::{Frame, PacketFramer, HEADER_LEN},
+        packet::Packet,
+        packet_ids::PacketIDs,
+        reader::LoginData,
+    },
+};
+
+/// Longest payload printed in full, anything longer is cut off
+const MAX_HEX_LEN: usize = 64;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let input = match args.first().map(String::as_str) {
+        Some("--hex") if args.len() > 1 => parse_hex(&args[1..].concat()),
+        Some("--hex") => read_stdin().and_then(|text| parse_hex(&String::from_utf8_lossy(&text))),
+        Some("-") => read_stdin(),
+        Some(path) if !path.starts_with('-') => std::fs::read(path).map_err(|e| e.to_string()),
+        _ => Err("usage: bancho-dump <file> | - | --hex [hex]".to_string()),
+    };
+
+    match input {
+        Ok(input) => {
+            print!("{}", dump(Bytes::from(input)));
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn read_stdin() -> Result<Vec<u8>, String> {
+    let mut input = Vec::new();
+    io::stdin()
+        .read_to_end(&mut input)
+        .map_err(|e| e.to_string())?;
+    Ok(input)
+}
+
+/// Hex digits with any whitespace, `0x` prefixes or commas in between
+fn parse_hex(text: &str) -> Result<Vec<u8>, String> {
+    let digits: Vec<u8> = text
+        .replace("0x", "")
+        .bytes()
+        .filter(|b| !b.is_ascii_whitespace() && *b != b',')
+        .collect();
+    if digits.len() % 2 == 1 {
+        return Err("odd number of hex digits".to_string());
+    }
+
+    digits
+        .chunks(2)
+        .map(|pair| {
+            std::str::from_utf8(pair)
+                .ok()
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                .ok_or_else(|| format!("bad hex `{}`", String::from_utf8_lossy(pair)))
+        })
+        .collect()
+}
+
+fn dump(input: Bytes) -> String {
+    let mut out = String::new();
+    if !input.starts_with(MAGIC) {
+        dump_body(&mut out, input);
+        return out;
+    }
+
+    let records = match CaptureReader::new(input) {
+        Ok(records) => records,
+        Err(e) => {
+            let _ = writeln!(out, "! bad capture: {e}");
+            return out;
+        }
+    };
+
+    let mut start = None;
+    for record in records {
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                let _ = writeln!(out, "! truncated record: {e}");
+                break;
+            }
+        };
+        let start = *start.get_or_insert(record.timestamp);
+        let offset = (record.timestamp - start) as f64 / 1_000_000.;
+        let direction = match record.kind.from_client() {
+            true => "->",
+            false => "<-",
+        };
+        let _ = writeln!(
+            out,
+            "+{offset:.6}s {direction} {:?} {} ({} bytes)",
+            record.kind,
+            record.token,
+            record.body.len()
+        );
+
+        match record.kind {
+            RecordKind::Login => dump_login(&mut out, record.body),
+            RecordKind::Request | RecordKind::Response => dump_body(&mut out, record.body),
+        }
+    }
+    out
+}
+
+fn dump_login(out: &mut String, body: Bytes) {
+    let _ = match LoginData::from_slice(&mut body.clone()) {
+        Ok(login) => writeln!(
+            out,
+            "  login {} on {}, utc{:+}, hashes {}",
+            login.username, login.client_version, login.utc_offset, login.client_hashes
+        ),
+        Err(e) => writeln!(out, "  ! bad login ({e}): {}", hex(&body)),
+    };
+}
+
+fn dump_body(out: &mut String, mut body: Bytes) {
+    while body.has_remaining() {
+        let rest = body.clone();
+        match PacketFramer::next_frame(&mut body) {
+            Ok(frame) => dump_frame(out, frame),
+            // the whole frame was split off, only its payload is unusable
+            Err(DecodeError::BadCompression) => {
+                let frame = rest.slice(..rest.len() - body.len());
+                let id = i16::from_le_bytes([frame[0], frame[1]]);
+                let payload = &frame[HEADER_LEN..];
+                let _ = match PacketIDs::try_from(id) {
+                    Ok(id) => write!(out, "  ! {id:?}"),
+                    Err(_) => write!(out, "  ! unknown packet id {id}"),
+                };
+                let _ = writeln!(
+                    out,
+                    " ({} bytes, compressed) failed to decompress: {}",
+                    payload.len(),
+                    hex(payload)
+                );
+            }
+            Err(e) => {
+                let _ = writeln!(
+                    out,
+                    "  ! {} trailing bytes ({e}): {}",
+                    rest.len(),
+                    hex(&rest)
+                );
+                return;
+            }
+        }
+    }
+}
+
+fn dump_frame(out: &mut String, frame: Frame) {
+    let length = frame.payload.len();
+    let compressed = match frame.compressed {
+        true => ", compressed",
+        false => "",
+    };
+
+    let id = match PacketIDs::try_from(frame.id) {
+        Ok(id) => id,
+        Err(_) => {
+            let _ = writeln!(
+                out,
+                "  ! unknown packet id {} ({length} bytes{compressed}): {}",
+                frame.id,
+                hex(&frame.payload)
+            );
+            return;
+        }
+    };
+
+    let mut payload = frame.payload.clone();
+    let _ = match Packet::decode_payload(id, &mut payload) {
+        Ok(packet) => writeln!(out, "  {id:?} ({length} bytes{compressed}) {packet:?}"),
+        Err(e) => writeln!(
+            out,
+            "  ! {id:?} ({length} bytes{compressed}) failed to decode ({e}): {}",
+            hex(&frame.payload)
+        ),
+    };
+    if payload.has_remaining() {
+        let _ = writeln!(
+            out,
+            "    ! {} trailing bytes in payload: {}",
+            payload.len(),
+            hex(&payload)
+        );
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    for byte in bytes.iter().take(MAX_HEX_LEN) {
+        let _ = write!(out, "{byte:02x} ");
+    }
+    if bytes.len() > MAX_HEX_LEN {
+        out.push_str("...");
+    }
+    out.trim_end().to_string()
+}
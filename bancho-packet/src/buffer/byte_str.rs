@@ -0,0 +1,135 @@
+use std::{borrow::Borrow, fmt, ops::Deref};
+
+use bytes::Bytes;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::error::DecodeError;
+
+/// A string which shares the `Bytes` it was read out of, instead of copying into a `String`
+///
+/// The contents are checked to be UTF-8 when it is created, and it derefs to a `str` from then on.
+#[derive(Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ByteStr(Bytes);
+
+impl ByteStr {
+    pub const fn from_static(string: &'static str) -> Self {
+        ByteStr(Bytes::from_static(string.as_bytes()))
+    }
+
+    pub fn from_utf8(bytes: Bytes) -> Result<Self, DecodeError> {
+        std::str::from_utf8(&bytes).map_err(|_| DecodeError::BadUtf8)?;
+        Ok(ByteStr(bytes))
+    }
+
+    /// A `ByteStr` for `subset`, which has to be a slice of `self`, sharing the same buffer
+    pub fn slice_ref(&self, subset: &str) -> Self {
+        ByteStr(self.0.slice_ref(subset.as_bytes()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        // only ever built from validated utf-8
+        unsafe { std::str::from_utf8_unchecked(&self.0) }
+    }
+
+    pub fn into_bytes(self) -> Bytes {
+        self.0
+    }
+}
+
+impl Deref for ByteStr {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for ByteStr {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<[u8]> for ByteStr {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Borrow<str> for ByteStr {
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl From<String> for ByteStr {
+    fn from(string: String) -> Self {
+        ByteStr(Bytes::from(string))
+    }
+}
+
+impl From<&str> for ByteStr {
+    fn from(string: &str) -> Self {
+        ByteStr(Bytes::copy_from_slice(string.as_bytes()))
+    }
+}
+
+impl From<ByteStr> for String {
+    fn from(string: ByteStr) -> Self {
+        string.as_str().to_string()
+    }
+}
+
+impl PartialEq<str> for ByteStr {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for ByteStr {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl PartialEq<String> for ByteStr {
+    fn eq(&self, other: &String) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<ByteStr> for str {
+    fn eq(&self, other: &ByteStr) -> bool {
+        self == other.as_str()
+    }
+}
+
+impl PartialEq<ByteStr> for String {
+    fn eq(&self, other: &ByteStr) -> bool {
+        self == other.as_str()
+    }
+}
+
+impl fmt::Debug for ByteStr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for ByteStr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+impl Serialize for ByteStr {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for ByteStr {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(ByteStr::from)
+    }
+}
@@ -1 +1,2 @@
-pub mod serialization;
\ No newline at end of file
+pub mod byte_str;
+pub mod serialization;
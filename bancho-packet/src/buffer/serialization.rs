@@ -1,152 +1,449 @@
 use bytes::{Buf, BufMut, Bytes, BytesMut};
 
-pub trait BytesMutExt {
-    fn put_header(&mut self, id: i16);
-    fn fix_header(&mut self, start: usize);
+use crate::buffer::byte_str::ByteStr;
+use crate::error::{DecodeError, EncodeError};
+use crate::packets::compression;
+use crate::packets::framer::HEADER_LEN;
+use crate::packets::packet_ids::PacketIDs;
+
+pub use bancho_packet_derive::{BanchoDeserialize, BanchoSerialize};
+
+/// Writing the osu! types into any buffer, see [`BytesMutExt`] for writing whole packets into a [`Buffer`]
+pub trait BufMutExt {
+    /// A header for a payload which is `length` bytes long, when it's known up front
+    ///
+    /// Nothing is written if `length` doesn't fit in the header's u32.
+    fn put_packet_header(&mut self, id: i16, length: usize) -> Result<(), EncodeError>;
 
     fn put_bool(&mut self, val: bool);
     fn put_uleb(&mut self, len: usize);
     fn put_string(&mut self, string: &str);
+    /// `None` is written as a lone 0x00 marker, rather than an empty string
+    fn put_optional_string(&mut self, string: Option<&str>);
+    /// Write a list prefixed with its length as an i16
+    ///
+    /// Panics if `list` has more than `i16::MAX` items, longer lists have to be split over several packets.
+    fn put_i32_list(&mut self, list: &[i32]);
+}
 
-    fn get_bool(&mut self) -> bool;
-    fn get_uleb(&mut self) -> usize;
-    fn get_string(&mut self) -> String;
+pub trait BytesMutExt {
+    fn put_header(&mut self, id: i16);
+    /// Fill in the length of the header at `start`, from everything written after it
+    ///
+    /// Panics if the payload is longer than `u32::MAX` bytes.
+    fn fix_header(&mut self, start: usize);
 
     fn with_header(&mut self, id: i16, f: impl FnOnce(&mut Self));
+    /// Like [`BytesMutExt::with_header`], but gzips the payload if it ends up longer than `threshold` bytes
+    ///
+    /// The payload is left uncompressed if gzipping it doesn't make it any smaller.
+    fn with_header_compressed(&mut self, id: i16, threshold: usize, f: impl FnOnce(&mut Self));
 }
 
 pub trait BytesExt {
     fn take_while(&mut self, f: impl FnMut(u8) -> bool) -> Bytes;
+
+    /// Fail with [`DecodeError::UnexpectedEof`] unless at least `len` bytes remain
+    fn ensure(&self, len: usize) -> Result<(), DecodeError>;
+
+    fn read_u8(&mut self) -> Result<u8, DecodeError>;
+    fn read_i16(&mut self) -> Result<i16, DecodeError>;
+    fn read_u16(&mut self) -> Result<u16, DecodeError>;
+    fn read_i32(&mut self) -> Result<i32, DecodeError>;
+    fn read_u32(&mut self) -> Result<u32, DecodeError>;
+    fn read_i64(&mut self) -> Result<i64, DecodeError>;
+    fn read_f32(&mut self) -> Result<f32, DecodeError>;
+    fn read_f64(&mut self) -> Result<f64, DecodeError>;
+
+    fn get_bool(&mut self) -> Result<bool, DecodeError>;
+    fn get_uleb(&mut self) -> Result<usize, DecodeError>;
+    /// Read a string, treating a null (0x00) string as empty
+    fn get_string(&mut self) -> Result<String, DecodeError>;
+    /// Read a string, with a null (0x00) string as `None`
+    fn get_optional_string(&mut self) -> Result<Option<String>, DecodeError>;
+    /// Like [`BytesExt::get_string`], but sharing the buffer instead of copying
+    fn get_str(&mut self) -> Result<ByteStr, DecodeError>;
+    /// Like [`BytesExt::get_optional_string`], but sharing the buffer instead of copying
+    fn get_optional_str(&mut self) -> Result<Option<ByteStr>, DecodeError>;
+    fn get_i32_list(&mut self) -> Result<Vec<i32>, DecodeError>;
+}
+
+impl<B: BufMut + ?Sized> BufMutExt for B {
+    fn put_packet_header(&mut self, id: i16, length: usize) -> Result<(), EncodeError> {
+        let length = u32::try_from(length).map_err(|_| EncodeError::PayloadTooLong(length))?;
+        self.put_i16_le(id);
+        self.put_bool(false);
+        self.put_u32_le(length);
+        Ok(())
+    }
+
+    fn put_bool(&mut self, val: bool) {
+        self.put_u8(val as u8);
+    }
+
+    fn put_uleb(&mut self, mut len: usize) {
+        loop {
+            let byte = (len & 0x7f) as u8;
+            len >>= 7;
+
+            if len == 0 {
+                self.put_u8(byte);
+                return;
+            }
+            // more groups to come, so set the continuation bit
+            self.put_u8(byte | 0x80);
+        }
+    }
+
+    fn put_string(&mut self, string: &str) {
+        self.put_optional_string(Some(string));
+    }
+
+    fn put_optional_string(&mut self, string: Option<&str>) {
+        match string {
+            Some(string) => {
+                self.put_u8(0x0b);
+                self.put_uleb(string.len());
+                self.put_slice(string.as_bytes());
+            }
+            None => self.put_u8(0x00),
+        }
+    }
+
+    fn put_i32_list(&mut self, list: &[i32]) {
+        let length = i16::try_from(list.len()).expect("i32 list is too long for its i16 length");
+        self.put_i16_le(length);
+        for &val in list {
+            self.put_i32_le(val);
+        }
+    }
 }
 
 impl BytesMutExt for BytesMut {
     fn put_header(&mut self, id: i16) {
+        // the length is filled in by `fix_header`, once the payload has been written
         self.put_i16_le(id);
         self.put_bool(false);
         self.put_u32_le(0);
     }
 
     fn fix_header(&mut self, start: usize) {
-        let length = self.len() - start - 7;
-        (self[start + 3]) = length as u8;
+        // the length is the last 4 bytes of the header, after the i16 id and compression flag
+        let length = u32::try_from(self.len() - start - HEADER_LEN)
+            .expect("payload is too long for its u32 length");
+        self[start + 3..start + HEADER_LEN].copy_from_slice(&length.to_le_bytes());
     }
 
-    fn put_bool(&mut self, val: bool) {
-        self.put_u8(val as u8);
+    fn with_header(&mut self, id: i16, f: impl FnOnce(&mut Self)) {
+        // record start and put header
+        let start = self.len();
+        self.put_header(id);
+
+        // run caller provided code
+        f(self);
+
+        // cleanup
+        self.fix_header(start);
     }
 
-    fn put_uleb(&mut self, mut len: usize) {
-        let mut uleb: Vec<u8> = vec![0; 32];
+    fn with_header_compressed(&mut self, id: i16, threshold: usize, f: impl FnOnce(&mut Self)) {
+        let start = self.len();
+        self.with_header(id, f);
 
-        let mut uleb_len: usize = 0;
+        let payload_start = start + HEADER_LEN;
+        if self.len() - payload_start <= threshold {
+            return;
+        }
 
-        while len > 0 {
-            uleb[uleb_len] = (len as u8) & 0x7f;
+        let compressed = compression::compress(&self[payload_start..]);
+        if compressed.len() >= self.len() - payload_start {
+            return;
+        }
+        self.truncate(payload_start);
+        self.put_slice(&compressed);
+        // the compression flag sits right after the i16 id
+        self[start + 2] = 1;
+        self.fix_header(start);
+    }
+}
 
-            len >>= 7;
-            if len != 0 {
-                uleb[uleb_len] |= 0x80;
+impl BytesExt for Bytes {
+    fn take_while(&mut self, mut f: impl FnMut(u8) -> bool) -> Bytes {
+        let mut len = 0;
+        while let Some(b) = self.get(len) {
+            if !f(*b) {
+                break;
             }
+            len += 1;
+        }
 
-            uleb_len += 1;
+        self.split_to(len)
+    }
+
+    fn ensure(&self, len: usize) -> Result<(), DecodeError> {
+        if self.remaining() < len {
+            return Err(DecodeError::UnexpectedEof);
         }
+        Ok(())
+    }
 
-        uleb.retain(|&x| x != 0);
+    fn read_u8(&mut self) -> Result<u8, DecodeError> {
+        self.ensure(1)?;
+        Ok(self.get_u8())
+    }
 
-        self.put(uleb.as_slice());
+    fn read_i16(&mut self) -> Result<i16, DecodeError> {
+        self.ensure(2)?;
+        Ok(self.get_i16_le())
     }
-    fn put_string(&mut self, string: &str) {
-        let length = string.len();
-        if length == 0 {
-            self.put_u8(11);
-            self.put_u8(0);
-            return;
-        }
-        self.put_u8(0xb);
-        self.put_uleb(length);
-        self.put(string.as_bytes());
+
+    fn read_u16(&mut self) -> Result<u16, DecodeError> {
+        self.ensure(2)?;
+        Ok(self.get_u16_le())
+    }
+
+    fn read_i32(&mut self) -> Result<i32, DecodeError> {
+        self.ensure(4)?;
+        Ok(self.get_i32_le())
+    }
+
+    fn read_u32(&mut self) -> Result<u32, DecodeError> {
+        self.ensure(4)?;
+        Ok(self.get_u32_le())
+    }
+
+    fn read_i64(&mut self) -> Result<i64, DecodeError> {
+        self.ensure(8)?;
+        Ok(self.get_i64_le())
+    }
+
+    fn read_f32(&mut self) -> Result<f32, DecodeError> {
+        self.ensure(4)?;
+        Ok(self.get_f32_le())
+    }
+
+    fn read_f64(&mut self) -> Result<f64, DecodeError> {
+        self.ensure(8)?;
+        Ok(self.get_f64_le())
     }
 
-    fn get_bool(&mut self) -> bool {
-        self.get_u8() != 0
+    fn get_bool(&mut self) -> Result<bool, DecodeError> {
+        Ok(self.read_u8()? != 0)
     }
 
-    fn get_uleb(&mut self) -> usize {
+    fn get_uleb(&mut self) -> Result<usize, DecodeError> {
         let mut result = 0;
         let mut shift = 0;
 
-        let mut byte = self.get_u8();
-        // check if the highest bit is set
-        if (byte & 0x80) == 0 {
-            // the value is one byte long, return it
-            return (byte & 0x7f) as usize;
-        }
-
         loop {
-            result |= (byte & 0x7f) << shift;
-            shift += 7;
+            let byte = self.read_u8()?;
+            let value = (byte & 0x7f) as usize;
+
+            // bail out instead of silently dropping bits that don't fit
+            if shift >= usize::BITS || (value << shift) >> shift != value {
+                return Err(DecodeError::BadUleb);
+            }
+            result |= value << shift;
 
-            byte = self.get_u8();
             if (byte & 0x80) == 0 {
                 // the highest bit is not set, the value has ended
-                break;
+                return Ok(result);
             }
+            shift += 7;
         }
+    }
 
-        result as usize
+    fn get_string(&mut self) -> Result<String, DecodeError> {
+        Ok(self.get_str()?.into())
     }
 
-    fn get_string(&mut self) -> String {
-        let _ = self.get_u8();
-        let length = self.get_uleb();
-        let mut string = "".to_string();
+    fn get_optional_string(&mut self) -> Result<Option<String>, DecodeError> {
+        Ok(self.get_optional_str()?.map(String::from))
+    }
 
-        if length > 0 {
-            let mut i = 0;
+    fn get_str(&mut self) -> Result<ByteStr, DecodeError> {
+        Ok(self.get_optional_str()?.unwrap_or_default())
+    }
 
-            while i < length + 1 {
-                let current_char = self.get(i);
-                let chr = match current_char {
-                    Some(x) => *x as char,
-                    None => '\0',
-                };
+    fn get_optional_str(&mut self) -> Result<Option<ByteStr>, DecodeError> {
+        match self.read_u8()? {
+            0x00 => return Ok(None),
+            0x0b => (),
+            marker => return Err(DecodeError::BadStringMarker(marker)),
+        }
 
-                string = format!("{}{}", string, chr); //;
-                i += 1;
-            }
+        let length = self.get_uleb()?;
+        if length > self.remaining() {
+            return Err(DecodeError::OverlongLength {
+                length,
+                remaining: self.remaining(),
+            });
+        }
 
-            self.advance(length);
+        ByteStr::from_utf8(self.split_to(length)).map(Some)
+    }
+
+    fn get_i32_list(&mut self) -> Result<Vec<i32>, DecodeError> {
+        let length = self.read_i16()?;
+        let length =
+            usize::try_from(length).map_err(|_| DecodeError::NegativeLength(length.into()))?;
+        if length * 4 > self.remaining() {
+            return Err(DecodeError::OverlongLength {
+                length: length * 4,
+                remaining: self.remaining(),
+            });
         }
-        string.retain(|x| x != '\0' && x != '\u{b}');
-        string
+
+        Ok((0..length).map(|_| self.get_i32_le()).collect())
     }
+}
 
-    fn with_header(&mut self, id: i16, f: impl FnOnce(&mut Self)) {
-        // record start and put header
-        let start = self.len();
-        self.put_header(id);
+pub type Buffer = BytesMut;
 
-        // run caller provided code
-        f(self);
+/// Number of bytes [`BufMutExt::put_uleb`] writes for `len`
+pub fn uleb_len(len: usize) -> usize {
+    // every byte holds 7 bits, and 0 still takes a byte
+    (usize::BITS - len.leading_zeros()).max(1).div_ceil(7) as usize
+}
 
-        // cleanup
-        self.fix_header(start);
+/// Number of bytes [`BufMutExt::put_string`] writes for `string`
+pub fn string_len(string: &str) -> usize {
+    1 + uleb_len(string.len()) + string.len()
+}
+
+/// Number of bytes [`BufMutExt::put_optional_string`] writes for `string`
+pub fn optional_string_len(string: Option<&str>) -> usize {
+    string.map_or(1, string_len)
+}
+
+/// Number of bytes [`BufMutExt::put_i32_list`] writes for `list`
+pub fn i32_list_len(list: &[i32]) -> usize {
+    2 + 4 * list.len()
+}
+
+/// A value which can be written into a packet payload
+///
+/// Usually derived, see [`bancho_packet_derive`] for the attributes.
+pub trait BanchoSerialize {
+    fn serialize<B: BufMut>(&self, buf: &mut B);
+
+    /// Number of bytes [`BanchoSerialize::serialize`] writes, so a buffer can be sized before writing into it
+    fn encoded_len(&self) -> usize;
+}
+
+/// A value which can be read out of a packet payload
+pub trait BanchoDeserialize: Sized {
+    fn deserialize(buf: &mut Bytes) -> Result<Self, DecodeError>;
+}
+
+/// A struct which is the whole payload of a packet, set with `#[bancho(id = ...)]`
+pub trait BanchoPacket: BanchoSerialize {
+    const ID: PacketIDs;
+
+    /// Length of the whole packet, header included
+    fn packet_len(&self) -> usize {
+        HEADER_LEN + self.encoded_len()
+    }
+
+    /// Write the value as a full packet into any buffer, which needs room for [`BanchoPacket::packet_len`] bytes
+    fn write_packet_into<B: BufMut>(&self, buf: &mut B) -> Result<(), EncodeError> {
+        buf.put_packet_header(Self::ID as i16, self.encoded_len())?;
+        self.serialize(buf);
+        Ok(())
+    }
+
+    /// Write the value as a full packet, header included
+    fn write_packet(&self, buf: &mut Buffer) {
+        buf.reserve(self.packet_len());
+        buf.with_header(Self::ID as i16, |buf| self.serialize(buf));
+    }
+
+    /// Encode the packet on its own, allocating exactly once, to be shared between everyone it's sent to
+    fn to_packet_bytes(&self) -> Bytes {
+        let mut buf = Buffer::with_capacity(self.packet_len());
+        self.write_packet(&mut buf);
+        buf.freeze()
     }
 }
 
-impl BytesExt for Bytes {
-    // TODO: This doesnt need to copy, could just slice into the buf
-    fn take_while(&mut self, mut f: impl FnMut(u8) -> bool) -> Bytes {
-        let mut len = 0;
-        while let Some(b) = self.get(len) {
-            if !f(*b) {
-                break;
+macro_rules! impl_primitive {
+    ($($ty:ty => $put:ident, $read:ident;)*) => {
+        $(
+            impl BanchoSerialize for $ty {
+                fn serialize<B: BufMut>(&self, buf: &mut B) {
+                    buf.$put(*self);
+                }
+
+                fn encoded_len(&self) -> usize {
+                    std::mem::size_of::<$ty>()
+                }
             }
-            len += 1;
-        }
 
-        self.copy_to_bytes(len)
+            impl BanchoDeserialize for $ty {
+                fn deserialize(buf: &mut Bytes) -> Result<Self, DecodeError> {
+                    buf.$read()
+                }
+            }
+        )*
+    };
+}
+
+impl_primitive! {
+    u8 => put_u8, read_u8;
+    bool => put_bool, get_bool;
+    i16 => put_i16_le, read_i16;
+    u16 => put_u16_le, read_u16;
+    i32 => put_i32_le, read_i32;
+    u32 => put_u32_le, read_u32;
+    i64 => put_i64_le, read_i64;
+    f32 => put_f32_le, read_f32;
+    f64 => put_f64_le, read_f64;
+}
+
+impl BanchoSerialize for String {
+    fn serialize<B: BufMut>(&self, buf: &mut B) {
+        buf.put_string(self);
+    }
+
+    fn encoded_len(&self) -> usize {
+        string_len(self)
     }
 }
 
-pub type Buffer = BytesMut;
+impl BanchoDeserialize for String {
+    fn deserialize(buf: &mut Bytes) -> Result<Self, DecodeError> {
+        buf.get_string()
+    }
+}
+
+impl BanchoSerialize for ByteStr {
+    fn serialize<B: BufMut>(&self, buf: &mut B) {
+        buf.put_string(self);
+    }
+
+    fn encoded_len(&self) -> usize {
+        string_len(self)
+    }
+}
+
+impl BanchoDeserialize for ByteStr {
+    fn deserialize(buf: &mut Bytes) -> Result<Self, DecodeError> {
+        buf.get_str()
+    }
+}
+
+impl BanchoSerialize for Option<String> {
+    fn serialize<B: BufMut>(&self, buf: &mut B) {
+        buf.put_optional_string(self.as_deref());
+    }
+
+    fn encoded_len(&self) -> usize {
+        optional_string_len(self.as_deref())
+    }
+}
+
+impl BanchoDeserialize for Option<String> {
+    fn deserialize(buf: &mut Bytes) -> Result<Self, DecodeError> {
+        buf.get_optional_string()
+    }
+}
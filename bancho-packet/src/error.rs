@@ -0,0 +1,65 @@
+use thiserror::Error;
+
+/// An error encountered while decoding a packet
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum DecodeError {
+    #[error("unexpected end of packet")]
+    UnexpectedEof,
+
+    #[error("unknown packet id {}", .0)]
+    UnknownPacket(i16),
+
+    #[error("uleb128 value does not fit in a usize")]
+    BadUleb,
+
+    #[error("bad utf-8 in string")]
+    BadUtf8,
+
+    #[error("bad string marker {:#04x}", .0)]
+    BadStringMarker(u8),
+
+    #[error("length {} is longer than the remaining {} bytes", .length, .remaining)]
+    OverlongLength { length: usize, remaining: usize },
+
+    #[error("bad integer in login data")]
+    BadInteger,
+
+    #[error("could not decompress payload")]
+    BadCompression,
+
+    #[error("unknown {} {}", .kind, .value)]
+    UnknownValue { kind: &'static str, value: i32 },
+
+    #[error("bad client version in login data")]
+    BadClientVersion,
+
+    #[error("bad client hashes in login data")]
+    BadClientHashes,
+
+    #[error("not a packet capture")]
+    BadCaptureMagic,
+
+    #[error("negative list length {}", .0)]
+    NegativeLength(i32),
+}
+
+/// An error encountered while encoding a packet
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum EncodeError {
+    #[error("payload of {} bytes does not fit in a packet header", .0)]
+    PayloadTooLong(usize),
+}
+
+/// An error from one of the tokio codecs
+#[cfg(feature = "codec")]
+#[derive(Debug, Error)]
+pub enum CodecError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error(transparent)]
+    Decode(#[from] DecodeError),
+
+    #[error("packet of {} bytes is over the limit of {} bytes", .length, .max)]
+    TooLong { length: usize, max: usize },
+}
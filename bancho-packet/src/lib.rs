@@ -1,2 +1,8 @@
+// lets the derives refer to `::bancho_packet` from inside this crate too
+extern crate self as bancho_packet;
+
+pub use bytes;
+
 pub mod buffer;
-pub mod packets;
\ No newline at end of file
+pub mod error;
+pub mod packets;
@@ -0,0 +1,164 @@
+//! A file format for recording the bodies a server sent and received, to dissect or replay later
+//!
+//! A capture starts with [`MAGIC`] and a version byte, followed by back to back records:
+//!
+//! | field     | type          |                                                      |
+//! |-----------|---------------|------------------------------------------------------|
+//! | kind      | u8            | see [`RecordKind`]                                   |
+//! | timestamp | i64           | microseconds since the unix epoch                    |
+//! | token     | string        | the session the body belongs to, in the osu! format  |
+//! | length    | u32           |                                                      |
+//! | body      | `length` bytes | the whole body, usually several packets back to back |
+//!
+//! All integers are little endian, the same as packets.
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use bytes::{Buf, BufMut, Bytes};
+
+use crate::buffer::byte_str::ByteStr;
+use crate::buffer::serialization::{BufMutExt, Buffer, BytesExt};
+use crate::error::DecodeError;
+
+pub const MAGIC: &[u8; 4] = b"BCAP";
+pub const VERSION: u8 = 1;
+
+/// Which way a body went, and whether it's the plain text login rather than packets
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum RecordKind {
+    /// The login request, which is plain text
+    Login = 0,
+    /// Packets from the client
+    Request = 1,
+    /// Packets from the server, including the reply to the login
+    Response = 2,
+}
+
+impl RecordKind {
+    /// Whether the body came from the client
+    pub fn from_client(self) -> bool {
+        matches!(self, RecordKind::Login | RecordKind::Request)
+    }
+}
+
+impl TryFrom<u8> for RecordKind {
+    type Error = DecodeError;
+
+    fn try_from(value: u8) -> Result<Self, DecodeError> {
+        match value {
+            0 => Ok(RecordKind::Login),
+            1 => Ok(RecordKind::Request),
+            2 => Ok(RecordKind::Response),
+            _ => Err(DecodeError::UnknownValue {
+                kind: "RecordKind",
+                value: value as i32,
+            }),
+        }
+    }
+}
+
+/// A single body sent one way over a session
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    pub kind: RecordKind,
+    /// Microseconds since the unix epoch
+    pub timestamp: i64,
+    pub token: ByteStr,
+    pub body: Bytes,
+}
+
+impl Record {
+    /// A record of `body`, timestamped now
+    pub fn now(kind: RecordKind, token: ByteStr, body: Bytes) -> Self {
+        let since_epoch = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        Record {
+            kind,
+            timestamp: since_epoch.as_micros() as i64,
+            token,
+            body,
+        }
+    }
+
+    pub fn time(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_micros(self.timestamp.max(0) as u64)
+    }
+
+    pub fn write(&self, buf: &mut Buffer) {
+        buf.put_u8(self.kind as u8);
+        buf.put_i64_le(self.timestamp);
+        buf.put_string(&self.token);
+        buf.put_u32_le(self.body.len() as u32);
+        buf.put_slice(&self.body);
+    }
+
+    pub fn read(buf: &mut Bytes) -> Result<Self, DecodeError> {
+        let kind = buf.read_u8()?.try_into()?;
+        let timestamp = buf.read_i64()?;
+        let token = buf.get_str()?;
+        let length = buf.read_u32()? as usize;
+        if length > buf.remaining() {
+            return Err(DecodeError::OverlongLength {
+                length,
+                remaining: buf.remaining(),
+            });
+        }
+
+        Ok(Record {
+            kind,
+            timestamp,
+            token,
+            body: buf.split_to(length),
+        })
+    }
+}
+
+/// Write the header a capture starts with, before any records
+pub fn write_header(buf: &mut Buffer) {
+    buf.put_slice(MAGIC);
+    buf.put_u8(VERSION);
+}
+
+/// Reads the records out of a whole capture file
+///
+/// Each record shares the buffer the capture was read into.
+pub struct CaptureReader {
+    buf: Bytes,
+}
+
+impl CaptureReader {
+    /// Check the header, leaving the reader at the first record
+    pub fn new(mut buf: Bytes) -> Result<Self, DecodeError> {
+        buf.ensure(MAGIC.len() + 1)?;
+        if !buf.starts_with(MAGIC) {
+            return Err(DecodeError::BadCaptureMagic);
+        }
+        buf.advance(MAGIC.len());
+
+        match buf.get_u8() {
+            VERSION => Ok(CaptureReader { buf }),
+            version => Err(DecodeError::UnknownValue {
+                kind: "capture version",
+                value: version as i32,
+            }),
+        }
+    }
+}
+
+impl Iterator for CaptureReader {
+    type Item = Result<Record, DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.buf.has_remaining() {
+            return None;
+        }
+
+        let record = Record::read(&mut self.buf);
+        if record.is_err() {
+            // a truncated capture, usually the server was killed mid write
+            self.buf.clear();
+        }
+        Some(record)
+    }
+}
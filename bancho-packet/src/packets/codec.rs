@@ -0,0 +1,126 @@
+use bytes::{Buf, Bytes, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::error::{CodecError, DecodeError};
+use crate::packets::framer::{Frame, PacketFramer, HEADER_LEN};
+use crate::packets::packet::Packet;
+
+/// Largest payload the codecs accept by default, anything bigger is treated as a broken stream
+pub const DEFAULT_MAX_LENGTH: usize = 8 * 1024 * 1024;
+
+/// Splits a stream into [`Frame`]s, and writes already encoded packets back out
+///
+/// Servers should prefer this over [`BanchoCodec`], since a payload that fails to decode doesn't end the stream.
+/// Like [`PacketFramer`], a frame whose payload fails to decompress is handed back as an `Err` item,
+/// only a broken header or an overlong length end the stream.
+#[derive(Debug, Clone)]
+pub struct FrameCodec {
+    max_length: usize,
+}
+
+impl FrameCodec {
+    pub fn new() -> Self {
+        Self::with_max_length(DEFAULT_MAX_LENGTH)
+    }
+
+    pub fn with_max_length(max_length: usize) -> Self {
+        FrameCodec { max_length }
+    }
+}
+
+impl Default for FrameCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Decoder for FrameCodec {
+    type Item = Result<Frame, DecodeError>;
+    type Error = CodecError;
+
+    fn decode(
+        &mut self,
+        src: &mut BytesMut,
+    ) -> Result<Option<Result<Frame, DecodeError>>, CodecError> {
+        if src.len() < HEADER_LEN {
+            return Ok(None);
+        }
+
+        let length = (&src[3..HEADER_LEN]).get_u32_le() as usize;
+        if length > self.max_length {
+            return Err(CodecError::TooLong {
+                length,
+                max: self.max_length,
+            });
+        }
+
+        let frame_len = HEADER_LEN + length;
+        if src.len() < frame_len {
+            src.reserve(frame_len - src.len());
+            return Ok(None);
+        }
+
+        // the frame is split off the stream first, so one that fails to decompress doesn't throw off the next
+        let mut frame = src.split_to(frame_len).freeze();
+        Ok(Some(PacketFramer::next_frame(&mut frame)))
+    }
+}
+
+/// Bytes passed in are expected to already be whole packets, such as a player's queued buffer
+impl Encoder<Bytes> for FrameCodec {
+    type Error = CodecError;
+
+    fn encode(&mut self, packets: Bytes, dst: &mut BytesMut) -> Result<(), CodecError> {
+        dst.extend_from_slice(&packets);
+        Ok(())
+    }
+}
+
+/// Reads and writes typed [`Packet`]s over a stream
+///
+/// Any packet which fails to decode is returned as an error, which ends a `Framed` stream.
+#[derive(Debug, Clone, Default)]
+pub struct BanchoCodec {
+    frames: FrameCodec,
+}
+
+impl BanchoCodec {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_max_length(max_length: usize) -> Self {
+        BanchoCodec {
+            frames: FrameCodec::with_max_length(max_length),
+        }
+    }
+}
+
+impl Decoder for BanchoCodec {
+    type Item = Packet;
+    type Error = CodecError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Packet>, CodecError> {
+        match self.frames.decode(src)? {
+            Some(frame) => Ok(Some(Packet::from_frame(frame?)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+impl Encoder<&Packet> for BanchoCodec {
+    type Error = CodecError;
+
+    fn encode(&mut self, packet: &Packet, dst: &mut BytesMut) -> Result<(), CodecError> {
+        packet.encode(dst);
+        Ok(())
+    }
+}
+
+impl Encoder<Packet> for BanchoCodec {
+    type Error = CodecError;
+
+    fn encode(&mut self, packet: Packet, dst: &mut BytesMut) -> Result<(), CodecError> {
+        self.encode(&packet, dst)
+    }
+}
@@ -0,0 +1,48 @@
+use std::io::{Read, Write};
+
+use bytes::Bytes;
+use flate2::{
+    read::{DeflateDecoder, GzDecoder, ZlibDecoder},
+    write::GzEncoder,
+    Compression,
+};
+
+use crate::error::DecodeError;
+
+/// Largest payload a compressed frame is allowed to inflate to, so a tiny frame can't blow up into gigabytes
+pub const MAX_DECOMPRESSED_LEN: usize = 16 * 1024 * 1024;
+
+/// Inflate the payload of a frame with the compression flag set
+///
+/// Clients have sent gzip, zlib and raw deflate streams over the years, so the format is picked from the first bytes.
+pub fn decompress(payload: &[u8]) -> Result<Bytes, DecodeError> {
+    match payload {
+        [0x1f, 0x8b, ..] => inflate(GzDecoder::new(payload)),
+        // a zlib header is a deflate method byte (0x?8), with the two header bytes being a multiple of 31
+        [cmf, flg, ..] if cmf & 0x0f == 8 && u16::from_be_bytes([*cmf, *flg]) % 31 == 0 => {
+            inflate(ZlibDecoder::new(payload))
+        }
+        _ => inflate(DeflateDecoder::new(payload)),
+    }
+}
+
+fn inflate(decoder: impl Read) -> Result<Bytes, DecodeError> {
+    let mut out = Vec::new();
+    decoder
+        .take(MAX_DECOMPRESSED_LEN as u64 + 1)
+        .read_to_end(&mut out)
+        .map_err(|_| DecodeError::BadCompression)?;
+
+    if out.len() > MAX_DECOMPRESSED_LEN {
+        return Err(DecodeError::BadCompression);
+    }
+    Ok(out.into())
+}
+
+/// Gzip a payload, the format osu! itself uses for compressed packets
+pub fn compress(payload: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    // writing into a Vec can't fail
+    encoder.write_all(payload).unwrap();
+    encoder.finish().unwrap()
+}
@@ -0,0 +1,81 @@
+use bytes::{Buf, Bytes};
+
+use crate::buffer::serialization::BytesExt;
+use crate::error::DecodeError;
+use crate::packets::compression;
+
+/// Length of the header in front of every packet: i16 id, u8 compression, u32 length
+pub const HEADER_LEN: usize = 7;
+
+/// A single packet split out of a body, with its payload left undecoded
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub id: i16,
+    /// Whether the payload was compressed on the wire, `payload` is always decompressed
+    pub compressed: bool,
+    pub payload: Bytes,
+}
+
+/// Splits a body made up of back to back packets into [`Frame`]s, using the length in each header
+///
+/// Each frame owns exactly its own payload, so a payload that fails to decode can't throw off the frames after it.
+/// If a frame isn't fully present the framer yields an error and stops.
+/// Payloads with the compression flag set are decompressed, see [`compression::decompress`].
+/// One that fails to decompress is yielded as [`DecodeError::BadCompression`], and the frames after it still follow.
+pub struct PacketFramer {
+    buf: Bytes,
+}
+
+impl PacketFramer {
+    pub fn new(buf: Bytes) -> Self {
+        PacketFramer { buf }
+    }
+
+    /// Split the next frame off the front of `buf`
+    pub fn next_frame(buf: &mut Bytes) -> Result<Frame, DecodeError> {
+        buf.ensure(HEADER_LEN)?;
+
+        let id = buf.get_i16_le();
+        let compressed = buf.get_u8() == 1;
+        let length = buf.get_u32_le() as usize;
+
+        if length > buf.remaining() {
+            return Err(DecodeError::OverlongLength {
+                length,
+                remaining: buf.remaining(),
+            });
+        }
+
+        let payload = buf.split_to(length);
+        // only fail after splitting, so the frames after this one are still lined up
+        let payload = match compressed {
+            true => compression::decompress(&payload)?,
+            false => payload,
+        };
+
+        Ok(Frame {
+            id,
+            compressed,
+            payload,
+        })
+    }
+}
+
+impl Iterator for PacketFramer {
+    type Item = Result<Frame, DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.buf.has_remaining() {
+            return None;
+        }
+
+        let frame = Self::next_frame(&mut self.buf);
+        match frame {
+            // the payload was split off before decompressing it, so the next header is still lined up
+            Ok(_) | Err(DecodeError::BadCompression) => {}
+            // the rest of the body can't be trusted to line up with a header
+            Err(_) => self.buf.clear(),
+        }
+        Some(frame)
+    }
+}
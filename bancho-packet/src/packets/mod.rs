@@ -1,4 +1,11 @@
 pub mod reader;
 pub mod writer;
+pub mod capture;
+#[cfg(feature = "codec")]
+pub mod codec;
+pub mod compression;
+pub mod framer;
+pub mod mods;
+pub mod packet;
 pub mod packet_ids;
 pub mod structures;
\ No newline at end of file
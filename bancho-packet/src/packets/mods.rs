@@ -0,0 +1,229 @@
+use std::{fmt, str::FromStr};
+
+use bitflags::bitflags;
+use bytes::{BufMut, Bytes};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::buffer::serialization::{BanchoDeserialize, BanchoSerialize, BytesExt};
+use crate::error::DecodeError;
+
+bitflags! {
+    /// Gameplay mods, as sent in statuses, scores and matches
+    ///
+    /// Formats as the acronyms shown in game, such as `HDDTRX`, and can be parsed back from them.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+    #[serde(from = "u32", into = "u32")]
+    pub struct Mods: u32 {
+        const NO_FAIL = 1 << 0;
+        const EASY = 1 << 1;
+        const TOUCH_DEVICE = 1 << 2;
+        const HIDDEN = 1 << 3;
+        const HARD_ROCK = 1 << 4;
+        const SUDDEN_DEATH = 1 << 5;
+        const DOUBLE_TIME = 1 << 6;
+        const RELAX = 1 << 7;
+        const HALF_TIME = 1 << 8;
+        /// Always sent along with `DOUBLE_TIME`
+        const NIGHTCORE = 1 << 9;
+        const FLASHLIGHT = 1 << 10;
+        const AUTOPLAY = 1 << 11;
+        const SPUN_OUT = 1 << 12;
+        const AUTOPILOT = 1 << 13;
+        /// Always sent along with `SUDDEN_DEATH`
+        const PERFECT = 1 << 14;
+        const KEY_4 = 1 << 15;
+        const KEY_5 = 1 << 16;
+        const KEY_6 = 1 << 17;
+        const KEY_7 = 1 << 18;
+        const KEY_8 = 1 << 19;
+        const FADE_IN = 1 << 20;
+        const RANDOM = 1 << 21;
+        const CINEMA = 1 << 22;
+        const TARGET = 1 << 23;
+        const KEY_9 = 1 << 24;
+        const KEY_COOP = 1 << 25;
+        const KEY_1 = 1 << 26;
+        const KEY_3 = 1 << 27;
+        const KEY_2 = 1 << 28;
+        const SCORE_V2 = 1 << 29;
+        const MIRROR = 1 << 30;
+
+        const KEY_MODS = Self::KEY_1.bits()
+            | Self::KEY_2.bits()
+            | Self::KEY_3.bits()
+            | Self::KEY_4.bits()
+            | Self::KEY_5.bits()
+            | Self::KEY_6.bits()
+            | Self::KEY_7.bits()
+            | Self::KEY_8.bits()
+            | Self::KEY_9.bits();
+    }
+}
+
+/// Acronyms in the order they are shown, which is also bit order
+const ACRONYMS: &[(Mods, &str)] = &[
+    (Mods::NO_FAIL, "NF"),
+    (Mods::EASY, "EZ"),
+    (Mods::TOUCH_DEVICE, "TD"),
+    (Mods::HIDDEN, "HD"),
+    (Mods::HARD_ROCK, "HR"),
+    (Mods::SUDDEN_DEATH, "SD"),
+    (Mods::DOUBLE_TIME, "DT"),
+    (Mods::RELAX, "RX"),
+    (Mods::HALF_TIME, "HT"),
+    (Mods::NIGHTCORE, "NC"),
+    (Mods::FLASHLIGHT, "FL"),
+    (Mods::AUTOPLAY, "AT"),
+    (Mods::SPUN_OUT, "SO"),
+    (Mods::AUTOPILOT, "AP"),
+    (Mods::PERFECT, "PF"),
+    (Mods::KEY_4, "4K"),
+    (Mods::KEY_5, "5K"),
+    (Mods::KEY_6, "6K"),
+    (Mods::KEY_7, "7K"),
+    (Mods::KEY_8, "8K"),
+    (Mods::FADE_IN, "FI"),
+    (Mods::RANDOM, "RD"),
+    (Mods::CINEMA, "CN"),
+    (Mods::TARGET, "TP"),
+    (Mods::KEY_9, "9K"),
+    (Mods::KEY_COOP, "CO"),
+    (Mods::KEY_1, "1K"),
+    (Mods::KEY_3, "3K"),
+    (Mods::KEY_2, "2K"),
+    (Mods::SCORE_V2, "V2"),
+    (Mods::MIRROR, "MR"),
+];
+
+/// Pairs of mods which the game never allows together
+const INCOMPATIBLE: &[(Mods, Mods)] = &[
+    (Mods::EASY, Mods::HARD_ROCK),
+    (Mods::HALF_TIME, Mods::DOUBLE_TIME),
+    (Mods::NO_FAIL, Mods::SUDDEN_DEATH),
+    (Mods::NO_FAIL, Mods::RELAX),
+    (Mods::NO_FAIL, Mods::AUTOPILOT),
+    (Mods::RELAX, Mods::AUTOPILOT),
+    (Mods::RELAX, Mods::AUTOPLAY),
+    (Mods::AUTOPILOT, Mods::AUTOPLAY),
+    (Mods::AUTOPILOT, Mods::SPUN_OUT),
+    (Mods::SUDDEN_DEATH, Mods::AUTOPLAY),
+    (Mods::SUDDEN_DEATH, Mods::RELAX),
+    (Mods::SUDDEN_DEATH, Mods::AUTOPILOT),
+    (Mods::HIDDEN, Mods::FADE_IN),
+];
+
+/// Mods which can't be played together, or a mod sent without the one it implies
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum InvalidMods {
+    #[error("{} can't be combined with {}", .0, .1)]
+    Incompatible(Mods, Mods),
+
+    #[error("{} was sent without {}", .0, .1)]
+    MissingImplied(Mods, Mods),
+
+    #[error("only one key mod can be used at once, got {}", .0)]
+    MultipleKeyMods(Mods),
+}
+
+impl Mods {
+    /// Check the mods could have come from a real client
+    pub fn validate(self) -> Result<(), InvalidMods> {
+        for (implied_by, implied) in [
+            (Mods::NIGHTCORE, Mods::DOUBLE_TIME),
+            (Mods::PERFECT, Mods::SUDDEN_DEATH),
+        ] {
+            if self.contains(implied_by) && !self.contains(implied) {
+                return Err(InvalidMods::MissingImplied(implied_by, implied));
+            }
+        }
+
+        for &(a, b) in INCOMPATIBLE {
+            if self.contains(a) && self.contains(b) {
+                return Err(InvalidMods::Incompatible(a, b));
+            }
+        }
+
+        let keys = self & Mods::KEY_MODS;
+        if keys.bits().count_ones() > 1 {
+            return Err(InvalidMods::MultipleKeyMods(keys));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Mods {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for &(mods, acronym) in ACRONYMS {
+            // NC and PF already imply DT and SD, so don't show those twice
+            let shown_by_other = (mods == Mods::DOUBLE_TIME && self.contains(Mods::NIGHTCORE))
+                || (mods == Mods::SUDDEN_DEATH && self.contains(Mods::PERFECT));
+            if self.contains(mods) && !shown_by_other {
+                f.write_str(acronym)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The acronym which couldn't be parsed into a mod
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("unknown mod acronym `{}`", .0)]
+pub struct UnknownMod(pub String);
+
+impl FromStr for Mods {
+    type Err = UnknownMod;
+
+    fn from_str(acronyms: &str) -> Result<Self, UnknownMod> {
+        let mut mods = Mods::empty();
+        let mut rest = acronyms;
+        while !rest.is_empty() {
+            let acronym = rest.get(..2).ok_or_else(|| UnknownMod(rest.to_string()))?;
+            let (found, _) = ACRONYMS
+                .iter()
+                .find(|(_, known)| known.eq_ignore_ascii_case(acronym))
+                .ok_or_else(|| UnknownMod(acronym.to_string()))?;
+
+            mods |= *found;
+            rest = &rest[2..];
+        }
+
+        // match what the client sends for the mods which imply another
+        if mods.contains(Mods::NIGHTCORE) {
+            mods |= Mods::DOUBLE_TIME;
+        }
+        if mods.contains(Mods::PERFECT) {
+            mods |= Mods::SUDDEN_DEATH;
+        }
+        Ok(mods)
+    }
+}
+
+impl From<u32> for Mods {
+    fn from(bits: u32) -> Self {
+        // keep bits we don't know about, so they're passed along untouched
+        Mods::from_bits_retain(bits)
+    }
+}
+
+impl From<Mods> for u32 {
+    fn from(mods: Mods) -> Self {
+        mods.bits()
+    }
+}
+
+impl BanchoSerialize for Mods {
+    fn serialize<B: BufMut>(&self, buf: &mut B) {
+        buf.put_u32_le(self.bits());
+    }
+
+    fn encoded_len(&self) -> usize {
+        4
+    }
+}
+
+impl BanchoDeserialize for Mods {
+    fn deserialize(buf: &mut Bytes) -> Result<Self, DecodeError> {
+        buf.read_u32().map(Mods::from)
+    }
+}
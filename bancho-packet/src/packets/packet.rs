@@ -0,0 +1,751 @@
+use bytes::{BufMut, Bytes};
+
+use crate::buffer::byte_str::ByteStr;
+use crate::buffer::serialization::{
+    i32_list_len, string_len, BanchoDeserialize, BanchoSerialize, BufMutExt, Buffer, BytesExt,
+    BytesMutExt,
+};
+use crate::error::{DecodeError, EncodeError};
+use crate::packets::framer::{Frame, PacketFramer, HEADER_LEN};
+use crate::packets::mods::Mods;
+use crate::packets::packet_ids::PacketIDs;
+use crate::packets::structures::{
+    BanchoChannel, BanchoMessage, BanchoPresence, BanchoPrivileges, BanchoStats, BeatmapInfoReply,
+    ClientStatus, Match, ProtocolVersion, ReplayFrameBundle, ScoreFrame,
+};
+use crate::packets::{reader, writer};
+
+/// Every packet in the protocol, along with its payload
+///
+/// Payloads which don't have a structured codec yet are carried as raw `Bytes`,
+/// so they can still be passed through untouched.
+/// Payloads whose layout changed between protocol versions use the one from [`ProtocolVersion::LATEST`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Packet {
+    ClientSendUserStatus(ClientStatus),
+    ClientSendIrcMessage(BanchoMessage),
+    ClientExit(i32),
+    ClientRequestStatusUpdate,
+    ClientPong,
+    BanchoLoginReply(i32),
+    BanchoCommandError,
+    BanchoSendMessage(BanchoMessage),
+    BanchoPing,
+    BanchoHandleIrcChangeUsername(ByteStr),
+    BanchoHandleIrcQuit(ByteStr),
+    BanchoHandleOsuUpdate(BanchoStats),
+    BanchoHandleUserQuit(i32),
+    BanchoSpectatorJoined(i32),
+    BanchoSpectatorLeft(i32),
+    BanchoSpectateFrames(ReplayFrameBundle),
+    ClientStartSpectating(i32),
+    ClientStopSpectating,
+    ClientSpectateFrames(ReplayFrameBundle),
+    BanchoVersionUpdate,
+    ClientErrorReport(ByteStr),
+    ClientCantSpectate,
+    BanchoSpectatorCantSpectate(i32),
+    BanchoGetAttention,
+    BanchoAnnounce(ByteStr),
+    ClientSendIrcMessagePrivate(BanchoMessage),
+    BanchoMatchUpdate(Match),
+    BanchoMatchNew(Match),
+    BanchoMatchDisband(i32),
+    ClientLobbyPart,
+    ClientLobbyJoin,
+    ClientMatchCreate(Match),
+    ClientMatchJoin { match_id: i32, password: ByteStr },
+    ClientMatchPart,
+    BanchoMatchJoinSuccess(Match),
+    BanchoMatchJoinFail,
+    ClientMatchChangeSlot(i32),
+    ClientMatchReady,
+    ClientMatchLock(i32),
+    ClientMatchChangeSettings(Match),
+    BanchoFellowSpectatorJoined(i32),
+    BanchoFellowSpectatorLeft(i32),
+    ClientMatchStart,
+    BanchoMatchStart(Match),
+    ClientMatchScoreUpdate(ScoreFrame),
+    BanchoMatchScoreUpdate(ScoreFrame),
+    ClientMatchComplete,
+    BanchoMatchTransferHost,
+    ClientMatchChangeMods(Mods),
+    ClientMatchLoadComplete,
+    BanchoMatchAllPlayersLoaded,
+    ClientMatchNoBeatmap,
+    ClientMatchNotReady,
+    ClientMatchFailed,
+    BanchoMatchPlayerFailed(i32),
+    BanchoMatchComplete,
+    ClientMatchHasBeatmap,
+    ClientMatchSkipRequest,
+    BanchoMatchSkip,
+    BanchoUnauthorised,
+    ClientChannelJoin(ByteStr),
+    BanchoChannelJoinSuccess(ByteStr),
+    BanchoChannelAvailable(BanchoChannel),
+    BanchoChannelRevoked(ByteStr),
+    BanchoChannelAvailableAutojoin(BanchoChannel),
+    ClientBeatmapInfoRequest(Bytes),
+    BanchoBeatmapInfoReply(BeatmapInfoReply),
+    ClientMatchTransferHost(i32),
+    BanchoLoginPermissions(BanchoPrivileges),
+    BanchoFriendsList(Vec<i32>),
+    ClientFriendAdd(i32),
+    ClientFriendRemove(i32),
+    BanchoProtocolNegotiation(i32),
+    BanchoTitleUpdate(ByteStr),
+    ClientMatchChangeTeam,
+    ClientChannelLeave(ByteStr),
+    ClientReceiveUpdates(i32),
+    BanchoMonitor,
+    BanchoMatchPlayerSkipped(i32),
+    ClientSetIrcAwayMessage(BanchoMessage),
+    BanchoUserPresence(BanchoPresence),
+    ClientUserStatsRequest(Vec<i32>),
+    BanchoRestart(i32),
+    ClientInvite(i32),
+    BanchoInvite(BanchoMessage),
+    BanchoChannelListingComplete,
+    ClientMatchChangePassword(Match),
+    BanchoMatchChangePassword(ByteStr),
+    BanchoBanInfo(u32),
+    ClientSpecialMatchInfoRequest(i32),
+    BanchoUserSilenced(i32),
+    BanchoUserPresenceSingle(i32),
+    BanchoUserPresenceBundle(Vec<i32>),
+    ClientUserPresenceRequest(Vec<i32>),
+    ClientUserPresenceRequestAll(i32),
+    ClientUserToggleBlockNonFriendPm(i32),
+    BanchoUserPmBlocked(BanchoMessage),
+    BanchoTargetIsSilenced(BanchoMessage),
+    BanchoVersionUpdateForced,
+    BanchoSwitchServer(ByteStr),
+    BanchoAccountRestricted,
+    BanchoRTX(ByteStr),
+    ClientMatchAbort,
+    BanchoSwitchTourneyServer(ByteStr),
+    ClientSpecialJoinMatchChannel(i32),
+    ClientSpecialLeaveMatchChannel(i32),
+}
+
+impl Packet {
+    pub fn id(&self) -> PacketIDs {
+        match self {
+            Packet::ClientSendUserStatus(_) => PacketIDs::ClientSendUserStatus,
+            Packet::ClientSendIrcMessage(_) => PacketIDs::ClientSendIrcMessage,
+            Packet::ClientExit(_) => PacketIDs::ClientExit,
+            Packet::ClientRequestStatusUpdate => PacketIDs::ClientRequestStatusUpdate,
+            Packet::ClientPong => PacketIDs::ClientPong,
+            Packet::BanchoLoginReply(_) => PacketIDs::BanchoLoginReply,
+            Packet::BanchoCommandError => PacketIDs::BanchoCommandError,
+            Packet::BanchoSendMessage(_) => PacketIDs::BanchoSendMessage,
+            Packet::BanchoPing => PacketIDs::BanchoPing,
+            Packet::BanchoHandleIrcChangeUsername(_) => PacketIDs::BanchoHandleIrcChangeUsername,
+            Packet::BanchoHandleIrcQuit(_) => PacketIDs::BanchoHandleIrcQuit,
+            Packet::BanchoHandleOsuUpdate(_) => PacketIDs::BanchoHandleOsuUpdate,
+            Packet::BanchoHandleUserQuit(_) => PacketIDs::BanchoHandleUserQuit,
+            Packet::BanchoSpectatorJoined(_) => PacketIDs::BanchoSpectatorJoined,
+            Packet::BanchoSpectatorLeft(_) => PacketIDs::BanchoSpectatorLeft,
+            Packet::BanchoSpectateFrames(_) => PacketIDs::BanchoSpectateFrames,
+            Packet::ClientStartSpectating(_) => PacketIDs::ClientStartSpectating,
+            Packet::ClientStopSpectating => PacketIDs::ClientStopSpectating,
+            Packet::ClientSpectateFrames(_) => PacketIDs::ClientSpectateFrames,
+            Packet::BanchoVersionUpdate => PacketIDs::BanchoVersionUpdate,
+            Packet::ClientErrorReport(_) => PacketIDs::ClientErrorReport,
+            Packet::ClientCantSpectate => PacketIDs::ClientCantSpectate,
+            Packet::BanchoSpectatorCantSpectate(_) => PacketIDs::BanchoSpectatorCantSpectate,
+            Packet::BanchoGetAttention => PacketIDs::BanchoGetAttention,
+            Packet::BanchoAnnounce(_) => PacketIDs::BanchoAnnounce,
+            Packet::ClientSendIrcMessagePrivate(_) => PacketIDs::ClientSendIrcMessagePrivate,
+            Packet::BanchoMatchUpdate(_) => PacketIDs::BanchoMatchUpdate,
+            Packet::BanchoMatchNew(_) => PacketIDs::BanchoMatchNew,
+            Packet::BanchoMatchDisband(_) => PacketIDs::BanchoMatchDisband,
+            Packet::ClientLobbyPart => PacketIDs::ClientLobbyPart,
+            Packet::ClientLobbyJoin => PacketIDs::ClientLobbyJoin,
+            Packet::ClientMatchCreate(_) => PacketIDs::ClientMatchCreate,
+            Packet::ClientMatchJoin { .. } => PacketIDs::ClientMatchJoin,
+            Packet::ClientMatchPart => PacketIDs::ClientMatchPart,
+            Packet::BanchoMatchJoinSuccess(_) => PacketIDs::BanchoMatchJoinSuccess,
+            Packet::BanchoMatchJoinFail => PacketIDs::BanchoMatchJoinFail,
+            Packet::ClientMatchChangeSlot(_) => PacketIDs::ClientMatchChangeSlot,
+            Packet::ClientMatchReady => PacketIDs::ClientMatchReady,
+            Packet::ClientMatchLock(_) => PacketIDs::ClientMatchLock,
+            Packet::ClientMatchChangeSettings(_) => PacketIDs::ClientMatchChangeSettings,
+            Packet::BanchoFellowSpectatorJoined(_) => PacketIDs::BanchoFellowSpectatorJoined,
+            Packet::BanchoFellowSpectatorLeft(_) => PacketIDs::BanchoFellowSpectatorLeft,
+            Packet::ClientMatchStart => PacketIDs::ClientMatchStart,
+            Packet::BanchoMatchStart(_) => PacketIDs::BanchoMatchStart,
+            Packet::ClientMatchScoreUpdate(_) => PacketIDs::ClientMatchScoreUpdate,
+            Packet::BanchoMatchScoreUpdate(_) => PacketIDs::BanchoMatchScoreUpdate,
+            Packet::ClientMatchComplete => PacketIDs::ClientMatchComplete,
+            Packet::BanchoMatchTransferHost => PacketIDs::BanchoMatchTransferHost,
+            Packet::ClientMatchChangeMods(_) => PacketIDs::ClientMatchChangeMods,
+            Packet::ClientMatchLoadComplete => PacketIDs::ClientMatchLoadComplete,
+            Packet::BanchoMatchAllPlayersLoaded => PacketIDs::BanchoMatchAllPlayersLoaded,
+            Packet::ClientMatchNoBeatmap => PacketIDs::ClientMatchNoBeatmap,
+            Packet::ClientMatchNotReady => PacketIDs::ClientMatchNotReady,
+            Packet::ClientMatchFailed => PacketIDs::ClientMatchFailed,
+            Packet::BanchoMatchPlayerFailed(_) => PacketIDs::BanchoMatchPlayerFailed,
+            Packet::BanchoMatchComplete => PacketIDs::BanchoMatchComplete,
+            Packet::ClientMatchHasBeatmap => PacketIDs::ClientMatchHasBeatmap,
+            Packet::ClientMatchSkipRequest => PacketIDs::ClientMatchSkipRequest,
+            Packet::BanchoMatchSkip => PacketIDs::BanchoMatchSkip,
+            Packet::BanchoUnauthorised => PacketIDs::BanchoUnauthorised,
+            Packet::ClientChannelJoin(_) => PacketIDs::ClientChannelJoin,
+            Packet::BanchoChannelJoinSuccess(_) => PacketIDs::BanchoChannelJoinSuccess,
+            Packet::BanchoChannelAvailable(_) => PacketIDs::BanchoChannelAvailable,
+            Packet::BanchoChannelRevoked(_) => PacketIDs::BanchoChannelRevoked,
+            Packet::BanchoChannelAvailableAutojoin(_) => PacketIDs::BanchoChannelAvailableAutojoin,
+            Packet::ClientBeatmapInfoRequest(_) => PacketIDs::ClientBeatmapInfoRequest,
+            Packet::BanchoBeatmapInfoReply(_) => PacketIDs::BanchoBeatmapInfoReply,
+            Packet::ClientMatchTransferHost(_) => PacketIDs::ClientMatchTransferHost,
+            Packet::BanchoLoginPermissions(_) => PacketIDs::BanchoLoginPermissions,
+            Packet::BanchoFriendsList(_) => PacketIDs::BanchoFriendsList,
+            Packet::ClientFriendAdd(_) => PacketIDs::ClientFriendAdd,
+            Packet::ClientFriendRemove(_) => PacketIDs::ClientFriendRemove,
+            Packet::BanchoProtocolNegotiation(_) => PacketIDs::BanchoProtocolNegotiation,
+            Packet::BanchoTitleUpdate(_) => PacketIDs::BanchoTitleUpdate,
+            Packet::ClientMatchChangeTeam => PacketIDs::ClientMatchChangeTeam,
+            Packet::ClientChannelLeave(_) => PacketIDs::ClientChannelLeave,
+            Packet::ClientReceiveUpdates(_) => PacketIDs::ClientReceiveUpdates,
+            Packet::BanchoMonitor => PacketIDs::BanchoMonitor,
+            Packet::BanchoMatchPlayerSkipped(_) => PacketIDs::BanchoMatchPlayerSkipped,
+            Packet::ClientSetIrcAwayMessage(_) => PacketIDs::ClientSetIrcAwayMessage,
+            Packet::BanchoUserPresence(_) => PacketIDs::BanchoUserPresence,
+            Packet::ClientUserStatsRequest(_) => PacketIDs::ClientUserStatsRequest,
+            Packet::BanchoRestart(_) => PacketIDs::BanchoRestart,
+            Packet::ClientInvite(_) => PacketIDs::ClientInvite,
+            Packet::BanchoInvite(_) => PacketIDs::BanchoInvite,
+            Packet::BanchoChannelListingComplete => PacketIDs::BanchoChannelListingComplete,
+            Packet::ClientMatchChangePassword(_) => PacketIDs::ClientMatchChangePassword,
+            Packet::BanchoMatchChangePassword(_) => PacketIDs::BanchoMatchChangePassword,
+            Packet::BanchoBanInfo(_) => PacketIDs::BanchoBanInfo,
+            Packet::ClientSpecialMatchInfoRequest(_) => PacketIDs::ClientSpecialMatchInfoRequest,
+            Packet::BanchoUserSilenced(_) => PacketIDs::BanchoUserSilenced,
+            Packet::BanchoUserPresenceSingle(_) => PacketIDs::BanchoUserPresenceSingle,
+            Packet::BanchoUserPresenceBundle(_) => PacketIDs::BanchoUserPresenceBundle,
+            Packet::ClientUserPresenceRequest(_) => PacketIDs::ClientUserPresenceRequest,
+            Packet::ClientUserPresenceRequestAll(_) => PacketIDs::ClientUserPresenceRequestAll,
+            Packet::ClientUserToggleBlockNonFriendPm(_) => {
+                PacketIDs::ClientUserToggleBlockNonFriendPm
+            }
+            Packet::BanchoUserPmBlocked(_) => PacketIDs::BanchoUserPmBlocked,
+            Packet::BanchoTargetIsSilenced(_) => PacketIDs::BanchoTargetIsSilenced,
+            Packet::BanchoVersionUpdateForced => PacketIDs::BanchoVersionUpdateForced,
+            Packet::BanchoSwitchServer(_) => PacketIDs::BanchoSwitchServer,
+            Packet::BanchoAccountRestricted => PacketIDs::BanchoAccountRestricted,
+            Packet::BanchoRTX(_) => PacketIDs::BanchoRTX,
+            Packet::ClientMatchAbort => PacketIDs::ClientMatchAbort,
+            Packet::BanchoSwitchTourneyServer(_) => PacketIDs::BanchoSwitchTourneyServer,
+            Packet::ClientSpecialJoinMatchChannel(_) => PacketIDs::ClientSpecialJoinMatchChannel,
+            Packet::ClientSpecialLeaveMatchChannel(_) => PacketIDs::ClientSpecialLeaveMatchChannel,
+        }
+    }
+
+    /// Decode a single packet (header and payload) from the front of `buf`
+    ///
+    /// The whole packet is consumed even if its id is unknown, so the caller can skip it and carry on.
+    pub fn decode(buf: &mut Bytes) -> Result<Packet, DecodeError> {
+        Self::from_frame(PacketFramer::next_frame(buf)?)
+    }
+
+    /// Decode a packet which has already been split out by a [`PacketFramer`]
+    pub fn from_frame(mut frame: Frame) -> Result<Packet, DecodeError> {
+        Self::decode_payload(PacketIDs::try_from(frame.id)?, &mut frame.payload)
+    }
+
+    /// Decode the payload of a packet whose header has already been read
+    pub fn decode_payload(id: PacketIDs, buf: &mut Bytes) -> Result<Packet, DecodeError> {
+        Ok(match id {
+            PacketIDs::ClientSendUserStatus => {
+                Packet::ClientSendUserStatus(reader::read_status(buf)?)
+            }
+            PacketIDs::ClientSendIrcMessage => {
+                Packet::ClientSendIrcMessage(reader::read_message(buf)?)
+            }
+            PacketIDs::ClientExit => Packet::ClientExit(buf.read_i32()?),
+            PacketIDs::ClientRequestStatusUpdate => Packet::ClientRequestStatusUpdate,
+            PacketIDs::ClientPong => Packet::ClientPong,
+            PacketIDs::BanchoLoginReply => Packet::BanchoLoginReply(buf.read_i32()?),
+            PacketIDs::BanchoCommandError => Packet::BanchoCommandError,
+            PacketIDs::BanchoSendMessage => Packet::BanchoSendMessage(reader::read_message(buf)?),
+            PacketIDs::BanchoPing => Packet::BanchoPing,
+            PacketIDs::BanchoHandleIrcChangeUsername => {
+                Packet::BanchoHandleIrcChangeUsername(buf.get_str()?)
+            }
+            PacketIDs::BanchoHandleIrcQuit => Packet::BanchoHandleIrcQuit(buf.get_str()?),
+            PacketIDs::BanchoHandleOsuUpdate => {
+                Packet::BanchoHandleOsuUpdate(reader::read_stats(buf)?)
+            }
+            PacketIDs::BanchoHandleUserQuit => {
+                let player_id = buf.read_i32()?;
+                // quit state, always written as 0
+                buf.read_u8()?;
+                Packet::BanchoHandleUserQuit(player_id)
+            }
+            PacketIDs::BanchoSpectatorJoined => Packet::BanchoSpectatorJoined(buf.read_i32()?),
+            PacketIDs::BanchoSpectatorLeft => Packet::BanchoSpectatorLeft(buf.read_i32()?),
+            PacketIDs::BanchoSpectateFrames => {
+                Packet::BanchoSpectateFrames(reader::read_frame_bundle(buf)?)
+            }
+            PacketIDs::ClientStartSpectating => Packet::ClientStartSpectating(buf.read_i32()?),
+            PacketIDs::ClientStopSpectating => Packet::ClientStopSpectating,
+            PacketIDs::ClientSpectateFrames => {
+                Packet::ClientSpectateFrames(reader::read_frame_bundle(buf)?)
+            }
+            PacketIDs::BanchoVersionUpdate => Packet::BanchoVersionUpdate,
+            PacketIDs::ClientErrorReport => Packet::ClientErrorReport(buf.get_str()?),
+            PacketIDs::ClientCantSpectate => Packet::ClientCantSpectate,
+            PacketIDs::BanchoSpectatorCantSpectate => {
+                Packet::BanchoSpectatorCantSpectate(buf.read_i32()?)
+            }
+            PacketIDs::BanchoGetAttention => Packet::BanchoGetAttention,
+            PacketIDs::BanchoAnnounce => Packet::BanchoAnnounce(buf.get_str()?),
+            PacketIDs::ClientSendIrcMessagePrivate => {
+                Packet::ClientSendIrcMessagePrivate(reader::read_message(buf)?)
+            }
+            PacketIDs::BanchoMatchUpdate => Packet::BanchoMatchUpdate(reader::read_match(buf)?),
+            PacketIDs::BanchoMatchNew => Packet::BanchoMatchNew(reader::read_match(buf)?),
+            PacketIDs::BanchoMatchDisband => Packet::BanchoMatchDisband(buf.read_i32()?),
+            PacketIDs::ClientLobbyPart => Packet::ClientLobbyPart,
+            PacketIDs::ClientLobbyJoin => Packet::ClientLobbyJoin,
+            PacketIDs::ClientMatchCreate => Packet::ClientMatchCreate(reader::read_match(buf)?),
+            PacketIDs::ClientMatchJoin => Packet::ClientMatchJoin {
+                match_id: buf.read_i32()?,
+                password: buf.get_str()?,
+            },
+            PacketIDs::ClientMatchPart => Packet::ClientMatchPart,
+            PacketIDs::BanchoMatchJoinSuccess => {
+                Packet::BanchoMatchJoinSuccess(reader::read_match(buf)?)
+            }
+            PacketIDs::BanchoMatchJoinFail => Packet::BanchoMatchJoinFail,
+            PacketIDs::ClientMatchChangeSlot => Packet::ClientMatchChangeSlot(buf.read_i32()?),
+            PacketIDs::ClientMatchReady => Packet::ClientMatchReady,
+            PacketIDs::ClientMatchLock => Packet::ClientMatchLock(buf.read_i32()?),
+            PacketIDs::ClientMatchChangeSettings => {
+                Packet::ClientMatchChangeSettings(reader::read_match(buf)?)
+            }
+            PacketIDs::BanchoFellowSpectatorJoined => {
+                Packet::BanchoFellowSpectatorJoined(buf.read_i32()?)
+            }
+            PacketIDs::BanchoFellowSpectatorLeft => {
+                Packet::BanchoFellowSpectatorLeft(buf.read_i32()?)
+            }
+            PacketIDs::ClientMatchStart => Packet::ClientMatchStart,
+            PacketIDs::BanchoMatchStart => Packet::BanchoMatchStart(reader::read_match(buf)?),
+            PacketIDs::ClientMatchScoreUpdate => {
+                Packet::ClientMatchScoreUpdate(reader::read_score_frame(buf)?)
+            }
+            PacketIDs::BanchoMatchScoreUpdate => {
+                Packet::BanchoMatchScoreUpdate(reader::read_score_frame(buf)?)
+            }
+            PacketIDs::ClientMatchComplete => Packet::ClientMatchComplete,
+            PacketIDs::BanchoMatchTransferHost => Packet::BanchoMatchTransferHost,
+            PacketIDs::ClientMatchChangeMods => {
+                Packet::ClientMatchChangeMods(Mods::deserialize(buf)?)
+            }
+            PacketIDs::ClientMatchLoadComplete => Packet::ClientMatchLoadComplete,
+            PacketIDs::BanchoMatchAllPlayersLoaded => Packet::BanchoMatchAllPlayersLoaded,
+            PacketIDs::ClientMatchNoBeatmap => Packet::ClientMatchNoBeatmap,
+            PacketIDs::ClientMatchNotReady => Packet::ClientMatchNotReady,
+            PacketIDs::ClientMatchFailed => Packet::ClientMatchFailed,
+            PacketIDs::BanchoMatchPlayerFailed => Packet::BanchoMatchPlayerFailed(buf.read_i32()?),
+            PacketIDs::BanchoMatchComplete => Packet::BanchoMatchComplete,
+            PacketIDs::ClientMatchHasBeatmap => Packet::ClientMatchHasBeatmap,
+            PacketIDs::ClientMatchSkipRequest => Packet::ClientMatchSkipRequest,
+            PacketIDs::BanchoMatchSkip => Packet::BanchoMatchSkip,
+            PacketIDs::BanchoUnauthorised => Packet::BanchoUnauthorised,
+            PacketIDs::ClientChannelJoin => Packet::ClientChannelJoin(buf.get_str()?),
+            PacketIDs::BanchoChannelJoinSuccess => Packet::BanchoChannelJoinSuccess(buf.get_str()?),
+            PacketIDs::BanchoChannelAvailable => {
+                Packet::BanchoChannelAvailable(reader::read_channel(buf)?)
+            }
+            PacketIDs::BanchoChannelRevoked => Packet::BanchoChannelRevoked(buf.get_str()?),
+            PacketIDs::BanchoChannelAvailableAutojoin => {
+                Packet::BanchoChannelAvailableAutojoin(reader::read_channel(buf)?)
+            }
+            PacketIDs::ClientBeatmapInfoRequest => {
+                Packet::ClientBeatmapInfoRequest(buf.split_off(0))
+            }
+            PacketIDs::BanchoBeatmapInfoReply => {
+                Packet::BanchoBeatmapInfoReply(reader::read_beatmap_info_reply(buf)?)
+            }
+            PacketIDs::ClientMatchTransferHost => Packet::ClientMatchTransferHost(buf.read_i32()?),
+            PacketIDs::BanchoLoginPermissions => {
+                Packet::BanchoLoginPermissions(BanchoPrivileges::deserialize(buf)?)
+            }
+            PacketIDs::BanchoFriendsList => Packet::BanchoFriendsList(buf.get_i32_list()?),
+            PacketIDs::ClientFriendAdd => Packet::ClientFriendAdd(buf.read_i32()?),
+            PacketIDs::ClientFriendRemove => Packet::ClientFriendRemove(buf.read_i32()?),
+            PacketIDs::BanchoProtocolNegotiation => {
+                Packet::BanchoProtocolNegotiation(buf.read_i32()?)
+            }
+            PacketIDs::BanchoTitleUpdate => Packet::BanchoTitleUpdate(buf.get_str()?),
+            PacketIDs::ClientMatchChangeTeam => Packet::ClientMatchChangeTeam,
+            PacketIDs::ClientChannelLeave => Packet::ClientChannelLeave(buf.get_str()?),
+            PacketIDs::ClientReceiveUpdates => Packet::ClientReceiveUpdates(buf.read_i32()?),
+            PacketIDs::BanchoMonitor => Packet::BanchoMonitor,
+            PacketIDs::BanchoMatchPlayerSkipped => {
+                Packet::BanchoMatchPlayerSkipped(buf.read_i32()?)
+            }
+            PacketIDs::ClientSetIrcAwayMessage => {
+                Packet::ClientSetIrcAwayMessage(reader::read_message(buf)?)
+            }
+            PacketIDs::BanchoUserPresence => {
+                Packet::BanchoUserPresence(reader::read_presence(buf, ProtocolVersion::LATEST)?)
+            }
+            PacketIDs::ClientUserStatsRequest => {
+                Packet::ClientUserStatsRequest(buf.get_i32_list()?)
+            }
+            PacketIDs::BanchoRestart => Packet::BanchoRestart(buf.read_i32()?),
+            PacketIDs::ClientInvite => Packet::ClientInvite(buf.read_i32()?),
+            PacketIDs::BanchoInvite => Packet::BanchoInvite(reader::read_message(buf)?),
+            PacketIDs::BanchoChannelListingComplete => {
+                buf.read_i32()?;
+                Packet::BanchoChannelListingComplete
+            }
+            PacketIDs::ClientMatchChangePassword => {
+                Packet::ClientMatchChangePassword(reader::read_match(buf)?)
+            }
+            PacketIDs::BanchoMatchChangePassword => {
+                Packet::BanchoMatchChangePassword(buf.get_str()?)
+            }
+            PacketIDs::BanchoBanInfo => Packet::BanchoBanInfo(buf.read_u32()?),
+            PacketIDs::ClientSpecialMatchInfoRequest => {
+                Packet::ClientSpecialMatchInfoRequest(buf.read_i32()?)
+            }
+            PacketIDs::BanchoUserSilenced => Packet::BanchoUserSilenced(buf.read_i32()?),
+            PacketIDs::BanchoUserPresenceSingle => {
+                Packet::BanchoUserPresenceSingle(buf.read_i32()?)
+            }
+            PacketIDs::BanchoUserPresenceBundle => {
+                Packet::BanchoUserPresenceBundle(buf.get_i32_list()?)
+            }
+            PacketIDs::ClientUserPresenceRequest => {
+                Packet::ClientUserPresenceRequest(buf.get_i32_list()?)
+            }
+            PacketIDs::ClientUserPresenceRequestAll => {
+                Packet::ClientUserPresenceRequestAll(buf.read_i32()?)
+            }
+            PacketIDs::ClientUserToggleBlockNonFriendPm => {
+                Packet::ClientUserToggleBlockNonFriendPm(buf.read_i32()?)
+            }
+            PacketIDs::BanchoUserPmBlocked => {
+                Packet::BanchoUserPmBlocked(reader::read_message(buf)?)
+            }
+            PacketIDs::BanchoTargetIsSilenced => {
+                Packet::BanchoTargetIsSilenced(reader::read_message(buf)?)
+            }
+            PacketIDs::BanchoVersionUpdateForced => Packet::BanchoVersionUpdateForced,
+            PacketIDs::BanchoSwitchServer => Packet::BanchoSwitchServer(buf.get_str()?),
+            PacketIDs::BanchoAccountRestricted => Packet::BanchoAccountRestricted,
+            PacketIDs::BanchoRTX => Packet::BanchoRTX(buf.get_str()?),
+            PacketIDs::ClientMatchAbort => Packet::ClientMatchAbort,
+            PacketIDs::BanchoSwitchTourneyServer => {
+                Packet::BanchoSwitchTourneyServer(buf.get_str()?)
+            }
+            PacketIDs::ClientSpecialJoinMatchChannel => {
+                Packet::ClientSpecialJoinMatchChannel(buf.read_i32()?)
+            }
+            PacketIDs::ClientSpecialLeaveMatchChannel => {
+                Packet::ClientSpecialLeaveMatchChannel(buf.read_i32()?)
+            }
+        })
+    }
+
+    /// Length of the whole packet, header included
+    pub fn encoded_len(&self) -> usize {
+        HEADER_LEN + self.payload_len()
+    }
+
+    /// Encode the packet, including its header, onto the end of `buf`
+    pub fn encode(&self, buf: &mut Buffer) {
+        let start = buf.len();
+        buf.reserve(self.encoded_len());
+        buf.with_header(self.id() as i16, |buf| self.encode_payload(buf));
+        debug_assert_eq!(buf.len() - start, self.encoded_len(), "{:?}", self.id());
+    }
+
+    /// Encode the packet into any buffer, such as a slice, with the header written up front
+    ///
+    /// Writing into a buffer with less than [`Packet::encoded_len`] bytes of room panics.
+    pub fn encode_into(&self, buf: &mut impl BufMut) -> Result<(), EncodeError> {
+        buf.put_packet_header(self.id() as i16, self.payload_len())?;
+        self.encode_payload(buf);
+        Ok(())
+    }
+
+    /// Encode the packet like [`Packet::encode`], gzipping payloads longer than `threshold` bytes
+    pub fn encode_compressed(&self, buf: &mut Buffer, threshold: usize) {
+        buf.with_header_compressed(self.id() as i16, threshold, |buf| self.encode_payload(buf));
+    }
+
+    /// Encode just the payload of the packet onto the end of `buf`
+    pub fn encode_payload(&self, buf: &mut impl BufMut) {
+        match self {
+            Packet::ClientSendUserStatus(status) => writer::write_status(buf, status),
+
+            Packet::ClientSendIrcMessage(message)
+            | Packet::BanchoSendMessage(message)
+            | Packet::ClientSendIrcMessagePrivate(message)
+            | Packet::ClientSetIrcAwayMessage(message)
+            | Packet::BanchoInvite(message)
+            | Packet::BanchoUserPmBlocked(message)
+            | Packet::BanchoTargetIsSilenced(message) => writer::write_message(buf, message),
+
+            Packet::BanchoHandleOsuUpdate(stats) => writer::write_stats(buf, stats),
+            Packet::BanchoUserPresence(presence) => {
+                writer::write_presence(buf, presence, ProtocolVersion::LATEST)
+            }
+
+            Packet::BanchoChannelAvailable(channel)
+            | Packet::BanchoChannelAvailableAutojoin(channel) => {
+                writer::write_channel(buf, channel)
+            }
+
+            Packet::ClientMatchCreate(game)
+            | Packet::ClientMatchChangeSettings(game)
+            | Packet::ClientMatchChangePassword(game)
+            | Packet::BanchoMatchUpdate(game)
+            | Packet::BanchoMatchNew(game)
+            | Packet::BanchoMatchJoinSuccess(game)
+            | Packet::BanchoMatchStart(game) => writer::write_match(buf, game, true),
+
+            Packet::ClientMatchJoin { match_id, password } => {
+                buf.put_i32_le(*match_id);
+                buf.put_string(password);
+            }
+
+            Packet::BanchoHandleUserQuit(player_id) => {
+                buf.put_i32_le(*player_id);
+                buf.put_bool(false);
+            }
+
+            Packet::ClientExit(val)
+            | Packet::BanchoLoginReply(val)
+            | Packet::BanchoSpectatorJoined(val)
+            | Packet::BanchoSpectatorLeft(val)
+            | Packet::ClientStartSpectating(val)
+            | Packet::BanchoSpectatorCantSpectate(val)
+            | Packet::BanchoMatchDisband(val)
+            | Packet::ClientMatchChangeSlot(val)
+            | Packet::ClientMatchLock(val)
+            | Packet::BanchoFellowSpectatorJoined(val)
+            | Packet::BanchoFellowSpectatorLeft(val)
+            | Packet::BanchoMatchPlayerFailed(val)
+            | Packet::ClientMatchTransferHost(val)
+            | Packet::ClientFriendAdd(val)
+            | Packet::ClientFriendRemove(val)
+            | Packet::BanchoProtocolNegotiation(val)
+            | Packet::ClientReceiveUpdates(val)
+            | Packet::BanchoMatchPlayerSkipped(val)
+            | Packet::BanchoRestart(val)
+            | Packet::ClientInvite(val)
+            | Packet::ClientSpecialMatchInfoRequest(val)
+            | Packet::BanchoUserSilenced(val)
+            | Packet::BanchoUserPresenceSingle(val)
+            | Packet::ClientUserPresenceRequestAll(val)
+            | Packet::ClientUserToggleBlockNonFriendPm(val)
+            | Packet::ClientSpecialJoinMatchChannel(val)
+            | Packet::ClientSpecialLeaveMatchChannel(val) => buf.put_i32_le(*val),
+
+            Packet::BanchoBanInfo(val) => buf.put_u32_le(*val),
+            Packet::ClientMatchChangeMods(mods) => mods.serialize(buf),
+            Packet::BanchoLoginPermissions(permissions) => permissions.serialize(buf),
+
+            Packet::BanchoHandleIrcChangeUsername(string)
+            | Packet::BanchoHandleIrcQuit(string)
+            | Packet::ClientErrorReport(string)
+            | Packet::BanchoAnnounce(string)
+            | Packet::ClientChannelJoin(string)
+            | Packet::BanchoChannelJoinSuccess(string)
+            | Packet::BanchoChannelRevoked(string)
+            | Packet::BanchoTitleUpdate(string)
+            | Packet::ClientChannelLeave(string)
+            | Packet::BanchoMatchChangePassword(string)
+            | Packet::BanchoSwitchServer(string)
+            | Packet::BanchoRTX(string)
+            | Packet::BanchoSwitchTourneyServer(string) => buf.put_string(string),
+
+            Packet::BanchoFriendsList(list)
+            | Packet::ClientUserStatsRequest(list)
+            | Packet::BanchoUserPresenceBundle(list)
+            | Packet::ClientUserPresenceRequest(list) => buf.put_i32_list(list),
+
+            Packet::ClientSpectateFrames(bundle) | Packet::BanchoSpectateFrames(bundle) => {
+                writer::write_frame_bundle(buf, bundle)
+            }
+            Packet::ClientMatchScoreUpdate(score_frame)
+            | Packet::BanchoMatchScoreUpdate(score_frame) => {
+                writer::write_score_frame(buf, score_frame)
+            }
+
+            Packet::BanchoBeatmapInfoReply(reply) => writer::write_beatmap_info_reply(buf, reply),
+            Packet::ClientBeatmapInfoRequest(raw) => buf.put_slice(raw),
+
+            // matches `bancho_channel_listing_complete`
+            Packet::BanchoChannelListingComplete => buf.put_i32_le(0),
+
+            Packet::ClientRequestStatusUpdate
+            | Packet::ClientPong
+            | Packet::BanchoCommandError
+            | Packet::BanchoPing
+            | Packet::ClientStopSpectating
+            | Packet::BanchoVersionUpdate
+            | Packet::ClientCantSpectate
+            | Packet::BanchoGetAttention
+            | Packet::ClientLobbyPart
+            | Packet::ClientLobbyJoin
+            | Packet::ClientMatchPart
+            | Packet::BanchoMatchJoinFail
+            | Packet::ClientMatchReady
+            | Packet::ClientMatchStart
+            | Packet::ClientMatchComplete
+            | Packet::BanchoMatchTransferHost
+            | Packet::ClientMatchLoadComplete
+            | Packet::BanchoMatchAllPlayersLoaded
+            | Packet::ClientMatchNoBeatmap
+            | Packet::ClientMatchNotReady
+            | Packet::ClientMatchFailed
+            | Packet::BanchoMatchComplete
+            | Packet::ClientMatchHasBeatmap
+            | Packet::ClientMatchSkipRequest
+            | Packet::BanchoMatchSkip
+            | Packet::BanchoUnauthorised
+            | Packet::ClientMatchChangeTeam
+            | Packet::BanchoMonitor
+            | Packet::BanchoVersionUpdateForced
+            | Packet::BanchoAccountRestricted
+            | Packet::ClientMatchAbort => {}
+        }
+    }
+
+    /// Number of bytes [`Packet::encode_payload`] writes
+    pub fn payload_len(&self) -> usize {
+        match self {
+            Packet::ClientSendUserStatus(status) => status.encoded_len(),
+
+            Packet::ClientSendIrcMessage(message)
+            | Packet::BanchoSendMessage(message)
+            | Packet::ClientSendIrcMessagePrivate(message)
+            | Packet::ClientSetIrcAwayMessage(message)
+            | Packet::BanchoInvite(message)
+            | Packet::BanchoUserPmBlocked(message)
+            | Packet::BanchoTargetIsSilenced(message) => message.encoded_len(),
+
+            Packet::BanchoHandleOsuUpdate(stats) => stats.encoded_len(),
+            Packet::BanchoUserPresence(presence) => presence.encoded_len(),
+
+            Packet::BanchoChannelAvailable(channel)
+            | Packet::BanchoChannelAvailableAutojoin(channel) => channel.encoded_len(),
+
+            Packet::ClientMatchCreate(game)
+            | Packet::ClientMatchChangeSettings(game)
+            | Packet::ClientMatchChangePassword(game)
+            | Packet::BanchoMatchUpdate(game)
+            | Packet::BanchoMatchNew(game)
+            | Packet::BanchoMatchJoinSuccess(game)
+            | Packet::BanchoMatchStart(game) => writer::match_len(game, true),
+
+            Packet::ClientMatchJoin { password, .. } => 4 + string_len(password),
+
+            Packet::BanchoHandleUserQuit(_) => 4 + 1,
+
+            Packet::ClientExit(_)
+            | Packet::BanchoLoginReply(_)
+            | Packet::BanchoSpectatorJoined(_)
+            | Packet::BanchoSpectatorLeft(_)
+            | Packet::ClientStartSpectating(_)
+            | Packet::BanchoSpectatorCantSpectate(_)
+            | Packet::BanchoMatchDisband(_)
+            | Packet::ClientMatchChangeSlot(_)
+            | Packet::ClientMatchLock(_)
+            | Packet::BanchoFellowSpectatorJoined(_)
+            | Packet::BanchoFellowSpectatorLeft(_)
+            | Packet::BanchoMatchPlayerFailed(_)
+            | Packet::ClientMatchTransferHost(_)
+            | Packet::ClientFriendAdd(_)
+            | Packet::ClientFriendRemove(_)
+            | Packet::BanchoProtocolNegotiation(_)
+            | Packet::ClientReceiveUpdates(_)
+            | Packet::BanchoMatchPlayerSkipped(_)
+            | Packet::BanchoRestart(_)
+            | Packet::ClientInvite(_)
+            | Packet::ClientSpecialMatchInfoRequest(_)
+            | Packet::BanchoUserSilenced(_)
+            | Packet::BanchoUserPresenceSingle(_)
+            | Packet::ClientUserPresenceRequestAll(_)
+            | Packet::ClientUserToggleBlockNonFriendPm(_)
+            | Packet::ClientSpecialJoinMatchChannel(_)
+            | Packet::ClientSpecialLeaveMatchChannel(_) => 4,
+
+            Packet::BanchoBanInfo(_) => 4,
+            Packet::ClientMatchChangeMods(mods) => mods.encoded_len(),
+            Packet::BanchoLoginPermissions(permissions) => permissions.encoded_len(),
+
+            Packet::BanchoHandleIrcChangeUsername(string)
+            | Packet::BanchoHandleIrcQuit(string)
+            | Packet::ClientErrorReport(string)
+            | Packet::BanchoAnnounce(string)
+            | Packet::ClientChannelJoin(string)
+            | Packet::BanchoChannelJoinSuccess(string)
+            | Packet::BanchoChannelRevoked(string)
+            | Packet::BanchoTitleUpdate(string)
+            | Packet::ClientChannelLeave(string)
+            | Packet::BanchoMatchChangePassword(string)
+            | Packet::BanchoSwitchServer(string)
+            | Packet::BanchoRTX(string)
+            | Packet::BanchoSwitchTourneyServer(string) => string_len(string),
+
+            Packet::BanchoFriendsList(list)
+            | Packet::ClientUserStatsRequest(list)
+            | Packet::BanchoUserPresenceBundle(list)
+            | Packet::ClientUserPresenceRequest(list) => i32_list_len(list),
+
+            Packet::ClientSpectateFrames(bundle) | Packet::BanchoSpectateFrames(bundle) => {
+                bundle.encoded_len()
+            }
+            Packet::ClientMatchScoreUpdate(score_frame)
+            | Packet::BanchoMatchScoreUpdate(score_frame) => score_frame.encoded_len(),
+
+            Packet::BanchoBeatmapInfoReply(reply) => reply.encoded_len(),
+            Packet::ClientBeatmapInfoRequest(raw) => raw.len(),
+
+            Packet::BanchoChannelListingComplete => 4,
+
+            Packet::ClientRequestStatusUpdate
+            | Packet::ClientPong
+            | Packet::BanchoCommandError
+            | Packet::BanchoPing
+            | Packet::ClientStopSpectating
+            | Packet::BanchoVersionUpdate
+            | Packet::ClientCantSpectate
+            | Packet::BanchoGetAttention
+            | Packet::ClientLobbyPart
+            | Packet::ClientLobbyJoin
+            | Packet::ClientMatchPart
+            | Packet::BanchoMatchJoinFail
+            | Packet::ClientMatchReady
+            | Packet::ClientMatchStart
+            | Packet::ClientMatchComplete
+            | Packet::BanchoMatchTransferHost
+            | Packet::ClientMatchLoadComplete
+            | Packet::BanchoMatchAllPlayersLoaded
+            | Packet::ClientMatchNoBeatmap
+            | Packet::ClientMatchNotReady
+            | Packet::ClientMatchFailed
+            | Packet::BanchoMatchComplete
+            | Packet::ClientMatchHasBeatmap
+            | Packet::ClientMatchSkipRequest
+            | Packet::BanchoMatchSkip
+            | Packet::BanchoUnauthorised
+            | Packet::ClientMatchChangeTeam
+            | Packet::BanchoMonitor
+            | Packet::BanchoVersionUpdateForced
+            | Packet::BanchoAccountRestricted
+            | Packet::ClientMatchAbort => 0,
+        }
+    }
+}
@@ -1,3 +1,7 @@
+use crate::error::DecodeError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(i16)]
 pub enum PacketIDs {
     ClientSendUserStatus = 0,
 	ClientSendIrcMessage = 1,
@@ -105,4 +109,120 @@ pub enum PacketIDs {
 	BanchoSwitchTourneyServer = 107,
 	ClientSpecialJoinMatchChannel = 108,
 	ClientSpecialLeaveMatchChannel = 109,
-}
\ No newline at end of file
+}
+
+impl TryFrom<i16> for PacketIDs {
+    type Error = DecodeError;
+
+    fn try_from(id: i16) -> Result<Self, Self::Error> {
+        Ok(match id {
+            0 => PacketIDs::ClientSendUserStatus,
+            1 => PacketIDs::ClientSendIrcMessage,
+            2 => PacketIDs::ClientExit,
+            3 => PacketIDs::ClientRequestStatusUpdate,
+            4 => PacketIDs::ClientPong,
+            5 => PacketIDs::BanchoLoginReply,
+            6 => PacketIDs::BanchoCommandError,
+            7 => PacketIDs::BanchoSendMessage,
+            8 => PacketIDs::BanchoPing,
+            9 => PacketIDs::BanchoHandleIrcChangeUsername,
+            10 => PacketIDs::BanchoHandleIrcQuit,
+            11 => PacketIDs::BanchoHandleOsuUpdate,
+            12 => PacketIDs::BanchoHandleUserQuit,
+            13 => PacketIDs::BanchoSpectatorJoined,
+            14 => PacketIDs::BanchoSpectatorLeft,
+            15 => PacketIDs::BanchoSpectateFrames,
+            16 => PacketIDs::ClientStartSpectating,
+            17 => PacketIDs::ClientStopSpectating,
+            18 => PacketIDs::ClientSpectateFrames,
+            19 => PacketIDs::BanchoVersionUpdate,
+            20 => PacketIDs::ClientErrorReport,
+            21 => PacketIDs::ClientCantSpectate,
+            22 => PacketIDs::BanchoSpectatorCantSpectate,
+            23 => PacketIDs::BanchoGetAttention,
+            24 => PacketIDs::BanchoAnnounce,
+            25 => PacketIDs::ClientSendIrcMessagePrivate,
+            26 => PacketIDs::BanchoMatchUpdate,
+            27 => PacketIDs::BanchoMatchNew,
+            28 => PacketIDs::BanchoMatchDisband,
+            29 => PacketIDs::ClientLobbyPart,
+            30 => PacketIDs::ClientLobbyJoin,
+            31 => PacketIDs::ClientMatchCreate,
+            32 => PacketIDs::ClientMatchJoin,
+            33 => PacketIDs::ClientMatchPart,
+            36 => PacketIDs::BanchoMatchJoinSuccess,
+            37 => PacketIDs::BanchoMatchJoinFail,
+            38 => PacketIDs::ClientMatchChangeSlot,
+            39 => PacketIDs::ClientMatchReady,
+            40 => PacketIDs::ClientMatchLock,
+            41 => PacketIDs::ClientMatchChangeSettings,
+            42 => PacketIDs::BanchoFellowSpectatorJoined,
+            43 => PacketIDs::BanchoFellowSpectatorLeft,
+            44 => PacketIDs::ClientMatchStart,
+            46 => PacketIDs::BanchoMatchStart,
+            47 => PacketIDs::ClientMatchScoreUpdate,
+            48 => PacketIDs::BanchoMatchScoreUpdate,
+            49 => PacketIDs::ClientMatchComplete,
+            50 => PacketIDs::BanchoMatchTransferHost,
+            51 => PacketIDs::ClientMatchChangeMods,
+            52 => PacketIDs::ClientMatchLoadComplete,
+            53 => PacketIDs::BanchoMatchAllPlayersLoaded,
+            54 => PacketIDs::ClientMatchNoBeatmap,
+            55 => PacketIDs::ClientMatchNotReady,
+            56 => PacketIDs::ClientMatchFailed,
+            57 => PacketIDs::BanchoMatchPlayerFailed,
+            58 => PacketIDs::BanchoMatchComplete,
+            59 => PacketIDs::ClientMatchHasBeatmap,
+            60 => PacketIDs::ClientMatchSkipRequest,
+            61 => PacketIDs::BanchoMatchSkip,
+            62 => PacketIDs::BanchoUnauthorised,
+            63 => PacketIDs::ClientChannelJoin,
+            64 => PacketIDs::BanchoChannelJoinSuccess,
+            65 => PacketIDs::BanchoChannelAvailable,
+            66 => PacketIDs::BanchoChannelRevoked,
+            67 => PacketIDs::BanchoChannelAvailableAutojoin,
+            68 => PacketIDs::ClientBeatmapInfoRequest,
+            69 => PacketIDs::BanchoBeatmapInfoReply,
+            70 => PacketIDs::ClientMatchTransferHost,
+            71 => PacketIDs::BanchoLoginPermissions,
+            72 => PacketIDs::BanchoFriendsList,
+            73 => PacketIDs::ClientFriendAdd,
+            74 => PacketIDs::ClientFriendRemove,
+            75 => PacketIDs::BanchoProtocolNegotiation,
+            76 => PacketIDs::BanchoTitleUpdate,
+            77 => PacketIDs::ClientMatchChangeTeam,
+            78 => PacketIDs::ClientChannelLeave,
+            79 => PacketIDs::ClientReceiveUpdates,
+            80 => PacketIDs::BanchoMonitor,
+            81 => PacketIDs::BanchoMatchPlayerSkipped,
+            82 => PacketIDs::ClientSetIrcAwayMessage,
+            83 => PacketIDs::BanchoUserPresence,
+            85 => PacketIDs::ClientUserStatsRequest,
+            86 => PacketIDs::BanchoRestart,
+            87 => PacketIDs::ClientInvite,
+            88 => PacketIDs::BanchoInvite,
+            89 => PacketIDs::BanchoChannelListingComplete,
+            90 => PacketIDs::ClientMatchChangePassword,
+            91 => PacketIDs::BanchoMatchChangePassword,
+            92 => PacketIDs::BanchoBanInfo,
+            93 => PacketIDs::ClientSpecialMatchInfoRequest,
+            94 => PacketIDs::BanchoUserSilenced,
+            95 => PacketIDs::BanchoUserPresenceSingle,
+            96 => PacketIDs::BanchoUserPresenceBundle,
+            97 => PacketIDs::ClientUserPresenceRequest,
+            98 => PacketIDs::ClientUserPresenceRequestAll,
+            99 => PacketIDs::ClientUserToggleBlockNonFriendPm,
+            100 => PacketIDs::BanchoUserPmBlocked,
+            101 => PacketIDs::BanchoTargetIsSilenced,
+            102 => PacketIDs::BanchoVersionUpdateForced,
+            103 => PacketIDs::BanchoSwitchServer,
+            104 => PacketIDs::BanchoAccountRestricted,
+            105 => PacketIDs::BanchoRTX,
+            106 => PacketIDs::ClientMatchAbort,
+            107 => PacketIDs::BanchoSwitchTourneyServer,
+            108 => PacketIDs::ClientSpecialJoinMatchChannel,
+            109 => PacketIDs::ClientSpecialLeaveMatchChannel,
+            id => return Err(DecodeError::UnknownPacket(id)),
+        })
+    }
+}
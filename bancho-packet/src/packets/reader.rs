@@ -1,74 +1,36 @@
-use crate::buffer::serialization::{Buffer, BytesExt, BytesMutExt};
-use crate::packets::structures;
+use crate::buffer::byte_str::ByteStr;
+use crate::buffer::serialization::{BanchoDeserialize, BytesExt};
+use crate::error::DecodeError;
+use crate::packets::mods::Mods;
+use crate::packets::structures::{self, GameMode};
 use bytes::{Buf, Bytes};
+use std::fmt;
 use tracing::instrument;
 
-const PARSE_BAD_UTF8: &str = "bad utf-8 in string";
-
-// TODO: This doesnt need to copy, could just slice into the buf
+/// The login request, which unlike every other request is plain text split into lines
+///
+/// Every field shares the request body rather than being copied out of it.
 #[derive(Debug, Clone)]
 pub struct LoginData {
-    pub username: String,
-    pub password_md5: String,
-    pub client_version: String,
+    pub username: ByteStr,
+    pub password_md5: ByteStr,
+    pub client_version: structures::ClientVersion,
     pub utc_offset: i32,
     pub show_city: i32,
     pub allow_pms: i32,
-    pub path_md5: String,
-    pub adapters_string: String,
-    pub adapters_md5: String,
-    pub uninstall_md5: String,
-    pub disk_signature_md5: String,
+    pub client_hashes: structures::ClientHashes,
 }
 
 impl LoginData {
     #[instrument(name = "deserialise_login_data", skip_all, fields(buf_len = buf.len()))]
-    pub fn from_slice(buf: &mut Bytes) -> Result<Self, &'static str> {
-        let username = String::from_utf8(buf.take_while(|b| b != b'\n').to_vec())
-            .map_err(|_| PARSE_BAD_UTF8)?;
-        buf.advance(1);
-
-        let password_md5 = String::from_utf8(buf.take_while(|b| b != b'\n').to_vec())
-            .map_err(|_| PARSE_BAD_UTF8)?;
-        buf.advance(1); // '\n'
-
-        let client_version = String::from_utf8(buf.take_while(|b| b != b'|').to_vec())
-            .map_err(|_| PARSE_BAD_UTF8)?;
-        buf.advance(1);
-
-        let utc_offset = String::from_utf8(buf.take_while(|b| b != b'|').to_vec())
-            .map_err(|_| PARSE_BAD_UTF8)?
-            .parse::<i32>()
-            .unwrap();
-        buf.advance(1);
-
-        let show_city = String::from_utf8(buf.take_while(|b| b != b'|').to_vec())
-            .map_err(|_| PARSE_BAD_UTF8)?
-            .parse::<i32>()
-            .unwrap();
-        buf.advance(1);
-
-        let client_hashes = String::from_utf8(buf.take_while(|b| b != b'|').to_vec())
-            .map_err(|_| PARSE_BAD_UTF8)?;
-        buf.advance(1);
-
-        let allow_pms = String::from_utf8(buf.take_while(|b| b != b'\n').to_vec())
-            .map_err(|_| PARSE_BAD_UTF8)?
-            .parse::<i32>()
-            .unwrap();
-        buf.advance(1);
-
-        let mut hashes_split = client_hashes.split(':');
-
-        let path_md5 = hashes_split.next().unwrap().to_string();
-
-        let adapters_string = hashes_split.next().unwrap().to_string();
-
-        let adapters_md5 = hashes_split.next().unwrap().to_string();
-
-        let uninstall_md5 = hashes_split.next().unwrap().to_string();
-
-        let disk_signature_md5 = hashes_split.next().unwrap().to_string();
+    pub fn from_slice(buf: &mut Bytes) -> Result<Self, DecodeError> {
+        let username = take_field(buf, b'\n')?;
+        let password_md5 = take_field(buf, b'\n')?;
+        let client_version = take_field(buf, b'|')?.parse()?;
+        let utc_offset = parse_int(&take_field(buf, b'|')?)?;
+        let show_city = parse_int(&take_field(buf, b'|')?)?;
+        let client_hashes = structures::ClientHashes::parse(&take_field(buf, b'|')?)?;
+        let allow_pms = parse_int(&take_field(buf, b'\n')?)?;
 
         Ok(LoginData {
             username,
@@ -77,42 +39,135 @@ impl LoginData {
             utc_offset,
             show_city,
             allow_pms,
-            path_md5,
-            adapters_string,
-            adapters_md5,
-            uninstall_md5,
-            disk_signature_md5,
+            client_hashes,
         })
     }
 }
 
-pub fn client_send_mesage(buf: &mut Buffer) -> structures::BanchoMessage {
-    let sending_client = buf.get_string(); //buf.get_string();
-    let message = buf.get_string();
-    let target = buf.get_string();
-    let sender_id = buf.get_i32_le();
-
-    structures::BanchoMessage {
-        sending_client,
-        message,
-        target,
-        sender_id,
+/// Writes the login request back out, as a client would send it
+impl fmt::Display for LoginData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.username)?;
+        writeln!(f, "{}", self.password_md5)?;
+        writeln!(
+            f,
+            "{}|{}|{}|{}|{}",
+            self.client_version,
+            self.utc_offset,
+            self.show_city,
+            self.client_hashes,
+            self.allow_pms
+        )
+    }
+}
+
+/// Take everything up to `separator` as a string, and skip over the separator itself
+fn take_field(buf: &mut Bytes, separator: u8) -> Result<ByteStr, DecodeError> {
+    let field = buf.take_while(|b| b != separator);
+    if !buf.has_remaining() {
+        return Err(DecodeError::UnexpectedEof);
     }
+    buf.advance(1);
+
+    ByteStr::from_utf8(field)
+}
+
+fn parse_int(field: &str) -> Result<i32, DecodeError> {
+    field.parse().map_err(|_| DecodeError::BadInteger)
+}
+
+pub fn read_message(buf: &mut Bytes) -> Result<structures::BanchoMessage, DecodeError> {
+    structures::BanchoMessage::deserialize(buf)
+}
+
+pub fn read_status(buf: &mut Bytes) -> Result<structures::ClientStatus, DecodeError> {
+    structures::ClientStatus::deserialize(buf)
 }
 
-pub fn client_user_status(buf: &mut Buffer) -> structures::ClientStatus {
-    let status = buf.get_u8();
-    let status_text = buf.get_string();
-    let beatmap_checksum = buf.get_string();
-    let current_mods = buf.get_u32_le();
-    let play_mode = buf.get_u8();
-    let beatmap_id = buf.get_i32_le();
-    structures::ClientStatus {
-        status,
-        status_text,
+pub fn read_stats(buf: &mut Bytes) -> Result<structures::BanchoStats, DecodeError> {
+    structures::BanchoStats::deserialize(buf)
+}
+
+pub fn read_presence(
+    buf: &mut Bytes,
+    protocol: structures::ProtocolVersion,
+) -> Result<structures::BanchoPresence, DecodeError> {
+    structures::BanchoPresence::deserialize_for(buf, protocol)
+}
+
+pub fn read_channel(buf: &mut Bytes) -> Result<structures::BanchoChannel, DecodeError> {
+    structures::BanchoChannel::deserialize(buf)
+}
+
+pub fn read_match(buf: &mut Bytes) -> Result<structures::Match, DecodeError> {
+    let match_id = buf.read_i16()?;
+    let in_progress = buf.get_bool()?;
+    let match_type = buf.read_u8()?;
+    let active_mods = Mods::deserialize(buf)?;
+    let game_name = buf.get_string()?;
+    let game_password = buf.get_optional_string()?;
+    let beatmap_name = buf.get_string()?;
+    let beatmap_id = buf.read_i32()?;
+    let beatmap_checksum = buf.get_string()?;
+
+    let mut slots = [structures::MatchSlot::default(); structures::MATCH_SLOTS];
+    for slot in slots.iter_mut() {
+        slot.status = buf.read_u8()?;
+    }
+    for slot in slots.iter_mut() {
+        slot.team = buf.read_u8()?;
+    }
+    // player ids are only sent for slots which have someone in them
+    for slot in slots.iter_mut().filter(|slot| slot.has_player()) {
+        slot.player_id = buf.read_i32()?;
+    }
+
+    let host_id = buf.read_i32()?;
+    let play_mode = GameMode::deserialize(buf)?;
+    let match_scoring_type = buf.read_u8()?;
+    let match_team_type = buf.read_u8()?;
+    let free_mod = buf.get_bool()?;
+    if free_mod {
+        for slot in slots.iter_mut() {
+            slot.slot_mods = Mods::deserialize(buf)?;
+        }
+    }
+    let seed = buf.read_i32()?;
+
+    Ok(structures::Match {
+        match_id,
+        in_progress,
+        match_type,
+        active_mods,
+        game_name,
+        game_password,
+        beatmap_name,
+        beatmap_id,
         beatmap_checksum,
-        current_mods,
+        slots,
+        host_id,
         play_mode,
-        beatmap_id,
-    }
+        match_scoring_type,
+        match_team_type,
+        free_mod,
+        seed,
+    })
+}
+
+pub fn read_frame_bundle(buf: &mut Bytes) -> Result<structures::ReplayFrameBundle, DecodeError> {
+    structures::ReplayFrameBundle::deserialize(buf)
+}
+
+pub fn read_replay_frame(buf: &mut Bytes) -> Result<structures::ReplayFrame, DecodeError> {
+    structures::ReplayFrame::deserialize(buf)
+}
+
+pub fn read_score_frame(buf: &mut Bytes) -> Result<structures::ScoreFrame, DecodeError> {
+    structures::ScoreFrame::deserialize(buf)
+}
+
+pub fn read_beatmap_info_reply(
+    buf: &mut Bytes,
+) -> Result<structures::BeatmapInfoReply, DecodeError> {
+    structures::BeatmapInfoReply::deserialize(buf)
 }
@@ -1,39 +1,186 @@
-use serde::{Serialize, Deserialize}; 
-#[derive(Debug, Clone)]
+use std::{fmt, str::FromStr};
+
+use bitflags::bitflags;
+use bytes::{BufMut, Bytes};
+use serde::{Deserialize, Serialize};
+
+use crate::buffer::byte_str::ByteStr;
+use crate::buffer::serialization::{
+    string_len, BanchoDeserialize, BanchoPacket, BanchoSerialize, BufMutExt, BytesExt,
+};
+use crate::error::DecodeError;
+use crate::packets::mods::Mods;
+use crate::packets::packet_ids::PacketIDs;
+
+/// An enum sent as a single byte, which keeps values it doesn't know in `Other` rather than failing to decode
+///
+/// Newer clients send values gamma doesn't know about yet, and their packets shouldn't be dropped because of it.
+macro_rules! byte_enum {
+    (
+        $(#[$meta:meta])*
+        pub enum $name:ident {
+            $($(#[$variant_meta:meta])* $variant:ident = $value:literal,)*
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+        #[serde(from = "u8", into = "u8")]
+        pub enum $name {
+            $($(#[$variant_meta])* $variant,)*
+            /// A value without a variant of its own, sent back out unchanged
+            Other(u8),
+        }
+
+        impl From<u8> for $name {
+            fn from(value: u8) -> Self {
+                match value {
+                    $($value => $name::$variant,)*
+                    _ => $name::Other(value),
+                }
+            }
+        }
+
+        impl From<$name> for u8 {
+            fn from(value: $name) -> Self {
+                match value {
+                    $($name::$variant => $value,)*
+                    $name::Other(value) => value,
+                }
+            }
+        }
+
+        impl BanchoSerialize for $name {
+            fn serialize<B: BufMut>(&self, buf: &mut B) {
+                buf.put_u8(u8::from(*self));
+            }
+
+            fn encoded_len(&self) -> usize {
+                1
+            }
+        }
+
+        impl BanchoDeserialize for $name {
+            fn deserialize(buf: &mut Bytes) -> Result<Self, DecodeError> {
+                buf.read_u8().map($name::from)
+            }
+        }
+    };
+}
+
+byte_enum! {
+    pub enum GameMode {
+        #[default]
+        Osu = 0,
+        Taiko = 1,
+        Catch = 2,
+        Mania = 3,
+    }
+}
+
+byte_enum! {
+    /// What a player is currently doing, shown under their name in the user panel
+    pub enum Action {
+        #[default]
+        Idle = 0,
+        Afk = 1,
+        Playing = 2,
+        Editing = 3,
+        Modding = 4,
+        Multiplayer = 5,
+        Watching = 6,
+        Unknown = 7,
+        Testing = 8,
+        Submitting = 9,
+        Paused = 10,
+        Lobby = 11,
+        Multiplaying = 12,
+        OsuDirect = 13,
+    }
+}
+
+bitflags! {
+    /// The privileges the client knows about, which decide the colour of a player's name
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+    #[serde(from = "u8", into = "u8")]
+    pub struct BanchoPrivileges: u8 {
+        const NORMAL = 1 << 0;
+        const MODERATOR = 1 << 1;
+        const SUPPORTER = 1 << 2;
+        const OWNER = 1 << 3;
+        const DEVELOPER = 1 << 4;
+        const TOURNAMENT = 1 << 5;
+    }
+}
+
+impl From<u8> for BanchoPrivileges {
+    fn from(bits: u8) -> Self {
+        BanchoPrivileges::from_bits_retain(bits)
+    }
+}
+
+impl From<BanchoPrivileges> for u8 {
+    fn from(privileges: BanchoPrivileges) -> Self {
+        privileges.bits()
+    }
+}
+
+impl BanchoSerialize for BanchoPrivileges {
+    fn serialize<B: BufMut>(&self, buf: &mut B) {
+        buf.put_u8(self.bits());
+    }
+
+    fn encoded_len(&self) -> usize {
+        1
+    }
+}
+
+impl BanchoDeserialize for BanchoPrivileges {
+    fn deserialize(buf: &mut Bytes) -> Result<Self, DecodeError> {
+        buf.read_u8().map(BanchoPrivileges::from)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, BanchoSerialize, BanchoDeserialize)]
+#[bancho(id = BanchoSendMessage)]
 pub struct BanchoMessage {
-    pub sending_client: String,
-    pub message: String,
-    pub target: String,
+    pub sending_client: ByteStr,
+    pub message: ByteStr,
+    pub target: ByteStr,
     pub sender_id: i32,
 }
 
+#[derive(Debug, Clone, PartialEq, BanchoSerialize, BanchoDeserialize)]
+#[bancho(id = BanchoChannelAvailable)]
 pub struct BanchoChannel {
     pub name: String,
     pub topic: String,
     pub connected: i16,
 }
-#[derive(Clone, Serialize, Deserialize)]
+/// Sent with a hand written layout, since the privileges and mode are packed into one byte
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct BanchoPresence {
     pub player_id: i32,
     pub username: String,
     pub timezone: u8,
     pub country_code: u8,
-    pub play_mode: u8,
-    pub permissions: u8,
+    pub play_mode: GameMode,
+    pub permissions: BanchoPrivileges,
     pub longitude: f32,
     pub latitude: f32,
     pub player_rank: i32,
 }
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, BanchoSerialize, BanchoDeserialize)]
+#[bancho(id = ClientSendUserStatus)]
 pub struct ClientStatus {
-    pub status: u8,
-    pub status_text: String,
-    pub beatmap_checksum: String,
-    pub current_mods: u32,
-    pub play_mode: u8,
+    pub status: Action,
+    pub status_text: ByteStr,
+    pub beatmap_checksum: ByteStr,
+    pub current_mods: Mods,
+    pub play_mode: GameMode,
     pub beatmap_id: i32,
 }
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, BanchoSerialize, BanchoDeserialize)]
+#[bancho(id = BanchoHandleOsuUpdate)]
 pub struct BanchoStats {
     pub player_id: i32,
     pub status: ClientStatus,
@@ -45,69 +192,474 @@ pub struct BanchoStats {
     pub performance: i16,
 }
 
+/// Number of slots in every multiplayer match
+pub const MATCH_SLOTS: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
 pub struct MatchSlot {
     pub status: u8,
     pub team: u8,
     pub player_id: i32,
-    pub slot_mods: u32,
+    pub slot_mods: Mods,
 
+    // server side state, never sent over the wire
     pub skipped: bool,
     pub completed: bool,
     pub loaded: bool,
 }
 
+impl MatchSlot {
+    pub const STATUS_OPEN: u8 = 1;
+    pub const STATUS_LOCKED: u8 = 2;
+    pub const STATUS_NOT_READY: u8 = 4;
+    pub const STATUS_READY: u8 = 8;
+    pub const STATUS_NO_MAP: u8 = 16;
+    pub const STATUS_PLAYING: u8 = 32;
+    pub const STATUS_COMPLETE: u8 = 64;
+    pub const STATUS_QUIT: u8 = 128;
+
+    /// Any status with one of these bits set has a player in the slot
+    pub const STATUS_HAS_PLAYER: u8 = Self::STATUS_NOT_READY
+        | Self::STATUS_READY
+        | Self::STATUS_NO_MAP
+        | Self::STATUS_PLAYING
+        | Self::STATUS_COMPLETE;
+
+    pub fn has_player(&self) -> bool {
+        self.status & Self::STATUS_HAS_PLAYER != 0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default, BanchoSerialize, BanchoDeserialize)]
+#[bancho(id = BanchoMatchScoreUpdate)]
 pub struct ScoreFrame {
     pub time: i32,
-    pub id: i32,
-    pub count_300: i16,
-    pub count_100: i16,
-    pub count_50: i16,
-    pub count_geki: i16,
-    pub count_katu: i16,
-    pub count_miss: i16,
+    /// The slot id when sent in a match
+    pub id: u8,
+    pub count_300: u16,
+    pub count_100: u16,
+    pub count_50: u16,
+    pub count_geki: u16,
+    pub count_katu: u16,
+    pub count_miss: u16,
     pub total_score: i32,
-    pub max_combo: i16,
-    pub current_combo: i16,
+    pub max_combo: u16,
+    pub current_combo: u16,
     pub perfect: bool,
-    pub current_hp: f32,
+    pub current_hp: u8,
     pub tag_byte: u8,
     pub using_score_v2: bool,
-    pub combo_portion: Option<f32>,
-    pub bonus_portion: Option<f32>,
+    #[bancho(if = "using_score_v2")]
+    pub combo_portion: Option<f64>,
+    #[bancho(if = "using_score_v2")]
+    pub bonus_portion: Option<f64>,
 }
 
+impl ScoreFrame {
+    /// Offset of `id` within an encoded score frame, for patching relayed frames
+    pub const ID_OFFSET: usize = 4;
+}
+
+/// Has a hand written layout, since player ids and slot mods depend on the slots and `free_mod`
+#[derive(Debug, Clone, PartialEq)]
 pub struct Match {
-    pub match_id: i32,
+    pub match_id: i16,
     pub in_progress: bool,
     pub match_type: u8,
-    pub active_mods: u32,
+    pub active_mods: Mods,
     pub game_name: String,
-    pub game_password: String,
+    /// `None` when the match has no password
+    pub game_password: Option<String>,
     pub beatmap_name: String,
     pub beatmap_id: i32,
     pub beatmap_checksum: String,
-    pub slot_status: Vec<u8>,
-    pub slot_team: Vec<u8>,
-    pub slot_id: Vec<i32>,
+    pub slots: [MatchSlot; MATCH_SLOTS],
     pub host_id: i32,
-    pub play_mode: u8,
+    pub play_mode: GameMode,
     pub match_scoring_type: u8,
     pub match_team_type: u8,
     pub free_mod: bool,
-    pub slot_mods: Vec<u32>,
     pub seed: i32,
 }
 
+impl Match {
+    /// A copy of the match safe to show in the lobby, which only reveals whether there is a password
+    pub fn masked(&self) -> Match {
+        Match {
+            game_password: self.game_password.as_ref().map(|_| String::new()),
+            ..self.clone()
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default, BanchoSerialize, BanchoDeserialize)]
 pub struct ReplayFrame {
     pub button_state: u8,
-    pub mouse_x: i16,
-    pub mouse_y: i16,
+    /// Left over from old clients, always 0 nowadays
+    pub legacy_byte: u8,
+    pub mouse_x: f32,
+    pub mouse_y: f32,
     pub time: i32,
 }
 
+#[derive(Debug, Clone, PartialEq, Default, BanchoSerialize, BanchoDeserialize)]
+#[bancho(id = BanchoSpectateFrames)]
 pub struct ReplayFrameBundle {
     pub extra: i32,
+    #[bancho(list = "u16")]
     pub frames: Vec<ReplayFrame>,
-    pub action: i32,
+    pub action: u8,
     pub score_frame: ScoreFrame,
-}
\ No newline at end of file
+    pub sequence: u16,
+}
+
+/// A beatmap's ranked status and the player's grades on it, the client asks for these when it finds new beatmaps
+///
+/// Grades are the client's numbering, from 0 for a silver SS down to 8 for an F, with 9 meaning no grade.
+#[derive(Debug, Clone, PartialEq, Default, BanchoSerialize, BanchoDeserialize)]
+pub struct BeatmapInfo {
+    /// Which beatmap of the request this is about
+    pub index: i16,
+    pub beatmap_id: i32,
+    pub beatmapset_id: i32,
+    pub thread_id: i32,
+    pub ranked_status: u8,
+    pub osu_grade: u8,
+    pub catch_grade: u8,
+    pub taiko_grade: u8,
+    pub mania_grade: u8,
+    pub beatmap_checksum: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, BanchoSerialize, BanchoDeserialize)]
+#[bancho(id = BanchoBeatmapInfoReply)]
+pub struct BeatmapInfoReply {
+    #[bancho(list = "i32")]
+    pub beatmaps: Vec<BeatmapInfo>,
+}
+
+impl BanchoPresence {
+    /// The privileges take up the low 5 bits of the byte, with the mode above them
+    const MODE_SHIFT: u8 = 5;
+    /// Privileges above the low 5 bits, such as [`BanchoPrivileges::TOURNAMENT`], would spill into the mode
+    const PRIVILEGES_MASK: u8 = (1 << Self::MODE_SHIFT) - 1;
+
+    /// Write the presence in the layout `protocol` expects
+    pub fn serialize_for<B: BufMut>(&self, buf: &mut B, protocol: ProtocolVersion) {
+        buf.put_i32_le(self.player_id);
+        buf.put_string(&self.username);
+        match protocol {
+            ProtocolVersion::V19 => buf.put_slice(&[self.timezone, self.country_code]),
+        }
+        buf.put_u8(
+            self.permissions.bits() & Self::PRIVILEGES_MASK
+                | u8::from(self.play_mode) << Self::MODE_SHIFT,
+        );
+        buf.put_f32_le(self.longitude);
+        buf.put_f32_le(self.latitude);
+        buf.put_i32_le(self.player_rank);
+    }
+
+    /// Read a presence written in the layout `protocol` expects
+    pub fn deserialize_for(
+        buf: &mut Bytes,
+        protocol: ProtocolVersion,
+    ) -> Result<Self, DecodeError> {
+        let player_id = buf.read_i32()?;
+        let username = buf.get_string()?;
+        let (timezone, country_code) = match protocol {
+            ProtocolVersion::V19 => (buf.read_u8()?, buf.read_u8()?),
+        };
+        let packed = buf.read_u8()?;
+        let longitude = buf.read_f32()?;
+        let latitude = buf.read_f32()?;
+        let player_rank = buf.read_i32()?;
+
+        Ok(BanchoPresence {
+            player_id,
+            username,
+            timezone,
+            country_code,
+            play_mode: GameMode::from(packed >> Self::MODE_SHIFT),
+            permissions: BanchoPrivileges::from(packed & Self::PRIVILEGES_MASK),
+            longitude,
+            latitude,
+            player_rank,
+        })
+    }
+}
+
+/// Uses the layout of [`ProtocolVersion::LATEST`]
+impl BanchoSerialize for BanchoPresence {
+    fn serialize<B: BufMut>(&self, buf: &mut B) {
+        self.serialize_for(buf, ProtocolVersion::LATEST);
+    }
+
+    /// The same for every protocol version, they only shuffle the fields around
+    fn encoded_len(&self) -> usize {
+        4 + string_len(&self.username) + 3 + 4 + 4 + 4
+    }
+}
+
+/// Uses the layout of [`ProtocolVersion::LATEST`]
+impl BanchoDeserialize for BanchoPresence {
+    fn deserialize(buf: &mut Bytes) -> Result<Self, DecodeError> {
+        Self::deserialize_for(buf, ProtocolVersion::LATEST)
+    }
+}
+
+impl BanchoPacket for BanchoPresence {
+    const ID: PacketIDs = PacketIDs::BanchoUserPresence;
+}
+
+impl BanchoSerialize for Match {
+    /// Always includes the password, use [`crate::packets::writer::write_match`] to mask it
+    fn serialize<B: BufMut>(&self, buf: &mut B) {
+        crate::packets::writer::write_match(buf, self, true);
+    }
+
+    fn encoded_len(&self) -> usize {
+        crate::packets::writer::match_len(self, true)
+    }
+}
+
+impl BanchoDeserialize for Match {
+    fn deserialize(buf: &mut Bytes) -> Result<Self, DecodeError> {
+        crate::packets::reader::read_match(buf)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ReleaseStream {
+    Stable,
+    Beta,
+    CuttingEdge,
+    Tourney,
+    Dev,
+}
+
+impl ReleaseStream {
+    fn suffix(self) -> &'static str {
+        match self {
+            ReleaseStream::Stable => "",
+            ReleaseStream::Beta => "beta",
+            ReleaseStream::CuttingEdge => "cuttingedge",
+            ReleaseStream::Tourney => "tourney",
+            ReleaseStream::Dev => "dev",
+        }
+    }
+}
+
+/// The version a client sends at login, such as `b20230326.2cuttingedge`
+///
+/// Ordering compares the build date first and then the revision, the stream is only a tie breaker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ClientVersion {
+    /// The build date as `yyyymmdd`
+    pub date: u32,
+    /// The hotfix on top of the build, `None` when there isn't a `.n` part
+    pub revision: Option<u32>,
+    pub stream: ReleaseStream,
+}
+
+impl FromStr for ClientVersion {
+    type Err = DecodeError;
+
+    fn from_str(version: &str) -> Result<Self, DecodeError> {
+        let version = version
+            .strip_prefix('b')
+            .ok_or(DecodeError::BadClientVersion)?;
+
+        // the stream is whatever letters are left after the date and revision
+        let stream_start = version
+            .find(|c: char| c.is_ascii_alphabetic())
+            .unwrap_or(version.len());
+        let (number, stream) = version.split_at(stream_start);
+        let stream = match stream {
+            "" => ReleaseStream::Stable,
+            "beta" => ReleaseStream::Beta,
+            "cuttingedge" => ReleaseStream::CuttingEdge,
+            "tourney" => ReleaseStream::Tourney,
+            "dev" => ReleaseStream::Dev,
+            _ => return Err(DecodeError::BadClientVersion),
+        };
+
+        let (date, revision) = match number.split_once('.') {
+            Some((date, revision)) => (date, Some(revision)),
+            None => (number, None),
+        };
+        if date.len() != 8 {
+            return Err(DecodeError::BadClientVersion);
+        }
+        let parse = |number: &str| {
+            // `u32::from_str` allows a leading +, which the client never sends
+            if !number.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(DecodeError::BadClientVersion);
+            }
+            number.parse().map_err(|_| DecodeError::BadClientVersion)
+        };
+
+        Ok(ClientVersion {
+            date: parse(date)?,
+            revision: revision.map(parse).transpose()?,
+            stream,
+        })
+    }
+}
+
+impl fmt::Display for ClientVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "b{}", self.date)?;
+        if let Some(revision) = self.revision {
+            write!(f, ".{}", revision)?;
+        }
+        f.write_str(self.stream.suffix())
+    }
+}
+
+/// The version of the protocol a client speaks, which decides the layout of the packets it's sent
+///
+/// Sent to the client in `BanchoProtocolNegotiation`. Every client is negotiated [`ProtocolVersion::LATEST`],
+/// an older version only belongs here once its layouts, and the builds speaking it, are known from captures
+/// of real clients.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default, Serialize, Deserialize,
+)]
+#[serde(try_from = "i32", into = "i32")]
+pub enum ProtocolVersion {
+    #[default]
+    V19 = 19,
+}
+
+impl ProtocolVersion {
+    pub const LATEST: ProtocolVersion = ProtocolVersion::V19;
+    /// Every version a packet can be laid out for
+    pub const ALL: [ProtocolVersion; 1] = [ProtocolVersion::V19];
+}
+
+impl TryFrom<i32> for ProtocolVersion {
+    type Error = DecodeError;
+
+    fn try_from(value: i32) -> Result<Self, DecodeError> {
+        match value {
+            19 => Ok(ProtocolVersion::V19),
+            _ => Err(DecodeError::UnknownValue {
+                kind: "ProtocolVersion",
+                value,
+            }),
+        }
+    }
+}
+
+impl From<ProtocolVersion> for i32 {
+    fn from(version: ProtocolVersion) -> Self {
+        version as i32
+    }
+}
+
+/// A network adapter's MAC address, as sent in the login hashes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct MacAddress(pub [u8; 6]);
+
+impl FromStr for MacAddress {
+    type Err = DecodeError;
+
+    fn from_str(mac: &str) -> Result<Self, DecodeError> {
+        let mut address = [0; 6];
+        let mut count = 0;
+        // clients have sent both `-` and `:` separated addresses
+        for c in mac.chars().filter(|&c| c != '-' && c != ':') {
+            let digit = c.to_digit(16).ok_or(DecodeError::BadClientHashes)? as u8;
+            let byte = address
+                .get_mut(count / 2)
+                .ok_or(DecodeError::BadClientHashes)?;
+            *byte = *byte << 4 | digit;
+            count += 1;
+        }
+
+        match count {
+            12 => Ok(MacAddress(address)),
+            _ => Err(DecodeError::BadClientHashes),
+        }
+    }
+}
+
+impl fmt::Display for MacAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [a, b, c, d, e, g] = self.0;
+        write!(f, "{a:02X}-{b:02X}-{c:02X}-{d:02X}-{e:02X}-{g:02X}")
+    }
+}
+
+/// The hardware hashes a client sends at login, `path:adapters:adapters md5:uninstall id:disk signature:`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientHashes {
+    /// md5 of the path osu! is installed to
+    pub path_md5: ByteStr,
+    /// Empty when `running_under_wine` is set, wine doesn't expose real adapters
+    pub adapters: Vec<MacAddress>,
+    pub adapters_md5: ByteStr,
+    /// md5 of the id osu! generates on install, stable across reinstalls on the same machine
+    pub uninstall_md5: ByteStr,
+    /// md5 of the disk serial, or of `unknown` when the client couldn't read it
+    pub disk_signature_md5: ByteStr,
+    pub running_under_wine: bool,
+}
+
+impl ClientHashes {
+    /// Marker sent in place of the adapter list by clients running under wine
+    pub const WINE_MARKER: &'static str = "runningunderwine";
+
+    pub fn parse(hashes: &ByteStr) -> Result<Self, DecodeError> {
+        let mut split = hashes.split(':');
+        let mut next = || {
+            split
+                .next()
+                .map(|hash| hashes.slice_ref(hash))
+                .ok_or(DecodeError::BadClientHashes)
+        };
+
+        let path_md5 = next()?;
+        let adapters_string = next()?;
+        let adapters_md5 = next()?;
+        let uninstall_md5 = next()?;
+        let disk_signature_md5 = next()?;
+
+        let running_under_wine = *adapters_string == *Self::WINE_MARKER;
+        let adapters = match running_under_wine {
+            true => Vec::new(),
+            // every address is followed by a `.`, so skip the empty string after the last one
+            false => adapters_string
+                .split('.')
+                .filter(|mac| !mac.is_empty())
+                .map(str::parse)
+                .collect::<Result<_, _>>()?,
+        };
+
+        Ok(ClientHashes {
+            path_md5,
+            adapters,
+            adapters_md5,
+            uninstall_md5,
+            disk_signature_md5,
+            running_under_wine,
+        })
+    }
+}
+
+impl fmt::Display for ClientHashes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:", self.path_md5)?;
+        if self.running_under_wine {
+            f.write_str(Self::WINE_MARKER)?;
+        }
+        for adapter in &self.adapters {
+            write!(f, "{adapter}.")?;
+        }
+        write!(
+            f,
+            ":{}:{}:{}:",
+            self.adapters_md5, self.uninstall_md5, self.disk_signature_md5
+        )
+    }
+}
@@ -1,7 +1,8 @@
 use bytes::BufMut;
 
 use crate::buffer::serialization::Buffer;
-use crate::buffer::serialization::BytesMutExt;
+use crate::buffer::serialization::{optional_string_len, string_len, BufMutExt, BytesMutExt};
+use crate::buffer::serialization::{BanchoPacket, BanchoSerialize};
 use crate::packets::packet_ids::PacketIDs;
 use crate::packets::structures;
 
@@ -11,37 +12,15 @@ pub fn bancho_login_reply(buf: &mut Buffer, player_id: i32) {
     });
 }
 
-pub fn bancho_send_message(buf: &mut Buffer, message: structures::BanchoMessage) {
-    buf.with_header(PacketIDs::BanchoSendMessage as i16, |buf| {
-        buf.put_string(&message.sending_client);
-        buf.put_string(&message.message);
-        buf.put_string(&message.target);
-        buf.put_i32_le(message.sender_id);
-    });
+pub fn bancho_send_message(buf: &mut Buffer, message: &structures::BanchoMessage) {
+    message.write_packet(buf);
 }
 pub fn bancho_ping(buf: &mut Buffer) {
     buf.with_header(PacketIDs::BanchoPing as i16, |_| {})
 }
 
-pub fn bancho_handle_osu_update(buf: &mut Buffer, stats: structures::BanchoStats) {
-    buf.with_header(PacketIDs::BanchoHandleOsuUpdate as i16, |buf| {
-        buf.put_i32_le(stats.player_id);
-        
-        buf.put_u8(stats.status.status);
-        buf.put_string(stats.status.status_text.as_str());
-        buf.put_string(stats.status.beatmap_checksum.as_str());
-        buf.put_u32_le(stats.status.current_mods);
-        buf.put_u8(stats.status.play_mode);
-        
-        buf.put_i32_le(stats.status.beatmap_id);
-        buf.put_i64_le(stats.ranked_score);
-        buf.put_f32_le(stats.accuracy);
-        
-        buf.put_i32_le(stats.play_count);
-        buf.put_i64_le(stats.total_score);
-        buf.put_i32_le(stats.rank);
-        buf.put_i16_le(stats.performance);
-    });
+pub fn bancho_handle_osu_update(buf: &mut Buffer, stats: &structures::BanchoStats) {
+    stats.write_packet(buf);
 }
 
 pub fn bancho_handle_user_quit(buf: &mut Buffer, player_id: i32) {
@@ -81,12 +60,8 @@ pub fn bancho_channel_join_success(buf: &mut Buffer, channel_name: &str) {
     })
 }
 
-pub fn bancho_channel_available(buf: &mut Buffer, channel: structures::BanchoChannel) {
-    buf.with_header(PacketIDs::BanchoChannelAvailable as i16, |buf| {
-        buf.put_string(&channel.name);
-        buf.put_string(&channel.topic);
-        buf.put_i16_le(channel.connected);
-    })
+pub fn bancho_channel_available(buf: &mut Buffer, channel: &structures::BanchoChannel) {
+    channel.write_packet(buf);
 }
 
 pub fn bancho_channel_revoked(buf: &mut Buffer, channel_name: &str) {
@@ -95,28 +70,25 @@ pub fn bancho_channel_revoked(buf: &mut Buffer, channel_name: &str) {
     })
 }
 
-pub fn bancho_login_permissions(buf: &mut Buffer, permissions: u8) {
+pub fn bancho_login_permissions(buf: &mut Buffer, permissions: structures::BanchoPrivileges) {
     buf.with_header(PacketIDs::BanchoLoginPermissions as i16, |buf| {
-        buf.put_u8(permissions)
+        permissions.serialize(buf)
     })
 }
 
-pub fn bancho_protocol_negotiaton(buf: &mut Buffer, version: i32) {
+pub fn bancho_protocol_negotiaton(buf: &mut Buffer, version: structures::ProtocolVersion) {
     buf.with_header(PacketIDs::BanchoProtocolNegotiation as i16, |buf| {
-        buf.put_i32_le(version);
+        buf.put_i32_le(version.into());
     })
 }
 
-pub fn bancho_user_presence(buf: &mut Buffer, presence: structures::BanchoPresence) {
+pub fn bancho_user_presence(
+    buf: &mut Buffer,
+    presence: &structures::BanchoPresence,
+    protocol: structures::ProtocolVersion,
+) {
     buf.with_header(PacketIDs::BanchoUserPresence as i16, |buf| {
-        buf.put_i32_le(presence.player_id);
-        buf.put_string(presence.username.as_str());
-        buf.put_u8(presence.timezone);
-        buf.put_u8(presence.country_code);
-        buf.put_u8(presence.play_mode);
-        buf.put_f32_le(presence.longitude);
-        buf.put_f32_le(presence.latitude);
-        buf.put_i32_le(presence.player_rank);
+        write_presence(buf, presence, protocol)
     })
 }
 
@@ -132,21 +104,15 @@ pub fn bancho_ban_info(buf: &mut Buffer, ban_info: u32) {
     })
 }
 
-pub fn bancho_user_pm_blocked(buf: &mut Buffer, message: structures::BanchoMessage) {
+pub fn bancho_user_pm_blocked(buf: &mut Buffer, message: &structures::BanchoMessage) {
     buf.with_header(PacketIDs::BanchoUserPmBlocked as i16, |buf| {
-        buf.put_string(&message.sending_client);
-        buf.put_string(&message.message);
-        buf.put_string(&message.target);
-        buf.put_i32_le(message.sender_id);
+        message.serialize(buf)
     });
 }
 
-pub fn bacnho_target_is_silenced(buf: &mut Buffer, message: structures::BanchoMessage) {
+pub fn bacnho_target_is_silenced(buf: &mut Buffer, message: &structures::BanchoMessage) {
     buf.with_header(PacketIDs::BanchoTargetIsSilenced as i16, |buf| {
-        buf.put_string(&message.sending_client);
-        buf.put_string(&message.message);
-        buf.put_string(&message.target);
-        buf.put_i32_le(message.sender_id);
+        message.serialize(buf)
     });
 }
 
@@ -169,3 +135,281 @@ pub fn bancho_rtx(buf: &mut Buffer, rtx: &str) {
         buf.put_string(rtx);
     })
 }
+
+pub fn bancho_match_new(buf: &mut Buffer, game: &structures::Match, send_password: bool) {
+    buf.with_header(PacketIDs::BanchoMatchNew as i16, |buf| {
+        write_match(buf, game, send_password);
+    })
+}
+
+pub fn bancho_match_update(buf: &mut Buffer, game: &structures::Match, send_password: bool) {
+    buf.with_header(PacketIDs::BanchoMatchUpdate as i16, |buf| {
+        write_match(buf, game, send_password);
+    })
+}
+
+pub fn bancho_match_join_success(buf: &mut Buffer, game: &structures::Match) {
+    buf.with_header(PacketIDs::BanchoMatchJoinSuccess as i16, |buf| {
+        write_match(buf, game, true);
+    })
+}
+
+pub fn bancho_match_start(buf: &mut Buffer, game: &structures::Match) {
+    buf.with_header(PacketIDs::BanchoMatchStart as i16, |buf| {
+        write_match(buf, game, true);
+    })
+}
+
+pub fn bancho_spectate_frames(buf: &mut Buffer, bundle: &structures::ReplayFrameBundle) {
+    bundle.write_packet(buf);
+}
+
+/// Relay the payload of a `ClientSpectateFrames` packet as is, without decoding it
+pub fn bancho_spectate_frames_raw(buf: &mut Buffer, bundle: &[u8]) {
+    buf.with_header(PacketIDs::BanchoSpectateFrames as i16, |buf| {
+        buf.put_slice(bundle);
+    })
+}
+
+pub fn bancho_match_score_update(buf: &mut Buffer, score_frame: &structures::ScoreFrame) {
+    score_frame.write_packet(buf);
+}
+
+/// Relay the payload of a `ClientMatchScoreUpdate` packet, only patching in the sender's slot id
+pub fn bancho_match_score_update_raw(buf: &mut Buffer, score_frame: &[u8], slot_id: u8) {
+    buf.with_header(PacketIDs::BanchoMatchScoreUpdate as i16, |buf| {
+        let start = buf.len();
+        buf.put_slice(score_frame);
+        if let Some(id) = buf.get_mut(start + structures::ScoreFrame::ID_OFFSET) {
+            *id = slot_id;
+        }
+    })
+}
+
+pub fn bancho_fellow_spectator_joined(buf: &mut Buffer, player_id: i32) {
+    buf.with_header(PacketIDs::BanchoFellowSpectatorJoined as i16, |buf| {
+        buf.put_i32_le(player_id);
+    })
+}
+
+pub fn bancho_fellow_spectator_left(buf: &mut Buffer, player_id: i32) {
+    buf.with_header(PacketIDs::BanchoFellowSpectatorLeft as i16, |buf| {
+        buf.put_i32_le(player_id);
+    })
+}
+
+pub fn bancho_version_update(buf: &mut Buffer) {
+    buf.with_header(PacketIDs::BanchoVersionUpdate as i16, |_| {})
+}
+
+pub fn bancho_get_attention(buf: &mut Buffer) {
+    buf.with_header(PacketIDs::BanchoGetAttention as i16, |_| {})
+}
+
+pub fn bancho_monitor(buf: &mut Buffer) {
+    buf.with_header(PacketIDs::BanchoMonitor as i16, |_| {})
+}
+
+/// Tell the client the server is restarting, it reconnects after `delay_ms`
+pub fn bancho_restart(buf: &mut Buffer, delay_ms: i32) {
+    buf.with_header(PacketIDs::BanchoRestart as i16, |buf| {
+        buf.put_i32_le(delay_ms);
+    })
+}
+
+/// Set the image on the main menu, `title` is the image url and the url it links to, split by a `|`
+pub fn bancho_title_update(buf: &mut Buffer, title: &str) {
+    buf.with_header(PacketIDs::BanchoTitleUpdate as i16, |buf| {
+        buf.put_string(title);
+    })
+}
+
+pub fn bancho_switch_tourney_server(buf: &mut Buffer, server: &str) {
+    buf.with_header(PacketIDs::BanchoSwitchTourneyServer as i16, |buf| {
+        buf.put_string(server);
+    })
+}
+
+pub fn bancho_friends_list(buf: &mut Buffer, friend_ids: &[i32]) {
+    buf.with_header(PacketIDs::BanchoFriendsList as i16, |buf| {
+        buf.put_i32_list(friend_ids);
+    })
+}
+
+/// List the players who are online, the client asks for the presences it wants after
+pub fn bancho_user_presence_bundle(buf: &mut Buffer, player_ids: &[i32]) {
+    buf.with_header(PacketIDs::BanchoUserPresenceBundle as i16, |buf| {
+        buf.put_i32_list(player_ids);
+    })
+}
+
+pub fn bancho_user_presence_single(buf: &mut Buffer, player_id: i32) {
+    buf.with_header(PacketIDs::BanchoUserPresenceSingle as i16, |buf| {
+        buf.put_i32_le(player_id);
+    })
+}
+
+pub fn bancho_user_silenced(buf: &mut Buffer, player_id: i32) {
+    buf.with_header(PacketIDs::BanchoUserSilenced as i16, |buf| {
+        buf.put_i32_le(player_id);
+    })
+}
+
+/// An invite to a match, the message holds the link to it
+pub fn bancho_invite(buf: &mut Buffer, message: &structures::BanchoMessage) {
+    buf.with_header(PacketIDs::BanchoInvite as i16, |buf| message.serialize(buf));
+}
+
+pub fn bancho_channel_available_autojoin(buf: &mut Buffer, channel: &structures::BanchoChannel) {
+    buf.with_header(PacketIDs::BanchoChannelAvailableAutojoin as i16, |buf| {
+        channel.serialize(buf)
+    });
+}
+
+pub fn bancho_match_transfer_host(buf: &mut Buffer) {
+    buf.with_header(PacketIDs::BanchoMatchTransferHost as i16, |_| {})
+}
+
+pub fn bancho_match_all_players_loaded(buf: &mut Buffer) {
+    buf.with_header(PacketIDs::BanchoMatchAllPlayersLoaded as i16, |_| {})
+}
+
+pub fn bancho_match_player_failed(buf: &mut Buffer, slot_id: i32) {
+    buf.with_header(PacketIDs::BanchoMatchPlayerFailed as i16, |buf| {
+        buf.put_i32_le(slot_id);
+    })
+}
+
+pub fn bancho_match_complete(buf: &mut Buffer) {
+    buf.with_header(PacketIDs::BanchoMatchComplete as i16, |_| {})
+}
+
+pub fn bancho_match_skip(buf: &mut Buffer) {
+    buf.with_header(PacketIDs::BanchoMatchSkip as i16, |_| {})
+}
+
+pub fn bancho_match_player_skipped(buf: &mut Buffer, player_id: i32) {
+    buf.with_header(PacketIDs::BanchoMatchPlayerSkipped as i16, |buf| {
+        buf.put_i32_le(player_id);
+    })
+}
+
+pub fn bancho_match_change_password(buf: &mut Buffer, password: &str) {
+    buf.with_header(PacketIDs::BanchoMatchChangePassword as i16, |buf| {
+        buf.put_string(password);
+    })
+}
+
+pub fn bancho_beatmap_info_reply(buf: &mut Buffer, reply: &structures::BeatmapInfoReply) {
+    reply.write_packet(buf);
+}
+
+pub fn write_message(buf: &mut impl BufMut, message: &structures::BanchoMessage) {
+    message.serialize(buf);
+}
+
+pub fn write_status(buf: &mut impl BufMut, status: &structures::ClientStatus) {
+    status.serialize(buf);
+}
+
+pub fn write_stats(buf: &mut impl BufMut, stats: &structures::BanchoStats) {
+    stats.serialize(buf);
+}
+
+pub fn write_presence(
+    buf: &mut impl BufMut,
+    presence: &structures::BanchoPresence,
+    protocol: structures::ProtocolVersion,
+) {
+    presence.serialize_for(buf, protocol);
+}
+
+pub fn write_channel(buf: &mut impl BufMut, channel: &structures::BanchoChannel) {
+    channel.serialize(buf);
+}
+
+pub fn write_beatmap_info_reply(buf: &mut impl BufMut, reply: &structures::BeatmapInfoReply) {
+    reply.serialize(buf);
+}
+
+/// Write a match, only including the password if `send_password` is set
+///
+/// Players in the lobby should only be told that there is a password, members of the match get the real one.
+pub fn write_match(buf: &mut impl BufMut, game: &structures::Match, send_password: bool) {
+    buf.put_i16_le(game.match_id);
+    buf.put_bool(game.in_progress);
+    buf.put_u8(game.match_type);
+    game.active_mods.serialize(buf);
+    buf.put_string(&game.game_name);
+    match &game.game_password {
+        Some(_) if !send_password => buf.put_string(""),
+        password => buf.put_optional_string(password.as_deref()),
+    }
+    buf.put_string(&game.beatmap_name);
+    buf.put_i32_le(game.beatmap_id);
+    buf.put_string(&game.beatmap_checksum);
+
+    for slot in &game.slots {
+        buf.put_u8(slot.status);
+    }
+    for slot in &game.slots {
+        buf.put_u8(slot.team);
+    }
+    for slot in game.slots.iter().filter(|slot| slot.has_player()) {
+        buf.put_i32_le(slot.player_id);
+    }
+
+    buf.put_i32_le(game.host_id);
+    game.play_mode.serialize(buf);
+    buf.put_u8(game.match_scoring_type);
+    buf.put_u8(game.match_team_type);
+    buf.put_bool(game.free_mod);
+    if game.free_mod {
+        for slot in &game.slots {
+            slot.slot_mods.serialize(buf);
+        }
+    }
+    buf.put_i32_le(game.seed);
+}
+
+/// Number of bytes [`write_match`] writes for `game`
+pub fn match_len(game: &structures::Match, send_password: bool) -> usize {
+    let password_len = match &game.game_password {
+        Some(_) if !send_password => string_len(""),
+        password => optional_string_len(password.as_deref()),
+    };
+    let players = game.slots.iter().filter(|slot| slot.has_player()).count();
+    let slot_mods = match game.free_mod {
+        true => structures::MATCH_SLOTS,
+        false => 0,
+    };
+
+    // id, in progress, type and mods
+    let settings = 2 + 1 + 1 + 4;
+    // host, mode, scoring type, team type, free mod and seed
+    let rest = 4 + 1 + 1 + 1 + 1 + 4;
+
+    settings
+        + string_len(&game.game_name)
+        + password_len
+        + string_len(&game.beatmap_name)
+        + 4
+        + string_len(&game.beatmap_checksum)
+        // a status and a team for every slot, then the ids of the players in them
+        + 2 * structures::MATCH_SLOTS
+        + 4 * players
+        + 4 * slot_mods
+        + rest
+}
+
+pub fn write_frame_bundle(buf: &mut impl BufMut, bundle: &structures::ReplayFrameBundle) {
+    bundle.serialize(buf);
+}
+
+pub fn write_replay_frame(buf: &mut impl BufMut, frame: &structures::ReplayFrame) {
+    frame.serialize(buf);
+}
+
+pub fn write_score_frame(buf: &mut impl BufMut, score_frame: &structures::ScoreFrame) {
+    score_frame.serialize(buf);
+}
@@ -0,0 +1,163 @@
+use std::process::Command;
+
+use bancho_packet::{
+    buffer::serialization::{Buffer, BytesMutExt},
+    error::DecodeError,
+    packets::{
+        capture::{write_header, CaptureReader, Record, RecordKind},
+        writer::{bancho_announce, bancho_login_reply},
+    },
+};
+use bytes::{BufMut, Bytes};
+
+fn records() -> Vec<Record> {
+    let mut response = Buffer::new();
+    bancho_login_reply(&mut response, 1000);
+    bancho_announce(&mut response, "welcome!");
+
+    vec![
+        Record {
+            kind: RecordKind::Login,
+            timestamp: 1_680_000_000_000_000,
+            token: "token".into(),
+            body: Bytes::from_static(
+                b"peppy\n5f4dcc3b5aa765d61d8327deb882cf99\nb20230326|0|0|a:runningunderwine:c:d:e:|1\n",
+            ),
+        },
+        Record {
+            kind: RecordKind::Response,
+            timestamp: 1_680_000_000_012_500,
+            token: "token".into(),
+            body: response.freeze(),
+        },
+    ]
+}
+
+fn capture(records: &[Record]) -> Buffer {
+    let mut buf = Buffer::new();
+    write_header(&mut buf);
+    for record in records {
+        record.write(&mut buf);
+    }
+    buf
+}
+
+#[test]
+fn records_round_trip() {
+    let records = records();
+    let read = CaptureReader::new(capture(&records).freeze())
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>();
+    assert_eq!(read, Ok(records));
+}
+
+#[test]
+fn bad_captures() {
+    assert_eq!(
+        CaptureReader::new(Bytes::from_static(b"PCAP\x01")).err(),
+        Some(DecodeError::BadCaptureMagic)
+    );
+    assert_eq!(
+        CaptureReader::new(Bytes::from_static(b"BCAP\x09")).err(),
+        Some(DecodeError::UnknownValue {
+            kind: "capture version",
+            value: 9
+        })
+    );
+
+    // a record cut off part way through its body
+    let mut buf = capture(&records());
+    buf.truncate(buf.len() - 4);
+    let mut reader = CaptureReader::new(buf.freeze()).unwrap();
+    assert!(reader.next().unwrap().is_ok());
+    assert!(matches!(
+        reader.next(),
+        Some(Err(DecodeError::OverlongLength { .. }))
+    ));
+    assert!(reader.next().is_none());
+}
+
+fn dump(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_bancho-dump"))
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn dump_flags_unknown_and_trailing_bytes() {
+    let output = dump(&[
+        "--hex",
+        "05 00 00 04 00 00 00 e8 03 00 00",
+        // an id nothing uses
+        "ff 7f 00 01 00 00 00 aa",
+        // a ping with a byte it doesn't need
+        "08 00 00 01 00 00 00 bb",
+        // half a header
+        "05 00 00",
+    ]);
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(
+        lines,
+        [
+            "  BanchoLoginReply (4 bytes) BanchoLoginReply(1000)",
+            "  ! unknown packet id 32767 (1 bytes): aa",
+            "  BanchoPing (1 bytes) BanchoPing",
+            "    ! 1 trailing bytes in payload: bb",
+            "  ! 3 trailing bytes (unexpected end of packet): 05 00 00",
+        ]
+    );
+}
+
+#[test]
+fn dump_keeps_going_after_a_bad_compressed_payload() {
+    let output = dump(&[
+        "--hex",
+        // a login reply flagged as compressed, but not gzip
+        "05 00 01 03 00 00 00 aa bb cc",
+        "08 00 00 00 00 00 00",
+    ]);
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(
+        lines,
+        [
+            "  ! BanchoLoginReply (3 bytes, compressed) failed to decompress: aa bb cc",
+            "  BanchoPing (0 bytes) BanchoPing",
+        ]
+    );
+}
+
+#[test]
+fn dump_capture_file() {
+    let path = std::env::temp_dir().join(format!("bancho-dump-{}.bcap", std::process::id()));
+    let mut buf = capture(&records());
+    // a body with a packet that doesn't decode
+    Record {
+        kind: RecordKind::Request,
+        timestamp: 1_680_000_001_000_000,
+        token: "token".into(),
+        body: {
+            let mut body = Buffer::new();
+            body.with_header(0, |buf| buf.put_u8(99));
+            body.freeze()
+        },
+    }
+    .write(&mut buf);
+    std::fs::write(&path, &buf).unwrap();
+
+    let output = dump(&[path.to_str().unwrap()]);
+    std::fs::remove_file(&path).unwrap();
+
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(lines[0], "+0.000000s -> Login token (81 bytes)");
+    assert!(lines[1].starts_with("  login peppy on b20230326, utc+0"));
+    assert_eq!(lines[2], "+0.012500s <- Response token (28 bytes)");
+    assert_eq!(
+        lines[3],
+        "  BanchoLoginReply (4 bytes) BanchoLoginReply(1000)"
+    );
+    assert_eq!(lines[5], "+1.000000s -> Request token (8 bytes)");
+    assert!(lines[6].starts_with("  ! ClientSendUserStatus (1 bytes) failed to decode"));
+}
@@ -0,0 +1,111 @@
+#![cfg(feature = "codec")]
+
+use bancho_packet::{
+    buffer::serialization::Buffer,
+    error::{CodecError, DecodeError},
+    packets::{
+        codec::{BanchoCodec, FrameCodec},
+        packet::Packet,
+        packet_ids::PacketIDs,
+    },
+};
+use bytes::{BufMut, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+fn packets() -> Vec<Packet> {
+    vec![
+        Packet::BanchoLoginReply(3),
+        Packet::BanchoAnnounce("welcome".into()),
+        Packet::BanchoUserPresenceBundle((0..100).collect()),
+        Packet::BanchoPing,
+    ]
+}
+
+#[test]
+fn decodes_packets_arriving_a_byte_at_a_time() {
+    let mut encoded = BytesMut::new();
+    for packet in packets() {
+        BanchoCodec::new().encode(packet, &mut encoded).unwrap();
+    }
+
+    let mut codec = BanchoCodec::new();
+    let mut src = BytesMut::new();
+    let mut decoded = Vec::new();
+    for byte in encoded {
+        src.put_u8(byte);
+        while let Some(packet) = codec.decode(&mut src).unwrap() {
+            decoded.push(packet);
+        }
+    }
+
+    assert_eq!(decoded, packets());
+    assert!(src.is_empty());
+}
+
+#[test]
+fn frames_survive_a_bad_payload() {
+    let mut src = BytesMut::new();
+    src.put_i16_le(PacketIDs::BanchoAnnounce as i16);
+    src.put_u8(0);
+    src.put_u32_le(1);
+    // not a valid string marker
+    src.put_u8(0x05);
+    Packet::BanchoPing.encode(&mut src);
+
+    let mut codec = FrameCodec::new();
+    let bad = codec.decode(&mut src).unwrap().unwrap().unwrap();
+    assert_eq!(
+        Packet::from_frame(bad),
+        Err(DecodeError::BadStringMarker(0x05))
+    );
+    let ping = codec.decode(&mut src).unwrap().unwrap().unwrap();
+    assert_eq!(Packet::from_frame(ping), Ok(Packet::BanchoPing));
+}
+
+#[test]
+fn frames_survive_a_bad_compressed_payload() {
+    let mut src = BytesMut::new();
+    src.put_i16_le(PacketIDs::BanchoAnnounce as i16);
+    src.put_u8(1);
+    src.put_u32_le(4);
+    // a gzip magic with nothing valid after it
+    src.put_slice(&[0x1f, 0x8b, 0xff, 0xff]);
+    Packet::BanchoPing.encode(&mut src);
+
+    let mut codec = FrameCodec::new();
+    assert_eq!(
+        codec.decode(&mut src).unwrap(),
+        Some(Err(DecodeError::BadCompression))
+    );
+    let ping = codec.decode(&mut src).unwrap().unwrap().unwrap();
+    assert_eq!(Packet::from_frame(ping), Ok(Packet::BanchoPing));
+}
+
+#[test]
+fn overlong_packets_are_rejected() {
+    let mut src = BytesMut::new();
+    src.put_i16_le(PacketIDs::BanchoPing as i16);
+    src.put_u8(0);
+    src.put_u32_le(1025);
+
+    assert!(matches!(
+        FrameCodec::with_max_length(1024).decode(&mut src),
+        Err(CodecError::TooLong {
+            length: 1025,
+            max: 1024
+        })
+    ));
+}
+
+#[test]
+fn raw_packets_are_passed_through() {
+    let mut buf = Buffer::new();
+    for packet in packets() {
+        packet.encode(&mut buf);
+    }
+    let buf = buf.freeze();
+
+    let mut dst = BytesMut::new();
+    FrameCodec::new().encode(buf.clone(), &mut dst).unwrap();
+    assert_eq!(dst, buf);
+}
@@ -0,0 +1,126 @@
+use bancho_packet::{
+    buffer::serialization::{Buffer, BytesMutExt},
+    error::DecodeError,
+    packets::{
+        framer::{PacketFramer, HEADER_LEN},
+        packet::Packet,
+        packet_ids::PacketIDs,
+        structures::BanchoMessage,
+    },
+};
+use bytes::BufMut;
+
+// a BanchoSendMessage payload from peppy to #osu saying "hello", compressed by python's gzip and zlib modules
+const GZIP: &[u8] = &[
+    0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0xe3, 0x66, 0x2d, 0x48, 0x2d, 0x28,
+    0xa8, 0xe4, 0x66, 0xcd, 0x48, 0xcd, 0xc9, 0xc9, 0xe7, 0x66, 0x51, 0xce, 0x2f, 0x2e, 0x65, 0x62,
+    0x60, 0x60, 0x00, 0x00, 0xf4, 0xdc, 0x17, 0xb8, 0x18, 0x00, 0x00, 0x00,
+];
+const ZLIB: &[u8] = &[
+    0x78, 0x9c, 0xe3, 0x66, 0x2d, 0x48, 0x2d, 0x28, 0xa8, 0xe4, 0x66, 0xcd, 0x48, 0xcd, 0xc9, 0xc9,
+    0xe7, 0x66, 0x51, 0xce, 0x2f, 0x2e, 0x65, 0x62, 0x60, 0x60, 0x00, 0x00, 0x52, 0xbe, 0x05, 0xee,
+];
+const DEFLATE: &[u8] = &[
+    0xe3, 0x66, 0x2d, 0x48, 0x2d, 0x28, 0xa8, 0xe4, 0x66, 0xcd, 0x48, 0xcd, 0xc9, 0xc9, 0xe7, 0x66,
+    0x51, 0xce, 0x2f, 0x2e, 0x65, 0x62, 0x60, 0x60, 0x00, 0x00,
+];
+
+fn message() -> BanchoMessage {
+    BanchoMessage {
+        sending_client: "peppy".into(),
+        message: "hello".into(),
+        target: "#osu".into(),
+        sender_id: 2,
+    }
+}
+
+fn compressed_packet(buf: &mut Buffer, payload: &[u8]) {
+    buf.put_i16_le(PacketIDs::BanchoSendMessage as i16);
+    buf.put_u8(1);
+    buf.put_u32_le(payload.len() as u32);
+    buf.put_slice(payload);
+}
+
+#[test]
+fn known_fixtures_decompress() {
+    for fixture in [GZIP, ZLIB, DEFLATE] {
+        let mut buf = Buffer::new();
+        compressed_packet(&mut buf, fixture);
+        // an uncompressed packet after it should still line up
+        buf.with_header(PacketIDs::BanchoPing as i16, |_| {});
+
+        let mut frames = PacketFramer::new(buf.freeze());
+        let frame = frames.next().unwrap().unwrap();
+        assert!(frame.compressed);
+        assert_eq!(
+            Packet::from_frame(frame),
+            Ok(Packet::BanchoSendMessage(message()))
+        );
+        assert_eq!(
+            Packet::from_frame(frames.next().unwrap().unwrap()),
+            Ok(Packet::BanchoPing)
+        );
+        assert!(frames.next().is_none());
+    }
+}
+
+#[test]
+fn bad_compressed_payload() {
+    let mut buf = Buffer::new();
+    compressed_packet(&mut buf, &GZIP[..20]);
+
+    assert_eq!(
+        Packet::decode(&mut buf.freeze()),
+        Err(DecodeError::BadCompression)
+    );
+}
+
+#[test]
+fn frames_after_a_bad_compressed_payload_survive() {
+    let mut buf = Buffer::new();
+    compressed_packet(&mut buf, &GZIP[..20]);
+    buf.with_header(PacketIDs::BanchoPing as i16, |_| {});
+
+    let mut frames = PacketFramer::new(buf.freeze());
+    assert_eq!(frames.next(), Some(Err(DecodeError::BadCompression)));
+    assert_eq!(
+        Packet::from_frame(frames.next().unwrap().unwrap()),
+        Ok(Packet::BanchoPing)
+    );
+    assert!(frames.next().is_none());
+}
+
+#[test]
+fn incompressible_payloads_are_sent_as_is() {
+    // a payload this short only grows once the gzip header and trailer are added
+    let packet = Packet::BanchoSendMessage(message());
+
+    let mut plain = Buffer::new();
+    packet.encode(&mut plain);
+
+    let mut buf = Buffer::new();
+    packet.encode_compressed(&mut buf, 0);
+    assert_eq!(buf, plain);
+}
+
+#[test]
+fn compresses_above_threshold() {
+    let packet = Packet::BanchoSendMessage(BanchoMessage {
+        message: "a".repeat(4096).into(),
+        ..message()
+    });
+
+    let mut plain = Buffer::new();
+    packet.encode(&mut plain);
+
+    // at or below the threshold the packet is left alone
+    let mut buf = Buffer::new();
+    packet.encode_compressed(&mut buf, plain.len() - HEADER_LEN);
+    assert_eq!(buf, plain);
+
+    let mut buf = Buffer::new();
+    packet.encode_compressed(&mut buf, 1024);
+    assert_eq!(buf[2], 1);
+    assert!(buf.len() < plain.len());
+    assert_eq!(Packet::decode(&mut buf.freeze()), Ok(packet));
+}
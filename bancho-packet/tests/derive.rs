@@ -0,0 +1,131 @@
+use bancho_packet::{
+    buffer::serialization::{
+        BanchoDeserialize, BanchoPacket, BanchoSerialize, BufMutExt, Buffer, BytesMutExt,
+    },
+    error::DecodeError,
+    packets::{packet_ids::PacketIDs, structures::BanchoMessage},
+};
+use bytes::BufMut;
+
+#[derive(Debug, PartialEq, BanchoSerialize, BanchoDeserialize)]
+#[bancho(id = BanchoFriendsList)]
+struct Layout {
+    name: String,
+    password: Option<String>,
+    #[bancho(list = "i16")]
+    short_list: Vec<i32>,
+    #[bancho(list = "i32")]
+    long_list: Vec<u8>,
+    #[bancho(skip)]
+    server_side: u32,
+    has_extra: bool,
+    #[bancho(if = "has_extra")]
+    extra: Option<f64>,
+}
+
+fn layout(has_extra: bool) -> Layout {
+    Layout {
+        name: "peppy".to_string(),
+        password: None,
+        short_list: vec![1, 2, 3],
+        long_list: vec![4, 5],
+        server_side: 0,
+        has_extra,
+        extra: has_extra.then_some(0.5),
+    }
+}
+
+#[test]
+fn fields_follow_declaration_order() {
+    let mut expected = Buffer::new();
+    expected.put_string("peppy");
+    expected.put_optional_string(None);
+    expected.put_i32_list(&[1, 2, 3]);
+    expected.put_i32_le(2);
+    expected.put_slice(&[4, 5]);
+    expected.put_bool(true);
+    expected.put_f64_le(0.5);
+
+    let mut buf = Buffer::new();
+    layout(true).serialize(&mut buf);
+    assert_eq!(buf, expected);
+    assert_eq!(layout(true).encoded_len(), expected.len());
+    assert_eq!(layout(false).encoded_len(), expected.len() - 8);
+}
+
+#[test]
+fn derived_round_trip() {
+    for has_extra in [false, true] {
+        let mut buf = Buffer::new();
+        Layout {
+            server_side: 42,
+            ..layout(has_extra)
+        }
+        .serialize(&mut buf);
+
+        // skipped fields come back as their default
+        assert_eq!(
+            Layout::deserialize(&mut buf.freeze()),
+            Ok(layout(has_extra))
+        );
+    }
+}
+
+#[test]
+fn lists_are_checked() {
+    let mut buf = Buffer::new();
+    buf.put_string("peppy");
+    buf.put_optional_string(None);
+    buf.put_i16_le(3);
+    buf.put_i32_le(1);
+
+    assert_eq!(
+        Layout::deserialize(&mut buf.freeze()),
+        Err(DecodeError::UnexpectedEof)
+    );
+}
+
+#[test]
+fn negative_list_lengths_are_refused() {
+    let mut buf = Buffer::new();
+    buf.put_string("peppy");
+    buf.put_optional_string(None);
+    buf.put_i16_le(-1);
+
+    assert_eq!(
+        Layout::deserialize(&mut buf.freeze()),
+        Err(DecodeError::NegativeLength(-1))
+    );
+}
+
+#[test]
+#[should_panic(expected = "`short_list` is too long for its i16 length")]
+fn overlong_lists_panic() {
+    let layout = Layout {
+        short_list: vec![0; i16::MAX as usize + 1],
+        ..layout(false)
+    };
+    layout.serialize(&mut Buffer::new());
+}
+
+#[test]
+fn packet_id_from_attribute() {
+    assert_eq!(Layout::ID, PacketIDs::BanchoFriendsList);
+
+    let message = BanchoMessage {
+        sending_client: "peppy".into(),
+        message: "hello".into(),
+        target: "#osu".into(),
+        sender_id: 2,
+    };
+    let mut buf = Buffer::new();
+    message.write_packet(&mut buf);
+
+    let mut expected = Buffer::new();
+    expected.with_header(PacketIDs::BanchoSendMessage as i16, |buf| {
+        message.serialize(buf)
+    });
+    assert_eq!(buf, expected);
+    assert_eq!(message.packet_len(), expected.len());
+    assert_eq!(message.to_packet_bytes(), expected.freeze());
+}
@@ -0,0 +1,88 @@
+use bancho_packet::{
+    buffer::serialization::Buffer,
+    packets::{
+        framer::PacketFramer,
+        packet::Packet,
+        structures::{ReplayFrame, ReplayFrameBundle, ScoreFrame},
+        writer::{bancho_match_score_update_raw, bancho_spectate_frames_raw},
+    },
+};
+
+fn score_frame(using_score_v2: bool) -> ScoreFrame {
+    ScoreFrame {
+        time: 12345,
+        id: 0,
+        count_300: 300,
+        count_100: 10,
+        count_50: 1,
+        count_geki: 40,
+        count_katu: 5,
+        count_miss: 2,
+        total_score: 1_000_000,
+        max_combo: 420,
+        current_combo: 69,
+        perfect: false,
+        current_hp: 200,
+        tag_byte: 0,
+        using_score_v2,
+        combo_portion: using_score_v2.then_some(0.75),
+        bonus_portion: using_score_v2.then_some(0.125),
+    }
+}
+
+fn bundle(using_score_v2: bool) -> ReplayFrameBundle {
+    ReplayFrameBundle {
+        extra: 0,
+        frames: (0..3)
+            .map(|i| ReplayFrame {
+                button_state: i as u8,
+                legacy_byte: 0,
+                mouse_x: 256.5 + i as f32,
+                mouse_y: 192.25,
+                time: 16 * i,
+            })
+            .collect(),
+        action: 0,
+        score_frame: score_frame(using_score_v2),
+        sequence: 7,
+    }
+}
+
+#[test]
+fn frames_round_trip() {
+    for using_score_v2 in [false, true] {
+        for packet in [
+            Packet::ClientSpectateFrames(bundle(using_score_v2)),
+            Packet::BanchoMatchScoreUpdate(score_frame(using_score_v2)),
+        ] {
+            let mut buf = Buffer::new();
+            packet.encode(&mut buf);
+            assert_eq!(Packet::decode(&mut buf.freeze()), Ok(packet));
+        }
+    }
+}
+
+#[test]
+fn raw_relay_matches_a_full_encode() {
+    let mut client = Buffer::new();
+    Packet::ClientSpectateFrames(bundle(true)).encode(&mut client);
+    Packet::ClientMatchScoreUpdate(score_frame(true)).encode(&mut client);
+
+    let mut frames = PacketFramer::new(client.freeze());
+    let spectate = frames.next().unwrap().unwrap();
+    let score = frames.next().unwrap().unwrap();
+
+    let mut relayed = Buffer::new();
+    bancho_spectate_frames_raw(&mut relayed, &spectate.payload);
+    bancho_match_score_update_raw(&mut relayed, &score.payload, 3);
+
+    let mut expected = Buffer::new();
+    Packet::BanchoSpectateFrames(bundle(true)).encode(&mut expected);
+    Packet::BanchoMatchScoreUpdate(ScoreFrame {
+        id: 3,
+        ..score_frame(true)
+    })
+    .encode(&mut expected);
+
+    assert_eq!(relayed, expected);
+}
@@ -0,0 +1,91 @@
+use bancho_packet::{
+    buffer::serialization::{BufMutExt, Buffer, BytesExt},
+    error::{DecodeError, EncodeError},
+    packets::{
+        framer::{PacketFramer, HEADER_LEN},
+        packet::Packet,
+        structures::BanchoMessage,
+    },
+};
+use bytes::BufMut;
+
+fn round_trip(packet: &Packet) {
+    let mut buf = Buffer::new();
+    packet.encode(&mut buf);
+
+    // the header should hold the full payload length, not just its low byte
+    let length = u32::from_le_bytes(buf[3..HEADER_LEN].try_into().unwrap()) as usize;
+    assert_eq!(length, buf.len() - HEADER_LEN);
+
+    let mut frames = PacketFramer::new(buf.freeze());
+    let frame = frames.next().unwrap().unwrap();
+    assert_eq!(frame.payload.len(), length);
+    assert!(frames.next().is_none());
+
+    assert_eq!(&Packet::from_frame(frame).unwrap(), packet);
+}
+
+#[test]
+fn large_chat_message() {
+    round_trip(&Packet::BanchoSendMessage(BanchoMessage {
+        sending_client: "GammaBot".into(),
+        message: "a".repeat(2048).into(),
+        target: "#osu".into(),
+        sender_id: 5,
+    }));
+}
+
+#[test]
+fn large_presence_bundle() {
+    round_trip(&Packet::BanchoUserPresenceBundle((0..500).collect()));
+}
+
+#[test]
+fn packets_after_a_large_packet_stay_aligned() {
+    let packets = [
+        Packet::BanchoUserPresenceBundle((0..500).collect()),
+        Packet::BanchoLoginReply(3),
+        Packet::BanchoAnnounce("b".repeat(300).into()),
+        Packet::BanchoPing,
+    ];
+
+    let mut buf = Buffer::new();
+    for packet in &packets {
+        packet.encode(&mut buf);
+    }
+
+    let mut buf = buf.freeze();
+    for packet in &packets {
+        assert_eq!(&Packet::decode(&mut buf).unwrap(), packet);
+    }
+    assert!(buf.is_empty());
+}
+
+#[test]
+#[should_panic(expected = "too long")]
+fn overlong_lists_panic() {
+    let mut buf = Buffer::new();
+    Packet::BanchoUserPresenceBundle(vec![0; i16::MAX as usize + 1]).encode(&mut buf);
+}
+
+#[test]
+fn negative_list_lengths_are_refused() {
+    let mut buf = Buffer::new();
+    buf.put_i16_le(-1);
+    buf.put_i32_le(5);
+    assert_eq!(
+        buf.freeze().get_i32_list(),
+        Err(DecodeError::NegativeLength(-1))
+    );
+}
+
+#[test]
+fn lengths_past_u32_are_refused() {
+    let length = u32::MAX as usize + 1;
+    let mut buf = Buffer::new();
+    assert_eq!(
+        buf.put_packet_header(5, length),
+        Err(EncodeError::PayloadTooLong(length))
+    );
+    assert!(buf.is_empty());
+}
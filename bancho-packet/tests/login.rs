@@ -0,0 +1,118 @@
+use bancho_packet::{
+    error::DecodeError,
+    packets::{
+        reader::LoginData,
+        structures::{ClientHashes, ClientVersion, MacAddress, ReleaseStream},
+    },
+};
+use bytes::Bytes;
+
+const LOGIN: &[u8] = b"peppy\n5f4dcc3b5aa765d61d8327deb882cf99\nb20230326.2cuttingedge|2|0|\
+0c5d2ae2f1a5c2a5e1d6a6bd0bb4d5b7:00-15-5D-12-34-56.00-15-5D-AB-CD-EF.:\
+e4b4c8e7f1cde5e8b4b2a1f1d2c3b4a5:9d6c6d8e7f6a5b4c3d2e1f0a9b8c7d6e:a1b2c3d4e5f6a7b8c9d0e1f2a3b4c5d6:|1\n";
+
+#[test]
+fn login_is_parsed() {
+    let body = Bytes::from_static(LOGIN);
+    let login = LoginData::from_slice(&mut body.clone()).unwrap();
+
+    assert_eq!(login.username, "peppy");
+    assert_eq!(login.utc_offset, 2);
+    assert_eq!(login.show_city, 0);
+    assert_eq!(login.allow_pms, 1);
+    assert_eq!(
+        login.client_version,
+        ClientVersion {
+            date: 20230326,
+            revision: Some(2),
+            stream: ReleaseStream::CuttingEdge,
+        }
+    );
+
+    let hashes = &login.client_hashes;
+    assert_eq!(hashes.path_md5, "0c5d2ae2f1a5c2a5e1d6a6bd0bb4d5b7");
+    assert_eq!(
+        hashes.adapters,
+        [
+            MacAddress([0x00, 0x15, 0x5d, 0x12, 0x34, 0x56]),
+            MacAddress([0x00, 0x15, 0x5d, 0xab, 0xcd, 0xef]),
+        ]
+    );
+    assert_eq!(hashes.uninstall_md5, "9d6c6d8e7f6a5b4c3d2e1f0a9b8c7d6e");
+    assert_eq!(
+        hashes.disk_signature_md5,
+        "a1b2c3d4e5f6a7b8c9d0e1f2a3b4c5d6"
+    );
+    assert!(!hashes.running_under_wine);
+
+    // the fields point into the body rather than at copies
+    assert_eq!(login.username.as_ptr(), body.as_ptr());
+    assert_eq!(hashes.path_md5.as_ptr(), body[66..].as_ptr());
+}
+
+#[test]
+fn wine_marker_is_detected() {
+    let hashes = ClientHashes::parse(&"a:runningunderwine:b:c:d:".into()).unwrap();
+    assert!(hashes.running_under_wine);
+    assert!(hashes.adapters.is_empty());
+    assert_eq!(hashes.disk_signature_md5, "d");
+
+    assert_eq!(
+        ClientHashes::parse(&"a:not-a-mac.:b:c:d:".into()),
+        Err(DecodeError::BadClientHashes)
+    );
+    assert_eq!(
+        ClientHashes::parse(&"a:b".into()),
+        Err(DecodeError::BadClientHashes)
+    );
+}
+
+#[test]
+fn client_versions() {
+    for (string, date, revision, stream) in [
+        ("b20230326", 20230326, None, ReleaseStream::Stable),
+        ("b20230326.2", 20230326, Some(2), ReleaseStream::Stable),
+        ("b20230326beta", 20230326, None, ReleaseStream::Beta),
+        (
+            "b20230326.2cuttingedge",
+            20230326,
+            Some(2),
+            ReleaseStream::CuttingEdge,
+        ),
+        ("b20230326tourney", 20230326, None, ReleaseStream::Tourney),
+        ("b20230326.10dev", 20230326, Some(10), ReleaseStream::Dev),
+    ] {
+        let version: ClientVersion = string.parse().unwrap();
+        assert_eq!(
+            version,
+            ClientVersion {
+                date,
+                revision,
+                stream
+            }
+        );
+        assert_eq!(version.to_string(), string);
+    }
+
+    let older: ClientVersion = "b20230101.5".parse().unwrap();
+    let newer: ClientVersion = "b20230326".parse().unwrap();
+    assert!(older < newer);
+
+    for bad in ["20230326", "b2023", "b20230326nightly", "b20230326.+1", "b"] {
+        assert_eq!(
+            bad.parse::<ClientVersion>(),
+            Err(DecodeError::BadClientVersion),
+            "{}",
+            bad
+        );
+    }
+}
+
+#[test]
+fn login_is_written_back_out() {
+    let login = LoginData::from_slice(&mut Bytes::from_static(LOGIN)).unwrap();
+    assert_eq!(login.to_string().as_bytes(), LOGIN);
+
+    let hashes = ClientHashes::parse(&"a:runningunderwine:b:c:d:".into()).unwrap();
+    assert_eq!(hashes.to_string(), "a:runningunderwine:b:c:d:");
+}
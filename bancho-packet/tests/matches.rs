@@ -0,0 +1,90 @@
+use bancho_packet::{
+    buffer::serialization::Buffer,
+    packets::{
+        mods::Mods,
+        packet::Packet,
+        structures::{GameMode, Match, MatchSlot, MATCH_SLOTS},
+        writer::bancho_match_update,
+    },
+};
+
+fn sample_match(free_mod: bool) -> Match {
+    let mut slots = [MatchSlot {
+        status: MatchSlot::STATUS_OPEN,
+        ..Default::default()
+    }; MATCH_SLOTS];
+    slots[0] = MatchSlot {
+        status: MatchSlot::STATUS_READY,
+        team: 1,
+        player_id: 1000,
+        slot_mods: if free_mod {
+            Mods::HIDDEN
+        } else {
+            Mods::empty()
+        },
+        ..Default::default()
+    };
+    slots[3] = MatchSlot {
+        status: MatchSlot::STATUS_NO_MAP,
+        team: 2,
+        player_id: 1001,
+        ..Default::default()
+    };
+    slots[15].status = MatchSlot::STATUS_LOCKED;
+
+    Match {
+        match_id: 12,
+        in_progress: false,
+        match_type: 0,
+        active_mods: Mods::DOUBLE_TIME,
+        game_name: "gamma's game".to_string(),
+        game_password: Some("hunter2".to_string()),
+        beatmap_name: "Artist - Title [Insane]".to_string(),
+        beatmap_id: 75,
+        beatmap_checksum: "a5b99395a42bd55bc5eb1d2411cbdf8b".to_string(),
+        slots,
+        host_id: 1000,
+        play_mode: GameMode::Osu,
+        match_scoring_type: 0,
+        match_team_type: 2,
+        free_mod,
+        seed: 0x1337,
+    }
+}
+
+#[test]
+fn match_round_trips() {
+    for free_mod in [false, true] {
+        let packet = Packet::BanchoMatchJoinSuccess(sample_match(free_mod));
+
+        let mut buf = Buffer::new();
+        packet.encode(&mut buf);
+        assert_eq!(Packet::decode(&mut buf.freeze()), Ok(packet));
+    }
+}
+
+#[test]
+fn lobby_updates_hide_the_password() {
+    let game = sample_match(false);
+
+    let mut buf = Buffer::new();
+    bancho_match_update(&mut buf, &game, false);
+    match Packet::decode(&mut buf.freeze()) {
+        Ok(Packet::BanchoMatchUpdate(decoded)) => {
+            assert_eq!(decoded.game_password.as_deref(), Some(""));
+            assert_eq!(decoded, game.masked());
+        }
+        other => panic!("unexpected packet {:?}", other),
+    }
+
+    let game = Match {
+        game_password: None,
+        ..game
+    };
+    let mut buf = Buffer::new();
+    bancho_match_update(&mut buf, &game, false);
+    assert_eq!(
+        Packet::decode(&mut buf.freeze()),
+        Ok(Packet::BanchoMatchUpdate(game))
+    );
+}
@@ -0,0 +1,134 @@
+use bancho_packet::{
+    buffer::serialization::{BanchoDeserialize, BanchoSerialize, Buffer},
+    packets::{
+        mods::{InvalidMods, Mods, UnknownMod},
+        structures::{Action, BanchoPresence, BanchoPrivileges, GameMode},
+    },
+};
+use bytes::Bytes;
+
+#[test]
+fn mods_format_as_acronyms() {
+    let mods = Mods::HIDDEN | Mods::DOUBLE_TIME | Mods::RELAX;
+    assert_eq!(mods.to_string(), "HDDTRX");
+    assert_eq!(Mods::empty().to_string(), "");
+
+    // nightcore and perfect are always sent with the mod they imply, which isn't shown
+    let mods = Mods::NIGHTCORE | Mods::DOUBLE_TIME | Mods::PERFECT | Mods::SUDDEN_DEATH;
+    assert_eq!(mods.to_string(), "NCPF");
+}
+
+#[test]
+fn mods_parse_from_acronyms() {
+    assert_eq!(
+        "hdDTrx".parse(),
+        Ok(Mods::HIDDEN | Mods::DOUBLE_TIME | Mods::RELAX)
+    );
+    assert_eq!("".parse(), Ok(Mods::empty()));
+    assert_eq!("NC".parse(), Ok(Mods::NIGHTCORE | Mods::DOUBLE_TIME));
+    assert_eq!("HDXX".parse::<Mods>(), Err(UnknownMod("XX".to_string())));
+    assert_eq!("HDH".parse::<Mods>(), Err(UnknownMod("H".to_string())));
+
+    let mods = Mods::HARD_ROCK | Mods::FLASHLIGHT | Mods::KEY_4;
+    assert_eq!(mods.to_string().parse(), Ok(mods));
+}
+
+#[test]
+fn mods_validate() {
+    assert_eq!((Mods::HIDDEN | Mods::HARD_ROCK).validate(), Ok(()));
+    assert_eq!(
+        (Mods::EASY | Mods::HARD_ROCK).validate(),
+        Err(InvalidMods::Incompatible(Mods::EASY, Mods::HARD_ROCK))
+    );
+    assert_eq!(
+        (Mods::RELAX | Mods::AUTOPILOT).validate(),
+        Err(InvalidMods::Incompatible(Mods::RELAX, Mods::AUTOPILOT))
+    );
+    assert_eq!(
+        Mods::NIGHTCORE.validate(),
+        Err(InvalidMods::MissingImplied(
+            Mods::NIGHTCORE,
+            Mods::DOUBLE_TIME
+        ))
+    );
+    assert_eq!(
+        (Mods::KEY_4 | Mods::KEY_7).validate(),
+        Err(InvalidMods::MultipleKeyMods(Mods::KEY_4 | Mods::KEY_7))
+    );
+}
+
+#[test]
+fn unknown_mod_bits_are_kept() {
+    let bits = Mods::HIDDEN.bits() | 1 << 31;
+    let mut buf = Buffer::new();
+    Mods::from(bits).serialize(&mut buf);
+    assert_eq!(
+        Mods::deserialize(&mut buf.freeze()).map(u32::from),
+        Ok(bits)
+    );
+}
+
+#[test]
+fn presence_packs_mode_with_privileges() {
+    let presence = BanchoPresence {
+        player_id: 2,
+        username: "peppy".to_string(),
+        timezone: 24,
+        country_code: 0,
+        permissions: BanchoPrivileges::NORMAL | BanchoPrivileges::SUPPORTER,
+        play_mode: GameMode::Mania,
+        longitude: 0.,
+        latitude: 0.,
+        player_rank: 1,
+    };
+
+    let mut buf = Buffer::new();
+    presence.serialize(&mut buf);
+    // i32 id, then "peppy" as a 7 byte string, then the timezone and country
+    assert_eq!(buf[13], 3 << 5 | 0b101);
+    assert_eq!(BanchoPresence::deserialize(&mut buf.freeze()), Ok(presence));
+}
+
+#[test]
+fn presence_privileges_stay_out_of_the_mode() {
+    let presence = BanchoPresence {
+        player_id: 2,
+        username: "peppy".to_string(),
+        timezone: 24,
+        country_code: 0,
+        permissions: BanchoPrivileges::NORMAL | BanchoPrivileges::TOURNAMENT,
+        play_mode: GameMode::Osu,
+        longitude: 0.,
+        latitude: 0.,
+        player_rank: 1,
+    };
+
+    let mut buf = Buffer::new();
+    presence.serialize(&mut buf);
+    assert_eq!(buf[13], 0b1);
+    assert_eq!(
+        BanchoPresence::deserialize(&mut buf.freeze()),
+        Ok(BanchoPresence {
+            permissions: BanchoPrivileges::NORMAL,
+            ..presence
+        })
+    );
+}
+
+#[test]
+fn unknown_enum_values_are_kept() {
+    assert_eq!(
+        GameMode::deserialize(&mut Bytes::from_static(&[4])),
+        Ok(GameMode::Other(4))
+    );
+    assert_eq!(
+        Action::deserialize(&mut Bytes::from_static(&[2])),
+        Ok(Action::Playing)
+    );
+
+    let mut buf = Buffer::new();
+    Action::deserialize(&mut Bytes::from_static(&[200]))
+        .unwrap()
+        .serialize(&mut buf);
+    assert_eq!(buf, [200][..]);
+}
@@ -0,0 +1,280 @@
+use std::collections::HashSet;
+
+use bancho_packet::{
+    buffer::serialization::Buffer,
+    packets::{
+        mods::Mods,
+        packet::Packet,
+        packet_ids::PacketIDs,
+        structures::{
+            Action, BanchoChannel, BanchoMessage, BanchoPresence, BanchoPrivileges, BanchoStats,
+            BeatmapInfo, BeatmapInfoReply, ClientStatus, GameMode, Match, MatchSlot, ReplayFrame,
+            ReplayFrameBundle, ScoreFrame, MATCH_SLOTS,
+        },
+    },
+};
+use bytes::Bytes;
+
+fn message() -> BanchoMessage {
+    BanchoMessage {
+        sending_client: "peppy".into(),
+        message: "hello".into(),
+        target: "#osu".into(),
+        sender_id: 2,
+    }
+}
+
+fn status() -> ClientStatus {
+    ClientStatus {
+        status: Action::Playing,
+        status_text: "Artist - Title [Insane]".into(),
+        beatmap_checksum: "a5b99395a42bd55bc5eb1d2411cbdf8b".into(),
+        current_mods: Mods::HIDDEN | Mods::HARD_ROCK,
+        play_mode: GameMode::Taiko,
+        beatmap_id: 75,
+    }
+}
+
+fn game() -> Match {
+    let mut slots = [MatchSlot {
+        status: MatchSlot::STATUS_OPEN,
+        ..Default::default()
+    }; MATCH_SLOTS];
+    slots[0] = MatchSlot {
+        status: MatchSlot::STATUS_READY,
+        player_id: 1000,
+        ..Default::default()
+    };
+
+    Match {
+        match_id: 12,
+        in_progress: false,
+        match_type: 0,
+        active_mods: Mods::empty(),
+        game_name: "gamma's game".to_string(),
+        game_password: None,
+        beatmap_name: "Artist - Title [Insane]".to_string(),
+        beatmap_id: 75,
+        beatmap_checksum: "a5b99395a42bd55bc5eb1d2411cbdf8b".to_string(),
+        slots,
+        host_id: 1000,
+        play_mode: GameMode::Osu,
+        match_scoring_type: 0,
+        match_team_type: 0,
+        free_mod: false,
+        seed: 0,
+    }
+}
+
+fn score_frame() -> ScoreFrame {
+    ScoreFrame {
+        time: 12345,
+        count_300: 300,
+        total_score: 1_000_000,
+        using_score_v2: true,
+        combo_portion: Some(0.75),
+        bonus_portion: Some(0.125),
+        ..Default::default()
+    }
+}
+
+fn bundle() -> ReplayFrameBundle {
+    ReplayFrameBundle {
+        frames: vec![ReplayFrame {
+            mouse_x: 256.5,
+            mouse_y: 192.25,
+            time: 16,
+            ..Default::default()
+        }],
+        score_frame: score_frame(),
+        sequence: 7,
+        ..Default::default()
+    }
+}
+
+fn channel() -> BanchoChannel {
+    BanchoChannel {
+        name: "#osu".to_string(),
+        topic: "General discussion".to_string(),
+        connected: 3,
+    }
+}
+
+/// One packet of every kind, with payloads that aren't all zeroes
+fn every_packet() -> Vec<Packet> {
+    vec![
+        Packet::ClientSendUserStatus(status()),
+        Packet::ClientSendIrcMessage(message()),
+        Packet::ClientExit(1),
+        Packet::ClientRequestStatusUpdate,
+        Packet::ClientPong,
+        Packet::BanchoLoginReply(1000),
+        Packet::BanchoCommandError,
+        Packet::BanchoSendMessage(message()),
+        Packet::BanchoPing,
+        Packet::BanchoHandleIrcChangeUsername("peppy>>cookiezi".into()),
+        Packet::BanchoHandleIrcQuit("peppy".into()),
+        Packet::BanchoHandleOsuUpdate(BanchoStats {
+            player_id: 1000,
+            status: status(),
+            ranked_score: 1 << 40,
+            accuracy: 0.98,
+            play_count: 50,
+            total_score: 1 << 41,
+            rank: 1,
+            performance: 727,
+        }),
+        Packet::BanchoHandleUserQuit(1000),
+        Packet::BanchoSpectatorJoined(1000),
+        Packet::BanchoSpectatorLeft(1000),
+        Packet::BanchoSpectateFrames(bundle()),
+        Packet::ClientStartSpectating(1000),
+        Packet::ClientStopSpectating,
+        Packet::ClientSpectateFrames(bundle()),
+        Packet::BanchoVersionUpdate,
+        Packet::ClientErrorReport("stack trace".into()),
+        Packet::ClientCantSpectate,
+        Packet::BanchoSpectatorCantSpectate(1000),
+        Packet::BanchoGetAttention,
+        Packet::BanchoAnnounce("hello".into()),
+        Packet::ClientSendIrcMessagePrivate(message()),
+        Packet::BanchoMatchUpdate(game()),
+        Packet::BanchoMatchNew(game()),
+        Packet::BanchoMatchDisband(12),
+        Packet::ClientLobbyPart,
+        Packet::ClientLobbyJoin,
+        Packet::ClientMatchCreate(game()),
+        Packet::ClientMatchJoin {
+            match_id: 12,
+            password: "hunter2".into(),
+        },
+        Packet::ClientMatchPart,
+        Packet::BanchoMatchJoinSuccess(game()),
+        Packet::BanchoMatchJoinFail,
+        Packet::ClientMatchChangeSlot(3),
+        Packet::ClientMatchReady,
+        Packet::ClientMatchLock(3),
+        Packet::ClientMatchChangeSettings(game()),
+        Packet::BanchoFellowSpectatorJoined(1001),
+        Packet::BanchoFellowSpectatorLeft(1001),
+        Packet::ClientMatchStart,
+        Packet::BanchoMatchStart(game()),
+        Packet::ClientMatchScoreUpdate(score_frame()),
+        Packet::BanchoMatchScoreUpdate(score_frame()),
+        Packet::ClientMatchComplete,
+        Packet::BanchoMatchTransferHost,
+        Packet::ClientMatchChangeMods(Mods::DOUBLE_TIME),
+        Packet::ClientMatchLoadComplete,
+        Packet::BanchoMatchAllPlayersLoaded,
+        Packet::ClientMatchNoBeatmap,
+        Packet::ClientMatchNotReady,
+        Packet::ClientMatchFailed,
+        Packet::BanchoMatchPlayerFailed(3),
+        Packet::BanchoMatchComplete,
+        Packet::ClientMatchHasBeatmap,
+        Packet::ClientMatchSkipRequest,
+        Packet::BanchoMatchSkip,
+        Packet::BanchoUnauthorised,
+        Packet::ClientChannelJoin("#osu".into()),
+        Packet::BanchoChannelJoinSuccess("#osu".into()),
+        Packet::BanchoChannelAvailable(channel()),
+        Packet::BanchoChannelRevoked("#osu".into()),
+        Packet::BanchoChannelAvailableAutojoin(channel()),
+        Packet::ClientBeatmapInfoRequest(Bytes::from_static(&[1, 0, 0, 0, 0])),
+        Packet::BanchoBeatmapInfoReply(BeatmapInfoReply {
+            beatmaps: vec![BeatmapInfo {
+                beatmap_id: 75,
+                beatmap_checksum: "a5b99395a42bd55bc5eb1d2411cbdf8b".to_string(),
+                osu_grade: 9,
+                ..Default::default()
+            }],
+        }),
+        Packet::ClientMatchTransferHost(3),
+        Packet::BanchoLoginPermissions(BanchoPrivileges::NORMAL | BanchoPrivileges::SUPPORTER),
+        Packet::BanchoFriendsList(vec![1001, 1002]),
+        Packet::ClientFriendAdd(1001),
+        Packet::ClientFriendRemove(1001),
+        Packet::BanchoProtocolNegotiation(19),
+        Packet::BanchoTitleUpdate("https://example.com/title.png".into()),
+        Packet::ClientMatchChangeTeam,
+        Packet::ClientChannelLeave("#osu".into()),
+        Packet::ClientReceiveUpdates(1),
+        Packet::BanchoMonitor,
+        Packet::BanchoMatchPlayerSkipped(1000),
+        Packet::ClientSetIrcAwayMessage(message()),
+        Packet::BanchoUserPresence(BanchoPresence {
+            player_id: 1000,
+            username: "peppy".to_string(),
+            timezone: 24 + 9,
+            country_code: 111,
+            play_mode: GameMode::Mania,
+            permissions: BanchoPrivileges::NORMAL | BanchoPrivileges::OWNER,
+            longitude: 139.69,
+            latitude: 35.69,
+            player_rank: 1,
+        }),
+        Packet::ClientUserStatsRequest(vec![1000, 1001]),
+        Packet::BanchoRestart(5000),
+        Packet::ClientInvite(1001),
+        Packet::BanchoInvite(message()),
+        Packet::BanchoChannelListingComplete,
+        Packet::ClientMatchChangePassword(game()),
+        Packet::BanchoMatchChangePassword("hunter2".into()),
+        Packet::BanchoBanInfo(u32::MAX),
+        Packet::ClientSpecialMatchInfoRequest(12),
+        Packet::BanchoUserSilenced(1001),
+        Packet::BanchoUserPresenceSingle(1000),
+        Packet::BanchoUserPresenceBundle(vec![1000, 1001]),
+        Packet::ClientUserPresenceRequest(vec![1000, 1001]),
+        Packet::ClientUserPresenceRequestAll(0),
+        Packet::ClientUserToggleBlockNonFriendPm(1),
+        Packet::BanchoUserPmBlocked(message()),
+        Packet::BanchoTargetIsSilenced(message()),
+        Packet::BanchoVersionUpdateForced,
+        Packet::BanchoSwitchServer("c.example.com".into()),
+        Packet::BanchoAccountRestricted,
+        Packet::BanchoRTX("boo".into()),
+        Packet::ClientMatchAbort,
+        Packet::BanchoSwitchTourneyServer("c.example.com".into()),
+        Packet::ClientSpecialJoinMatchChannel(12),
+        Packet::ClientSpecialLeaveMatchChannel(12),
+    ]
+}
+
+#[test]
+fn every_packet_id_is_covered() {
+    let covered: HashSet<_> = every_packet().iter().map(Packet::id).collect();
+    let missing: Vec<_> = (0..=i16::MAX)
+        .filter_map(|id| PacketIDs::try_from(id).ok())
+        .filter(|id| !covered.contains(id))
+        .collect();
+    assert!(missing.is_empty(), "no sample for {missing:?}");
+}
+
+#[test]
+fn every_packet_round_trips() {
+    for packet in every_packet() {
+        let mut buf = Buffer::new();
+        packet.encode(&mut buf);
+
+        assert_eq!(buf.len(), packet.encoded_len(), "{packet:?}");
+        assert_eq!(Packet::decode(&mut buf.freeze()), Ok(packet));
+    }
+}
+
+/// The framer splits each payload off before decoding it, so leftovers have to be checked on the payload itself
+#[test]
+fn every_payload_is_fully_consumed() {
+    for packet in every_packet() {
+        let mut payload = Buffer::new();
+        packet.encode_payload(&mut payload);
+
+        let mut payload = payload.freeze();
+        Packet::decode_payload(packet.id(), &mut payload).unwrap();
+        assert!(
+            payload.is_empty(),
+            "{packet:?} left {} bytes",
+            payload.len()
+        );
+    }
+}
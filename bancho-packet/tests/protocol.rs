@@ -0,0 +1,70 @@
+use bancho_packet::{
+    buffer::serialization::Buffer,
+    error::DecodeError,
+    packets::{
+        packet::Packet,
+        structures::{BanchoPresence, BanchoPrivileges, GameMode, ProtocolVersion},
+        writer::{bancho_protocol_negotiaton, bancho_user_presence},
+    },
+};
+
+fn presence() -> BanchoPresence {
+    BanchoPresence {
+        player_id: 1000,
+        username: "peppy".to_string(),
+        timezone: 24 + 9,
+        country_code: 111,
+        play_mode: GameMode::Taiko,
+        permissions: BanchoPrivileges::SUPPORTER,
+        longitude: 0.,
+        latitude: 0.,
+        player_rank: 1,
+    }
+}
+
+#[test]
+fn only_known_versions_are_accepted() {
+    assert_eq!(ProtocolVersion::try_from(19), Ok(ProtocolVersion::LATEST));
+    // older versions aren't spoken until their layouts are known from real clients
+    for value in [18, 20] {
+        assert_eq!(
+            ProtocolVersion::try_from(value),
+            Err(DecodeError::UnknownValue {
+                kind: "ProtocolVersion",
+                value
+            })
+        );
+    }
+}
+
+#[test]
+fn negotiation_sends_the_version() {
+    let mut buf = Buffer::new();
+    bancho_protocol_negotiaton(&mut buf, ProtocolVersion::V19);
+    assert_eq!(
+        Packet::decode(&mut buf.freeze()),
+        Ok(Packet::BanchoProtocolNegotiation(19))
+    );
+}
+
+#[test]
+fn presence_layouts() {
+    // everything after the id and the username, up to the packed privileges and mode
+    let fields = |protocol| {
+        let mut buf = Buffer::new();
+        bancho_user_presence(&mut buf, &presence(), protocol);
+        buf[7 + 4 + 7..][..3].to_vec()
+    };
+
+    assert_eq!(fields(ProtocolVersion::V19), [33, 111, 4 | 1 << 5]);
+}
+
+#[test]
+fn packets_use_the_latest_layout() {
+    let mut buf = Buffer::new();
+    bancho_user_presence(&mut buf, &presence(), ProtocolVersion::LATEST);
+    assert_eq!(
+        Packet::decode(&mut buf.freeze()),
+        Ok(Packet::BanchoUserPresence(presence()))
+    );
+}
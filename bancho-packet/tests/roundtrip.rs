@@ -0,0 +1,615 @@
+//! Property tests for every writer and reader pair, checking that whatever gets written reads back the same
+//!
+//! Floats are kept away from NaN, which would never compare equal to itself.
+use bancho_packet::{
+    buffer::{
+        byte_str::ByteStr,
+        serialization::{BufMutExt, Buffer, BytesExt, BytesMutExt},
+    },
+    packets::{
+        framer::{PacketFramer, HEADER_LEN},
+        mods::Mods,
+        packet::Packet,
+        reader::{self, LoginData},
+        structures::{
+            Action, BanchoChannel, BanchoMessage, BanchoPresence, BanchoPrivileges, BanchoStats,
+            BeatmapInfo, BeatmapInfoReply, ClientHashes, ClientStatus, ClientVersion, GameMode,
+            MacAddress, Match, MatchSlot, ProtocolVersion, ReleaseStream, ReplayFrame,
+            ReplayFrameBundle, ScoreFrame, MATCH_SLOTS,
+        },
+        writer,
+    },
+};
+use bytes::Bytes;
+use proptest::{collection::vec, prelude::*};
+
+fn float() -> impl Strategy<Value = f32> {
+    any::<f32>().prop_filter("NaN never equals itself", |f| !f.is_nan())
+}
+
+fn byte_str() -> impl Strategy<Value = ByteStr> {
+    any::<String>().prop_map(ByteStr::from)
+}
+
+fn mods() -> impl Strategy<Value = Mods> {
+    any::<u32>().prop_map(Mods::from)
+}
+
+fn game_mode() -> impl Strategy<Value = GameMode> {
+    (0..4u8).prop_map(GameMode::from)
+}
+
+fn action() -> impl Strategy<Value = Action> {
+    any::<u8>().prop_map(Action::from)
+}
+
+fn message() -> impl Strategy<Value = BanchoMessage> {
+    (byte_str(), byte_str(), byte_str(), any::<i32>()).prop_map(
+        |(sending_client, message, target, sender_id)| BanchoMessage {
+            sending_client,
+            message,
+            target,
+            sender_id,
+        },
+    )
+}
+
+fn channel() -> impl Strategy<Value = BanchoChannel> {
+    (any::<String>(), any::<String>(), any::<i16>()).prop_map(|(name, topic, connected)| {
+        BanchoChannel {
+            name,
+            topic,
+            connected,
+        }
+    })
+}
+
+fn status() -> impl Strategy<Value = ClientStatus> {
+    (
+        action(),
+        byte_str(),
+        byte_str(),
+        mods(),
+        game_mode(),
+        any::<i32>(),
+    )
+        .prop_map(
+            |(status, status_text, beatmap_checksum, current_mods, play_mode, beatmap_id)| {
+                ClientStatus {
+                    status,
+                    status_text,
+                    beatmap_checksum,
+                    current_mods,
+                    play_mode,
+                    beatmap_id,
+                }
+            },
+        )
+}
+
+fn stats() -> impl Strategy<Value = BanchoStats> {
+    (
+        any::<i32>(),
+        status(),
+        any::<i64>(),
+        float(),
+        any::<i32>(),
+        any::<i64>(),
+        any::<i32>(),
+        any::<i16>(),
+    )
+        .prop_map(
+            |(
+                player_id,
+                status,
+                ranked_score,
+                accuracy,
+                play_count,
+                total_score,
+                rank,
+                performance,
+            )| BanchoStats {
+                player_id,
+                status,
+                ranked_score,
+                accuracy,
+                play_count,
+                total_score,
+                rank,
+                performance,
+            },
+        )
+}
+
+fn presence() -> impl Strategy<Value = BanchoPresence> {
+    (
+        any::<i32>(),
+        any::<String>(),
+        any::<u8>(),
+        any::<u8>(),
+        game_mode(),
+        any::<u8>().prop_map(BanchoPrivileges::from),
+        float(),
+        float(),
+        any::<i32>(),
+    )
+        .prop_map(
+            |(
+                player_id,
+                username,
+                timezone,
+                country_code,
+                play_mode,
+                permissions,
+                longitude,
+                latitude,
+                player_rank,
+            )| BanchoPresence {
+                player_id,
+                username,
+                timezone,
+                country_code,
+                play_mode,
+                permissions,
+                longitude,
+                latitude,
+                player_rank,
+            },
+        )
+}
+
+/// What a presence reads back as, only the low 5 bits of the privileges fit next to the mode
+fn as_sent(presence: BanchoPresence) -> BanchoPresence {
+    BanchoPresence {
+        permissions: BanchoPrivileges::from(presence.permissions.bits() & 0x1f),
+        ..presence
+    }
+}
+
+fn protocol() -> impl Strategy<Value = ProtocolVersion> {
+    proptest::sample::select(ProtocolVersion::ALL.to_vec())
+}
+
+fn slot(free_mod: bool) -> impl Strategy<Value = MatchSlot> {
+    (any::<u8>(), any::<u8>(), any::<i32>(), mods()).prop_map(
+        move |(status, team, player_id, slot_mods)| {
+            let mut slot = MatchSlot {
+                status,
+                team,
+                player_id,
+                slot_mods,
+                ..Default::default()
+            };
+            // only sent for slots with a player, and when mods are picked per slot
+            if !slot.has_player() {
+                slot.player_id = 0;
+            }
+            if !free_mod {
+                slot.slot_mods = Mods::empty();
+            }
+            slot
+        },
+    )
+}
+
+fn game() -> impl Strategy<Value = Match> {
+    let settings = (
+        any::<i16>(),
+        any::<bool>(),
+        any::<u8>(),
+        mods(),
+        any::<String>(),
+        any::<Option<String>>(),
+        any::<String>(),
+        any::<i32>(),
+        any::<String>(),
+    );
+    let rest = (
+        any::<i32>(),
+        game_mode(),
+        any::<u8>(),
+        any::<u8>(),
+        any::<i32>(),
+    );
+
+    (any::<bool>(), settings, rest).prop_flat_map(|(free_mod, settings, rest)| {
+        vec(slot(free_mod), MATCH_SLOTS).prop_map(move |slots| {
+            let (
+                match_id,
+                in_progress,
+                match_type,
+                active_mods,
+                game_name,
+                game_password,
+                beatmap_name,
+                beatmap_id,
+                beatmap_checksum,
+            ) = settings.clone();
+            let (host_id, play_mode, match_scoring_type, match_team_type, seed) = rest;
+
+            Match {
+                match_id,
+                in_progress,
+                match_type,
+                active_mods,
+                game_name,
+                game_password,
+                beatmap_name,
+                beatmap_id,
+                beatmap_checksum,
+                slots: slots.try_into().unwrap(),
+                host_id,
+                play_mode,
+                match_scoring_type,
+                match_team_type,
+                free_mod,
+                seed,
+            }
+        })
+    })
+}
+
+fn score_frame() -> impl Strategy<Value = ScoreFrame> {
+    let counts = (
+        any::<i32>(),
+        any::<u8>(),
+        any::<[u16; 6]>(),
+        any::<i32>(),
+        any::<u16>(),
+        any::<u16>(),
+    );
+    let rest = (
+        any::<bool>(),
+        any::<u8>(),
+        any::<u8>(),
+        any::<bool>(),
+        any::<f64>().prop_filter("NaN never equals itself", |f| !f.is_nan()),
+        any::<f64>().prop_filter("NaN never equals itself", |f| !f.is_nan()),
+    );
+
+    (counts, rest).prop_map(
+        |(
+            (
+                time,
+                id,
+                [count_300, count_100, count_50, count_geki, count_katu, count_miss],
+                total_score,
+                max_combo,
+                current_combo,
+            ),
+            (perfect, current_hp, tag_byte, using_score_v2, combo_portion, bonus_portion),
+        )| ScoreFrame {
+            time,
+            id,
+            count_300,
+            count_100,
+            count_50,
+            count_geki,
+            count_katu,
+            count_miss,
+            total_score,
+            max_combo,
+            current_combo,
+            perfect,
+            current_hp,
+            tag_byte,
+            using_score_v2,
+            combo_portion: using_score_v2.then_some(combo_portion),
+            bonus_portion: using_score_v2.then_some(bonus_portion),
+        },
+    )
+}
+
+fn replay_frame() -> impl Strategy<Value = ReplayFrame> {
+    (any::<u8>(), any::<u8>(), float(), float(), any::<i32>()).prop_map(
+        |(button_state, legacy_byte, mouse_x, mouse_y, time)| ReplayFrame {
+            button_state,
+            legacy_byte,
+            mouse_x,
+            mouse_y,
+            time,
+        },
+    )
+}
+
+fn frame_bundle() -> impl Strategy<Value = ReplayFrameBundle> {
+    (
+        any::<i32>(),
+        vec(replay_frame(), 0..32),
+        any::<u8>(),
+        score_frame(),
+        any::<u16>(),
+    )
+        .prop_map(
+            |(extra, frames, action, score_frame, sequence)| ReplayFrameBundle {
+                extra,
+                frames,
+                action,
+                score_frame,
+                sequence,
+            },
+        )
+}
+
+fn beatmap_info() -> impl Strategy<Value = BeatmapInfo> {
+    (
+        any::<i16>(),
+        any::<[i32; 3]>(),
+        any::<[u8; 5]>(),
+        any::<String>(),
+    )
+        .prop_map(
+            |(index, [beatmap_id, beatmapset_id, thread_id], bytes, beatmap_checksum)| {
+                let [ranked_status, osu_grade, catch_grade, taiko_grade, mania_grade] = bytes;
+                BeatmapInfo {
+                    index,
+                    beatmap_id,
+                    beatmapset_id,
+                    thread_id,
+                    ranked_status,
+                    osu_grade,
+                    catch_grade,
+                    taiko_grade,
+                    mania_grade,
+                    beatmap_checksum,
+                }
+            },
+        )
+}
+
+fn id_list() -> impl Strategy<Value = Vec<i32>> {
+    vec(any::<i32>(), 0..64)
+}
+
+/// A spread of packets covering every kind of payload
+fn packet() -> impl Strategy<Value = Packet> {
+    prop_oneof![
+        Just(Packet::BanchoPing),
+        any::<i32>().prop_map(Packet::BanchoLoginReply),
+        any::<u32>().prop_map(Packet::BanchoBanInfo),
+        byte_str().prop_map(Packet::BanchoAnnounce),
+        byte_str().prop_map(Packet::ClientChannelJoin),
+        status().prop_map(Packet::ClientSendUserStatus),
+        message().prop_map(Packet::BanchoSendMessage),
+        message().prop_map(Packet::ClientSendIrcMessagePrivate),
+        stats().prop_map(Packet::BanchoHandleOsuUpdate),
+        presence().prop_map(|presence| Packet::BanchoUserPresence(as_sent(presence))),
+        channel().prop_map(Packet::BanchoChannelAvailable),
+        game().prop_map(Packet::BanchoMatchUpdate),
+        game().prop_map(Packet::ClientMatchCreate),
+        (any::<i32>(), byte_str())
+            .prop_map(|(match_id, password)| Packet::ClientMatchJoin { match_id, password }),
+        mods().prop_map(Packet::ClientMatchChangeMods),
+        any::<u8>().prop_map(|bits| Packet::BanchoLoginPermissions(bits.into())),
+        id_list().prop_map(Packet::BanchoFriendsList),
+        id_list().prop_map(Packet::ClientUserPresenceRequest),
+        frame_bundle().prop_map(Packet::ClientSpectateFrames),
+        score_frame().prop_map(Packet::BanchoMatchScoreUpdate),
+        vec(any::<u8>(), 0..64).prop_map(|raw| Packet::ClientBeatmapInfoRequest(raw.into())),
+        vec(beatmap_info(), 0..8)
+            .prop_map(|beatmaps| Packet::BanchoBeatmapInfoReply(BeatmapInfoReply { beatmaps })),
+    ]
+}
+
+fn client_version() -> impl Strategy<Value = ClientVersion> {
+    let stream = prop_oneof![
+        Just(ReleaseStream::Stable),
+        Just(ReleaseStream::Beta),
+        Just(ReleaseStream::CuttingEdge),
+        Just(ReleaseStream::Tourney),
+        Just(ReleaseStream::Dev),
+    ];
+    (10_000_000..=99_999_999u32, any::<Option<u32>>(), stream).prop_map(
+        |(date, revision, stream)| ClientVersion {
+            date,
+            revision,
+            stream,
+        },
+    )
+}
+
+fn client_hashes() -> impl Strategy<Value = ClientHashes> {
+    let md5 = || "[0-9a-f]{32}".prop_map(ByteStr::from);
+    (
+        md5(),
+        any::<bool>(),
+        vec(any::<[u8; 6]>().prop_map(MacAddress), 0..4),
+        md5(),
+        md5(),
+        md5(),
+    )
+        .prop_map(
+            |(
+                path_md5,
+                running_under_wine,
+                adapters,
+                adapters_md5,
+                uninstall_md5,
+                disk_signature_md5,
+            )| {
+                ClientHashes {
+                    path_md5,
+                    // wine doesn't expose any adapters
+                    adapters: match running_under_wine {
+                        true => Vec::new(),
+                        false => adapters,
+                    },
+                    adapters_md5,
+                    uninstall_md5,
+                    disk_signature_md5,
+                    running_under_wine,
+                }
+            },
+        )
+}
+
+proptest! {
+    #[test]
+    fn ulebs_round_trip(len in any::<usize>()) {
+        let mut buf = Buffer::new();
+        buf.put_uleb(len);
+
+        let mut buf = buf.freeze();
+        prop_assert_eq!(buf.get_uleb(), Ok(len));
+        prop_assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn strings_round_trip(string in any::<Option<String>>()) {
+        let mut buf = Buffer::new();
+        buf.put_optional_string(string.as_deref());
+
+        let mut buf = buf.freeze();
+        prop_assert_eq!(buf.get_optional_string(), Ok(string));
+        prop_assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn i32_lists_round_trip(list in id_list()) {
+        let mut buf = Buffer::new();
+        buf.put_i32_list(&list);
+        prop_assert_eq!(buf.freeze().get_i32_list(), Ok(list));
+    }
+
+    #[test]
+    fn messages_round_trip(message in message()) {
+        let mut buf = Buffer::new();
+        writer::write_message(&mut buf, &message);
+        prop_assert_eq!(reader::read_message(&mut buf.freeze()), Ok(message));
+    }
+
+    #[test]
+    fn channels_round_trip(channel in channel()) {
+        let mut buf = Buffer::new();
+        writer::write_channel(&mut buf, &channel);
+        prop_assert_eq!(reader::read_channel(&mut buf.freeze()), Ok(channel));
+    }
+
+    #[test]
+    fn statuses_round_trip(status in status()) {
+        let mut buf = Buffer::new();
+        writer::write_status(&mut buf, &status);
+        prop_assert_eq!(reader::read_status(&mut buf.freeze()), Ok(status));
+    }
+
+    #[test]
+    fn stats_round_trip(stats in stats()) {
+        let mut buf = Buffer::new();
+        writer::write_stats(&mut buf, &stats);
+        prop_assert_eq!(reader::read_stats(&mut buf.freeze()), Ok(stats));
+    }
+
+    #[test]
+    fn presences_round_trip(presence in presence(), protocol in protocol()) {
+        let mut buf = Buffer::new();
+        writer::write_presence(&mut buf, &presence, protocol);
+        prop_assert_eq!(
+            reader::read_presence(&mut buf.freeze(), protocol),
+            Ok(as_sent(presence))
+        );
+    }
+
+    #[test]
+    fn matches_round_trip(game in game()) {
+        let mut buf = Buffer::new();
+        writer::write_match(&mut buf, &game, true);
+        prop_assert_eq!(reader::read_match(&mut buf.freeze()), Ok(game.clone()));
+
+        let mut buf = Buffer::new();
+        writer::write_match(&mut buf, &game, false);
+        prop_assert_eq!(reader::read_match(&mut buf.freeze()), Ok(game.masked()));
+    }
+
+    #[test]
+    fn score_frames_round_trip(score_frame in score_frame()) {
+        let mut buf = Buffer::new();
+        writer::write_score_frame(&mut buf, &score_frame);
+        prop_assert_eq!(reader::read_score_frame(&mut buf.freeze()), Ok(score_frame));
+    }
+
+    #[test]
+    fn frame_bundles_round_trip(bundle in frame_bundle()) {
+        let mut buf = Buffer::new();
+        writer::write_frame_bundle(&mut buf, &bundle);
+        prop_assert_eq!(reader::read_frame_bundle(&mut buf.freeze()), Ok(bundle));
+    }
+
+    #[test]
+    fn packets_round_trip(packets in vec(packet(), 0..8), threshold in 0..256usize) {
+        let mut buf = Buffer::new();
+        for packet in &packets {
+            packet.encode_compressed(&mut buf, threshold);
+        }
+
+        let decoded = PacketFramer::new(buf.freeze())
+            .map(|frame| Packet::from_frame(frame?))
+            .collect::<Result<Vec<_>, _>>();
+        prop_assert_eq!(decoded, Ok(packets));
+    }
+
+    #[test]
+    fn packets_fill_their_encoded_len(packet in packet()) {
+        let mut buf = Buffer::new();
+        packet.encode(&mut buf);
+        prop_assert_eq!(buf.len(), packet.encoded_len());
+
+        // an exactly sized slice takes the whole packet, with nothing left over
+        let mut slice = vec![0; packet.encoded_len()];
+        let mut rest = &mut slice[..];
+        packet.encode_into(&mut rest).unwrap();
+        prop_assert!(rest.is_empty());
+        prop_assert_eq!(slice, buf.to_vec());
+    }
+
+    #[test]
+    fn frames_round_trip(frames in vec((any::<i16>(), vec(any::<u8>(), 0..256)), 0..8)) {
+        let mut buf = Buffer::new();
+        for (id, payload) in &frames {
+            buf.with_header(*id, |buf| buf.extend_from_slice(payload));
+        }
+        prop_assert_eq!(
+            buf.len(),
+            frames.iter().map(|(_, payload)| HEADER_LEN + payload.len()).sum::<usize>()
+        );
+
+        let split = PacketFramer::new(buf.freeze())
+            .map(|frame| frame.map(|frame| (frame.id, frame.payload.to_vec())))
+            .collect::<Result<Vec<_>, _>>();
+        prop_assert_eq!(split, Ok(frames));
+    }
+
+    #[test]
+    fn client_versions_round_trip(version in client_version()) {
+        prop_assert_eq!(version.to_string().parse(), Ok(version));
+    }
+
+    #[test]
+    fn logins_round_trip(
+        username in "[^\n]*",
+        password_md5 in "[0-9a-f]{32}",
+        client_version in client_version(),
+        [utc_offset, show_city, allow_pms] in any::<[i32; 3]>(),
+        client_hashes in client_hashes(),
+    ) {
+        let body = format!(
+            "{username}\n{password_md5}\n{client_version}|{utc_offset}|{show_city}|{client_hashes}|{allow_pms}\n"
+        );
+        let login = LoginData::from_slice(&mut Bytes::from(body.clone())).unwrap();
+
+        prop_assert_eq!(&login.client_hashes, &client_hashes);
+        prop_assert_eq!(login.to_string(), body);
+    }
+
+    #[test]
+    fn garbage_never_panics(mut body in vec(any::<u8>(), 0..512).prop_map(Bytes::from)) {
+        let _ = LoginData::from_slice(&mut body.clone());
+        for frame in PacketFramer::new(body.clone()).flatten() {
+            let _ = Packet::from_frame(frame);
+        }
+        while !body.is_empty() {
+            if Packet::decode(&mut body).is_err() {
+                break;
+            }
+        }
+    }
+}
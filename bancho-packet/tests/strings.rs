@@ -0,0 +1,112 @@
+use bancho_packet::{
+    buffer::serialization::{BufMutExt, Buffer, BytesExt},
+    error::DecodeError,
+};
+use bytes::Bytes;
+
+#[test]
+fn uleb_round_trips_across_group_boundaries() {
+    for len in [
+        0,
+        1,
+        127,
+        128,
+        255,
+        256,
+        16383,
+        16384,
+        2097151,
+        2097152,
+        usize::MAX,
+    ] {
+        let mut buf = Buffer::new();
+        buf.put_uleb(len);
+        assert_eq!(buf.freeze().get_uleb(), Ok(len), "length {}", len);
+    }
+}
+
+#[test]
+fn uleb_known_encodings() {
+    let cases: [(usize, &[u8]); 4] = [
+        (0, &[0x00]),
+        (127, &[0x7f]),
+        (128, &[0x80, 0x01]),
+        (16384, &[0x80, 0x80, 0x01]),
+    ];
+
+    for (len, encoded) in cases {
+        let mut buf = Buffer::new();
+        buf.put_uleb(len);
+        assert_eq!(&buf[..], encoded, "length {}", len);
+    }
+}
+
+#[test]
+fn uleb_overflow_is_an_error() {
+    let mut buf = Bytes::from_static(&[0xff; 11]);
+    assert_eq!(buf.get_uleb(), Err(DecodeError::BadUleb));
+}
+
+#[test]
+fn multibyte_strings_round_trip() {
+    for string in [
+        "",
+        "peppy",
+        "ｃｏｏｌ",
+        "日本語のユーザー名",
+        "한국어 채팅",
+        "🎵\0\x0b",
+    ] {
+        let mut buf = Buffer::new();
+        buf.put_string(string);
+
+        let mut buf = buf.freeze();
+        assert_eq!(buf.get_string().as_deref(), Ok(string));
+        assert!(buf.is_empty());
+    }
+}
+
+#[test]
+fn null_and_empty_strings_are_distinct() {
+    let mut buf = Buffer::new();
+    buf.put_optional_string(None);
+    buf.put_optional_string(Some(""));
+    assert_eq!(&buf[..], &[0x00, 0x0b, 0x00]);
+
+    let mut buf = buf.freeze();
+    assert_eq!(buf.get_optional_string(), Ok(None));
+    assert_eq!(buf.get_optional_string(), Ok(Some(String::new())));
+}
+
+#[test]
+fn string_errors() {
+    let mut bad_marker = Bytes::from_static(&[0x0c, 0x00]);
+    assert_eq!(
+        bad_marker.get_string(),
+        Err(DecodeError::BadStringMarker(0x0c))
+    );
+
+    let mut too_long = Bytes::from_static(&[0x0b, 0x05, b'a']);
+    assert_eq!(
+        too_long.get_string(),
+        Err(DecodeError::OverlongLength {
+            length: 5,
+            remaining: 1
+        })
+    );
+
+    let mut bad_utf8 = Bytes::from_static(&[0x0b, 0x02, 0xc3, 0x28]);
+    assert_eq!(bad_utf8.get_string(), Err(DecodeError::BadUtf8));
+}
+
+#[test]
+fn strings_share_the_buffer() {
+    let mut buf = Buffer::new();
+    buf.put_string("#osu");
+    let body = buf.freeze();
+
+    let string = body.clone().get_str().unwrap();
+    assert_eq!(string, "#osu");
+    // the string points into the body rather than at a copy
+    assert_eq!(string.as_ptr(), body[2..].as_ptr());
+}
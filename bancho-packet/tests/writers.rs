@@ -0,0 +1,171 @@
+use bancho_packet::{
+    buffer::serialization::Buffer,
+    packets::{
+        packet::Packet,
+        structures::{BanchoChannel, BanchoMessage, BeatmapInfo, BeatmapInfoReply},
+        writer::*,
+    },
+};
+
+type Writer = fn(&mut Buffer);
+type IdWriter = fn(&mut Buffer, i32);
+type IdPacket = fn(i32) -> Packet;
+
+/// Run a writer on its own, and decode the single packet it wrote
+fn written(write: impl FnOnce(&mut Buffer)) -> Packet {
+    let mut buf = Buffer::new();
+    write(&mut buf);
+
+    let len = buf.len();
+    let mut buf = buf.freeze();
+    let packet = Packet::decode(&mut buf).unwrap();
+    assert!(buf.is_empty(), "{packet:?} left {} bytes", buf.len());
+    assert_eq!(packet.encoded_len(), len, "{packet:?}");
+    packet
+}
+
+fn invite() -> BanchoMessage {
+    BanchoMessage {
+        sending_client: "peppy".into(),
+        message: "Come join my game: [osump://12/hunter2 gamma's game]".into(),
+        target: "cookiezi".into(),
+        sender_id: 1000,
+    }
+}
+
+#[test]
+fn empty_packets() {
+    let cases: [(Writer, Packet); 8] = [
+        (bancho_version_update, Packet::BanchoVersionUpdate),
+        (bancho_get_attention, Packet::BanchoGetAttention),
+        (bancho_monitor, Packet::BanchoMonitor),
+        (bancho_match_transfer_host, Packet::BanchoMatchTransferHost),
+        (
+            bancho_match_all_players_loaded,
+            Packet::BanchoMatchAllPlayersLoaded,
+        ),
+        (bancho_match_complete, Packet::BanchoMatchComplete),
+        (bancho_match_skip, Packet::BanchoMatchSkip),
+        (
+            bancho_version_update_forced,
+            Packet::BanchoVersionUpdateForced,
+        ),
+    ];
+
+    for (write, expected) in cases {
+        assert_eq!(written(write), expected);
+    }
+}
+
+#[test]
+fn id_packets() {
+    let cases: [(IdWriter, IdPacket); 7] = [
+        (
+            bancho_fellow_spectator_joined,
+            Packet::BanchoFellowSpectatorJoined,
+        ),
+        (
+            bancho_fellow_spectator_left,
+            Packet::BanchoFellowSpectatorLeft,
+        ),
+        (bancho_spectator_left, Packet::BanchoSpectatorLeft),
+        (
+            bancho_user_presence_single,
+            Packet::BanchoUserPresenceSingle,
+        ),
+        (bancho_user_silenced, Packet::BanchoUserSilenced),
+        (bancho_match_player_failed, Packet::BanchoMatchPlayerFailed),
+        (
+            bancho_match_player_skipped,
+            Packet::BanchoMatchPlayerSkipped,
+        ),
+    ];
+
+    for (write, expected) in cases {
+        assert_eq!(written(|buf| write(buf, 1000)), expected(1000));
+    }
+    assert_eq!(
+        written(|buf| bancho_restart(buf, 5000)),
+        Packet::BanchoRestart(5000)
+    );
+}
+
+#[test]
+fn string_packets() {
+    assert_eq!(
+        written(|buf| bancho_title_update(buf, "https://i.ppy.sh/menu.png|https://osu.ppy.sh")),
+        Packet::BanchoTitleUpdate("https://i.ppy.sh/menu.png|https://osu.ppy.sh".into())
+    );
+    assert_eq!(
+        written(|buf| bancho_switch_tourney_server(buf, "127.0.0.1")),
+        Packet::BanchoSwitchTourneyServer("127.0.0.1".into())
+    );
+    assert_eq!(
+        written(|buf| bancho_match_change_password(buf, "hunter2")),
+        Packet::BanchoMatchChangePassword("hunter2".into())
+    );
+}
+
+#[test]
+fn list_packets() {
+    assert_eq!(
+        written(|buf| bancho_friends_list(buf, &[2, 3, 1001])),
+        Packet::BanchoFriendsList(vec![2, 3, 1001])
+    );
+    assert_eq!(
+        written(|buf| bancho_user_presence_bundle(buf, &[])),
+        Packet::BanchoUserPresenceBundle(Vec::new())
+    );
+}
+
+#[test]
+fn structured_packets() {
+    assert_eq!(
+        written(|buf| bancho_invite(buf, &invite())),
+        Packet::BanchoInvite(invite())
+    );
+
+    let channel = BanchoChannel {
+        name: "#osu".to_string(),
+        topic: "General discussion.".to_string(),
+        connected: 12,
+    };
+    assert_eq!(
+        written(|buf| bancho_channel_available_autojoin(buf, &channel)),
+        Packet::BanchoChannelAvailableAutojoin(channel)
+    );
+}
+
+#[test]
+fn beatmap_info_reply() {
+    let reply = BeatmapInfoReply {
+        beatmaps: vec![
+            BeatmapInfo {
+                index: 0,
+                beatmap_id: 129891,
+                beatmapset_id: 39804,
+                thread_id: 0,
+                ranked_status: 2,
+                osu_grade: 3,
+                catch_grade: 9,
+                taiko_grade: 9,
+                mania_grade: 9,
+                beatmap_checksum: "a5b99395a42bd55bc5eb1d2411cbdf8b".to_string(),
+            },
+            BeatmapInfo {
+                index: 1,
+                beatmap_id: -1,
+                ..Default::default()
+            },
+        ],
+    };
+
+    let mut buf = Buffer::new();
+    bancho_beatmap_info_reply(&mut buf, &reply);
+    // the header, then the count as an i32
+    assert_eq!(&buf[7..11], &2i32.to_le_bytes());
+    assert_eq!(
+        Packet::decode(&mut buf.freeze()),
+        Ok(Packet::BanchoBeatmapInfoReply(reply))
+    );
+}
@@ -0,0 +1,49 @@
+//! Optional per session packet captures, written when `capture_dir` is set
+//!
+//! Each session gets its own `<token>.bcap` file, in the format from `bancho_packet::packets::capture`.
+//! They can be read with `bancho-dump`.
+use std::path::Path;
+
+use actix_web::web::Bytes;
+use bancho_packet::{
+    buffer::serialization::Buffer,
+    packets::capture::{self, Record, RecordKind},
+};
+use tokio::{fs::OpenOptions, io::AsyncWriteExt};
+use tracing::warn;
+
+/// Append a body to the session's capture, starting the file if this is the first record
+///
+/// Failing to write a capture is only logged, it shouldn't get in the way of the player.
+pub async fn record(dir: &Path, kind: RecordKind, token: &str, body: Bytes) {
+    // the token comes straight from the client, so make sure it can't point outside `dir`
+    if !token
+        .bytes()
+        .all(|b| b.is_ascii_alphanumeric() || b == b'-')
+    {
+        return;
+    }
+
+    if let Err(e) = append(dir, kind, token, body).await {
+        warn!(
+            msg = "could not write capture",
+            token = token,
+            err = e.to_string()
+        );
+    }
+}
+
+async fn append(dir: &Path, kind: RecordKind, token: &str, body: Bytes) -> std::io::Result<()> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(dir.join(format!("{token}.bcap")))
+        .await?;
+
+    let mut buf = Buffer::new();
+    if file.metadata().await?.len() == 0 {
+        capture::write_header(&mut buf);
+    }
+    Record::now(kind, token.into(), body).write(&mut buf);
+    file.write_all(&buf).await
+}
@@ -1,44 +1,45 @@
-use std::{str::FromStr, time::Duration};
+use std::{collections::HashMap, str::FromStr, sync::Arc, time::Duration};
 
 use crate::errors::{InternalError, RequestError};
+use crate::sessions::SessionRegistry;
+use crate::store::{MemoryStore, SessionStore};
 use deadpool_redis::Pool as RedisPool;
 use redis::AsyncCommands;
 use tracing::{info_span, instrument, Instrument, Level};
 
-use crate::settings::DatabaseSettings;
+use crate::settings::{DatabaseSettings, SessionStoreKind};
 use sqlx::{
     mysql::{MySqlConnectOptions, MySqlPoolOptions},
-    ConnectOptions, MySqlPool,
+    ConnectOptions, MySqlPool, Row,
 };
+use tokio::sync::OnceCell;
 
 pub type PoolConnection = sqlx::pool::PoolConnection<sqlx::MySql>;
 
 #[derive(Clone)]
 pub struct Databases {
-    redis: RedisPool,
+    sessions: Arc<dyn SessionStore>,
     mysql: MySqlPool,
+    channel_topics: Arc<OnceCell<HashMap<String, String>>>,
 }
 
 impl Databases {
     #[instrument(name = "connect_databases", skip_all)]
     pub async fn new(settings: &DatabaseSettings) -> Self {
-        let redis = {
-            let cfg = deadpool_redis::Config::from_url(settings.redis_url.as_str());
-            cfg.create_pool(Some(deadpool_redis::Runtime::Tokio1))
-                .unwrap()
+        let sessions: Arc<dyn SessionStore> = match settings.session_store {
+            SessionStoreKind::Redis => {
+                let url = settings
+                    .redis_url
+                    .as_deref()
+                    .expect("the redis session store needs `db.redis_url`");
+                Arc::new(SessionRegistry::new(
+                    connect_redis(url).await,
+                    settings.queue,
+                ))
+            }
+            SessionStoreKind::Memory => Arc::new(MemoryStore::new(settings.queue)),
         };
 
-        // test to make sure the connection actually works
-        redis
-            .get()
-            .instrument(info_span!("get_redis_conn"))
-            .await
-            .unwrap()
-            .acl_whoami::<()>()
-            .instrument(info_span!("test_redis_conn"))
-            .await
-            .unwrap();
-
         let mut mysql = MySqlConnectOptions::from_str(&settings.mysql_url).unwrap();
         mysql
             .log_statements(tracing::log::LevelFilter::Debug)
@@ -49,16 +50,32 @@ impl Databases {
             .await
             .unwrap();
 
-        Databases { redis, mysql }
+        Databases {
+            sessions,
+            mysql,
+            channel_topics: Arc::default(),
+        }
     }
 
-    /// Get a redis connection
-    #[instrument(level = Level::DEBUG, name = "get_redis_conn", skip_all)]
-    pub async fn redis(&self) -> Result<deadpool_redis::Connection, RequestError> {
-        self.redis
-            .get()
-            .await
-            .map_err(|e| InternalError::RedisPool(e).into())
+    /// Sessions kept in memory, and a mysql pool which only tries to connect once it's used
+    ///
+    /// There's no mysql behind it, so it's only for tests which don't need a query to succeed.
+    #[cfg(test)]
+    pub fn for_tests() -> Self {
+        let mysql = MySqlPoolOptions::new()
+            .acquire_timeout(Duration::from_millis(100))
+            .connect_lazy_with(MySqlConnectOptions::new().host("127.0.0.1").port(1));
+
+        Databases {
+            sessions: Arc::new(MemoryStore::default()),
+            mysql,
+            channel_topics: Arc::default(),
+        }
+    }
+
+    /// Where sessions and the packets queued for them are kept
+    pub fn sessions(&self) -> &dyn SessionStore {
+        &*self.sessions
     }
 
     /// Get a mysql connection
@@ -69,4 +86,43 @@ impl Databases {
             .await
             .map_err(|e| InternalError::SqlPool(e).into())
     }
+
+    /// The topic of every channel by its name
+    ///
+    /// Read from mysql the first time it's asked for, so channels added after that need a restart to show up here.
+    pub async fn channel_topics(&self) -> Result<&HashMap<String, String>, RequestError> {
+        self.channel_topics
+            .get_or_try_init(|| async {
+                let rows = sqlx::query("SELECT `name`, `topic` FROM `channels`")
+                    .fetch_all(&mut self.mysql().await?)
+                    .await
+                    .map_err(InternalError::SqlPool)?;
+                Ok(rows
+                    .into_iter()
+                    .map(|row| (row.get("name"), row.get("topic")))
+                    .collect())
+            })
+            .await
+    }
+}
+
+async fn connect_redis(url: &str) -> RedisPool {
+    let redis = {
+        let cfg = deadpool_redis::Config::from_url(url);
+        cfg.create_pool(Some(deadpool_redis::Runtime::Tokio1))
+            .unwrap()
+    };
+
+    // test to make sure the connection actually works
+    redis
+        .get()
+        .instrument(info_span!("get_redis_conn"))
+        .await
+        .unwrap()
+        .acl_whoami::<()>()
+        .instrument(info_span!("test_redis_conn"))
+        .await
+        .unwrap();
+
+    redis
 }
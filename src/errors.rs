@@ -14,6 +14,7 @@
 //! Otherwise, you can mostly just use `Result` and sprinkle `?` in everywhere
 
 use actix_web::{http::StatusCode, web::BytesMut, HttpResponseBuilder};
+use bancho_packet::error::DecodeError;
 use redis::RedisError;
 use std::fmt::Write;
 use thiserror::Error;
@@ -53,6 +54,9 @@ pub enum InternalError {
 
     #[error("could not get from sql connection pool: {}", .0)]
     SqlPool(#[from] sqlx::Error),
+
+    #[error("stored session is malformed: {}", .0)]
+    MalformedSession(#[from] serde_json::Error),
 }
 
 /// An error encountered by the server that it's ok to share details with the client about
@@ -60,7 +64,7 @@ pub enum InternalError {
 #[derive(Debug, Error)]
 pub enum ExternalError {
     #[error("malformed packet: {}", .0)]
-    MalformedPacket(&'static str),
+    MalformedPacket(#[from] DecodeError),
     #[error("token is invalid")]
     InvalidToken,
 }
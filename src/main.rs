@@ -1,18 +1,24 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use actix_web::{web, App, HttpServer};
 use dotenv::dotenv;
+use tokio::net::TcpListener;
 use tracing::info;
 use tracing_actix_web::TracingLogger;
 
 use crate::{db::Databases, settings::Settings, telem::setup_tracing};
 
+mod capture;
 mod db;
 mod errors;
 mod server;
+#[cfg(test)]
+mod replay;
 mod settings;
 mod telem;
 mod sessions;
+mod store;
+mod tcp;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
@@ -26,6 +32,17 @@ async fn main() -> std::io::Result<()> {
     let databases = Arc::new(Databases::new(&settings.db).await);
     let bind_info = (settings.ip.clone(), settings.port);
 
+    actix_web::rt::spawn(sessions::reap_idle(
+        databases.clone(),
+        Duration::from_secs(settings.session_timeout),
+    ));
+
+    if let Some(tcp_port) = settings.tcp_port {
+        let listener = TcpListener::bind((settings.ip.as_str(), tcp_port)).await?;
+        info!("accepting tcp connections on port {}", tcp_port);
+        actix_web::rt::spawn(tcp::serve(listener, databases.clone(), settings.clone()));
+    }
+
     HttpServer::new(move || {
         App::new()
             .app_data(web::Data::new(settings.clone()))
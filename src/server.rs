@@ -1,29 +1,32 @@
-use std::sync::Arc;
+use std::{path::Path, sync::Arc};
 
 use actix_web::{
     get, post,
-    web::{Buf, Bytes, BytesMut, Data},
+    web::{Bytes, BytesMut, Data},
     HttpRequest, HttpResponse, Responder,
 };
-use bancho_packet::{
-    buffer::serialization::BytesMutExt,
-    packets::{
-        reader::{self, *},
-        structures::*,
-        writer::*,
-    },
+use bancho_packet::packets::{
+    capture::RecordKind,
+    framer::{Frame, PacketFramer},
+    mods::Mods,
+    packet::Packet,
+    reader::LoginData,
+    structures::*,
+    writer::*,
 };
 
 use bcrypt::verify;
-use redis::AsyncCommands;
 use sqlx::{Executor, Row};
 use tracing::{debug, error, info_span, instrument, Instrument};
 use uuid::Uuid;
 
 use crate::{
+    capture,
     db::Databases,
-    errors::{ExternalError, InternalError, RequestError, Result},
+    errors::{ExternalError, RequestError, Result},
     sessions,
+    settings::Settings,
+    store::{unix_now, SessionStore},
 };
 extern crate lazy_static;
 
@@ -33,8 +36,8 @@ lazy_static::lazy_static! {
         username: "GammaBot".to_string(),
         timezone: 24,
         country_code: 0,
-        permissions: 8,
-        play_mode: 0,
+        permissions: BanchoPrivileges::OWNER,
+        play_mode: GameMode::Osu,
         longitude: 0.,
         latitude: 0.,
         player_rank: 0
@@ -43,11 +46,11 @@ lazy_static::lazy_static! {
     static ref BOT_STATS: BanchoStats = BanchoStats {
         player_id: 5,
         status: ClientStatus {
-            status: 0,
-            status_text: "- Helping run Gamma".to_string(),
-            beatmap_checksum: "".to_string(),
-            current_mods: 0,
-            play_mode: 2,
+            status: Action::Idle,
+            status_text: "- Helping run Gamma".into(),
+            beatmap_checksum: "".into(),
+            current_mods: Mods::empty(),
+            play_mode: GameMode::Catch,
             beatmap_id: 0,
         },
         ranked_score: 0,
@@ -64,42 +67,90 @@ pub async fn index() -> impl Responder {
     "theta! Gamma Server\n"
 }
 
+/// The outcome of a login, whichever transport it came in over
+pub struct LoginResponse {
+    /// The session token, or the reason the login failed
+    pub token: String,
+    pub success: bool,
+    /// Packets to send back to the client
+    pub body: BytesMut,
+}
+
+impl LoginResponse {
+    fn failed(reason: &str, body: BytesMut) -> Self {
+        LoginResponse {
+            token: reason.to_string(),
+            success: false,
+            body,
+        }
+    }
+}
+
 #[post("/")]
 pub async fn bancho_server(
     req: HttpRequest,
     body: Bytes,
     data: Data<Arc<Databases>>,
+    settings: Data<Arc<Settings>>,
 ) -> Result<HttpResponse> {
+    let mut res = HttpResponse::Ok();
+    let capture_dir = settings.capture_dir.as_deref();
     match req.headers().get("osu-token") {
         Some(token) => {
             let token = token.to_str().map_err(|_| ExternalError::InvalidToken)?;
-            handle_regular_req(&req, token, body, &data).await
+            let request_capture = capture_dir.map(|dir| (dir, body.clone()));
+            // a frame which can't be split out is skipped, the framer stops by itself once the rest can't line up
+            let frames = PacketFramer::new(body)
+                .filter_map(|frame| {
+                    frame
+                        .map_err(|e| error!(msg = "could not split frame", err = e.to_string()))
+                        .ok()
+                })
+                .collect();
+
+            let response = handle_packets(token, frames, &data, request_capture)
+                .await?
+                .freeze();
+            if let Some(dir) = capture_dir {
+                capture::record(dir, RecordKind::Response, token, response.clone()).await;
+            }
+            Ok(res.body(response))
+        }
+        None => {
+            let peer_addr = req.connection_info().peer_addr().map(str::to_string);
+            let login = handle_login(body.clone(), peer_addr.as_deref(), &data).await?;
+            let response = login.body.freeze();
+            // failed logins don't have a session to file them under
+            if let (Some(dir), true) = (capture_dir, login.success) {
+                capture::record(dir, RecordKind::Login, &login.token, body).await;
+                capture::record(dir, RecordKind::Response, &login.token, response.clone()).await;
+            }
+            res.append_header(("cho-token", login.token));
+            Ok(res.body(response))
         }
-        None => handle_auth_req(&req, body, &data).await,
     }
 }
 
+/// Log a player in from the plain text login request
 #[instrument(skip_all)]
-async fn handle_auth_req(
-    req: &HttpRequest,
+pub async fn handle_login(
     mut body: Bytes,
+    peer_addr: Option<&str>,
     data: &Databases,
-) -> Result<HttpResponse> {
+) -> Result<LoginResponse> {
     let login = LoginData::from_slice(&mut body).map_err(ExternalError::MalformedPacket)?;
     let mut mysql_pool = data.mysql().await.unwrap();
-    let mut redis_pool = data.redis().await?;
+    let store = data.sessions();
 
     debug!(
-        "login request for `{}` from `{:?}`",
-        &login.username,
-        req.connection_info().peer_addr()
+        "login request for `{}` from `{:?}` on {}",
+        &login.username, peer_addr, login.client_version
     );
     // TODO: Check against db, etc.
-    let mut res = HttpResponse::Ok();
     let mut buffer = BytesMut::new();
     let uuid = Uuid::new_v4();
 
-    let username_safe = login.username.replace(' ', "_").to_lowercase();
+    let username_safe = sessions::safe_username(&login.username);
     let player_query = sqlx::query("SELECT * FROM `users` WHERE username_safe = ?")
         .bind(username_safe)
         .fetch_one(&mut mysql_pool)
@@ -107,15 +158,13 @@ async fn handle_auth_req(
 
     if player_query.is_err() {
         bancho_login_reply(&mut buffer, -1);
-        res.append_header(("cho-token", "invalid username"));
-        return Ok(res.body(buffer));
+        return Ok(LoginResponse::failed("invalid username", buffer));
     }
     let user_data = player_query.unwrap();
     let password: String = user_data.get(5_usize);
     if !verify(login.password_md5, &password).unwrap() {
         bancho_login_reply(&mut buffer, -1);
-        res.append_header(("cho-token", "invalid password"));
-        return Ok(res.body(buffer));
+        return Ok(LoginResponse::failed("invalid password", buffer));
     }
 
     let user_id: i32 = user_data.get(0_usize);
@@ -127,22 +176,23 @@ async fn handle_auth_req(
     if stats.is_err() {
         debug!("could not find player in stats table");
         bancho_login_reply(&mut buffer, -5);
-        res.append_header(("cho-token", "invalid stats"));
-        return Ok(res.body(buffer));
+        return Ok(LoginResponse::failed("invalid stats", buffer));
     }
     {
         let _span = info_span!("prepare_response", uuid = uuid.to_string()).entered();
         // Write all of the necessary login packets, similar to that of the official osu! server
         let user_stats = stats.unwrap();
-        let session = sessions::build_session(user_data, user_stats, uuid.to_string());
+        let protocol = ProtocolVersion::LATEST;
+        let mut session =
+            sessions::build_session(user_data, user_stats, uuid.to_string(), protocol);
 
         bancho_login_reply(&mut buffer, 69);
-        bancho_protocol_negotiaton(&mut buffer, 19);
+        bancho_protocol_negotiaton(&mut buffer, protocol);
         bancho_announce(
             &mut buffer,
             format!("Welcome to Gamma, {}!", &login.username).as_str(),
         );
-        bancho_login_permissions(&mut buffer, 4);
+        bancho_login_permissions(&mut buffer, BanchoPrivileges::SUPPORTER);
         bancho_channel_listing_complete(&mut buffer);
         let channels_query = sqlx::query("SELECT * FROM `channels`")
             .fetch_all(&mut mysql_pool)
@@ -150,8 +200,7 @@ async fn handle_auth_req(
         if channels_query.is_err() {
             debug!("could not find channels in db");
             bancho_login_reply(&mut buffer, -5);
-            res.append_header(("cho-token", "invalid channels"));
-            return Ok(res.body(buffer));
+            return Ok(LoginResponse::failed("invalid channels", buffer));
         }
         let channels = channels_query.unwrap();
         for channel in channels {
@@ -161,7 +210,7 @@ async fn handle_auth_req(
             println!("{} {}", name, topic);
             bancho_channel_available(
                 &mut buffer,
-                BanchoChannel {
+                &BanchoChannel {
                     name: name.clone(),
                     topic: topic.clone(),
                     connected: 0,
@@ -169,76 +218,95 @@ async fn handle_auth_req(
             );
             if autojoin == 1 {
                 bancho_channel_join_success(&mut buffer, &name);
+                session.channels.push(name);
             }
         }
         bancho_ban_info(&mut buffer, 0);
 
-        bancho_user_presence(&mut buffer, session.presence.clone());
-        bancho_handle_osu_update(&mut buffer, session.stats.clone());
+        bancho_user_presence(&mut buffer, &session.presence, protocol);
+        bancho_handle_osu_update(&mut buffer, &session.stats);
 
         bancho_channel_join_success(&mut buffer, "#osu");
+        if !session.channels.iter().any(|name| name == "#osu") {
+            session.channels.push("#osu".to_string());
+        }
 
         let bot_presence = &*BOT_PRESENCE;
         let bot_stats = &*BOT_STATS;
-        bancho_user_presence(&mut buffer, bot_presence.clone());
-        bancho_handle_osu_update(&mut buffer, bot_stats.clone());
-        sessions::all_online_status(&mut buffer, &mut redis_pool).await;
-        sessions::announce_online(session.clone(), &mut redis_pool).await;
-
-        res.append_header(("cho-token", uuid.to_string()));
-        let session_string = serde_json::to_string(&session).unwrap();
-        data.redis()
-            .await?
-            .set::<_, _, ()>(format!("gamma::sessions::{}", uuid), session_string)
+        bancho_user_presence(&mut buffer, bot_presence, protocol);
+        bancho_handle_osu_update(&mut buffer, bot_stats);
+        sessions::all_online_status(&mut buffer, protocol, store).await;
+        sessions::announce_online(session.clone(), store).await;
+
+        store
+            .insert(&session)
             .instrument(info_span!("add_session", uuid = uuid.to_string()))
-            .await
-            .map_err(InternalError::Redis)?;
+            .await?;
+        let _ = sessions::announce_channel_counts(&session.channels, data).await;
     }
 
-    Ok(res.body(buffer))
+    Ok(LoginResponse {
+        token: uuid.to_string(),
+        success: true,
+        body: buffer,
+    })
 }
 
+/// Exits saying the client is restarting to update are ignored within this many seconds of logging in
+///
+/// The client sends one right after logging in when it's about to restart, a logout is sent as 0 and is never ignored.
+const EXIT_GRACE_SECS: u64 = 5;
+
+/// Handle the packets sent by a logged in player, returning everything that should be sent back to them
+///
+/// `request_capture` is the capture directory and the request to record there. It's only recorded once the token
+/// turns out to belong to a session, so clients can't create capture files for made up tokens.
 #[instrument(skip_all)]
-async fn handle_regular_req(
-    _req: &HttpRequest,
+pub async fn handle_packets(
     token: &str,
-    body: Bytes,
+    frames: Vec<Frame>,
     data: &Databases,
-) -> Result<HttpResponse> {
-    let mut res = HttpResponse::Ok();
-    // get session object from redis
-    let mut redis_pool = data.redis().await?;
+    request_capture: Option<(&Path, Bytes)>,
+) -> Result<BytesMut> {
+    let store = data.sessions();
 
-    let session_redis: String = redis_pool
-        .get(format!("gamma::sessions::{}", token))
+    let mut session = store
+        .get(token)
         .instrument(info_span!("get_session", token = token))
-        .await
-        .map_err(|_| ExternalError::InvalidToken)?;
-    let buffer_redis: Vec<u8> = flush_and_get(token, &mut redis_pool).await?;
-
-
-    let mut session: sessions::Session = serde_json::from_str(&session_redis).unwrap();
+        .await?
+        .ok_or(ExternalError::InvalidToken)?;
+    if let Some((dir, request)) = request_capture {
+        capture::record(dir, RecordKind::Request, token, request).await;
+    }
+    store.touch(token).await?;
     // get the players buffer
-    let mut player_buffer = BytesMut::from(buffer_redis.as_slice());
-    let binding = body.to_vec();
-    let body_vec = binding.as_slice();
-
-    let mut in_buf = BytesMut::from(body_vec);
-
-    let mut length = 0;
-    while length <= in_buf.len() {
-        let id = in_buf.get_i16_le();
-        let _compression = in_buf.get_u8() == 1;
-        let packet_length = in_buf.get_u32_le();
+    let mut player_buffer = store.drain(token).await?;
+    // joined or left in this request, everyone is sent their new counts once they're all handled
+    let mut changed_channels = Vec::new();
+
+    for frame in frames {
+        let id = frame.id;
+        let packet = match Packet::from_frame(frame) {
+            Ok(packet) => packet,
+            Err(e) => {
+                // each frame carries its own payload, so just skip over this one
+                error!(
+                    msg = "could not decode packet",
+                    id = id,
+                    err = e.to_string()
+                );
+                continue;
+            }
+        };
 
-        match id {
-            0 => {
-                let status = reader::client_user_status(&mut in_buf);
+        match packet {
+            Packet::ClientSendUserStatus(status) => {
                 session.stats.status = status;
-                bancho_handle_osu_update(&mut player_buffer, session.stats.clone());
-                sessions::update_stats(session.stats.clone(), &mut redis_pool).await;
+                bancho_handle_osu_update(&mut player_buffer, &session.stats);
+                sessions::update_stats(session.stats.clone(), store).await;
                 // TODO: maybe check if a player is already in relax mode and if they are, don't announce it
-                if (session.stats.status.current_mods & 128 == 128) && (!session.relax) {
+                let mods = session.stats.status.current_mods;
+                if mods.contains(Mods::RELAX) && !session.relax {
                     bancho_announce(
                         &mut player_buffer,
                         format!(
@@ -248,8 +316,7 @@ async fn handle_regular_req(
                         .as_str(),
                     );
                     session.relax = true;
-                } else if (session.stats.status.current_mods & 8192 == 8192) && (!session.autopilot)
-                {
+                } else if mods.contains(Mods::AUTOPILOT) && !session.autopilot {
                     bancho_announce(
                         &mut player_buffer,
                         format!(
@@ -259,7 +326,7 @@ async fn handle_regular_req(
                         .as_str(),
                     );
                     session.autopilot = true;
-                } else if (session.relax) && (session.stats.status.current_mods & 128 != 128) {
+                } else if session.relax && !mods.contains(Mods::RELAX) {
                     bancho_announce(
                         &mut player_buffer,
                         format!(
@@ -269,8 +336,7 @@ async fn handle_regular_req(
                         .as_str(),
                     );
                     session.relax = false;
-                } else if (session.autopilot) && (session.stats.status.current_mods & 8192 != 8192)
-                {
+                } else if session.autopilot && !mods.contains(Mods::AUTOPILOT) {
                     bancho_announce(
                         &mut player_buffer,
                         format!(
@@ -282,77 +348,160 @@ async fn handle_regular_req(
                     session.autopilot = false;
                 }
             }
-            1 => {
-                let message = reader::client_send_mesage(&mut in_buf);
+            Packet::ClientSendIrcMessage(message) => {
                 debug!(
                     msg = "packet received",
                     typ = "send_message",
-                    target = &message.target
+                    target = message.target.as_str()
                 );
             }
-            4 => (), // update last pinged... maybe should have something to destroy it on no ping for n amount of time
-            25 => {
-                let message = reader::client_send_mesage(&mut in_buf);
+            Packet::ClientExit(updating) => {
+                debug!(msg = "packet received", typ = "exit", updating = updating);
+                // an update restart, rather than the player leaving
+                if updating != 0 && unix_now().saturating_sub(session.login_time) < EXIT_GRACE_SECS
+                {
+                    continue;
+                }
+                // anything else in this request should be cleaned up along with the session
+                store.save(&session).await?;
+                sessions::end_session(token, data).await?;
+                return Ok(player_buffer);
+            }
+            Packet::ClientPong => (), // every request counts towards being seen, see `sessions::reap_idle`
+            Packet::ClientSendIrcMessagePrivate(message) => {
                 debug!(
                     msg = "packet received",
                     typ = "send_message",
-                    target = &message.target
+                    target = message.target.as_str()
                 );
                 if message.target == "GammaBot" {
                 } else {
                     let mut message_cloned = message.clone();
 
                     message_cloned.sender_id = session.presence.player_id;
-                    message_cloned.sending_client = session.presence.username.clone();
+                    message_cloned.sending_client = session.presence.username.clone().into();
 
-                    sessions::send_pm(message_cloned, &mut redis_pool).await;
+                    sessions::send_pm(message_cloned, store).await;
                 }
             }
-            63 => {
-                let channel_name = in_buf.get_string();
+            Packet::ClientChannelJoin(channel_name) => {
                 debug!(
                     msg = "packet received",
                     typ = "join_channel",
-                    channel_name = &channel_name
+                    channel_name = channel_name.as_str()
                 );
                 bancho_channel_join_success(&mut player_buffer, channel_name.as_str());
+                let channel_name = String::from(channel_name);
+                if !session.channels.contains(&channel_name) {
+                    store.join_channel(token, &channel_name).await?;
+                    session.channels.push(channel_name.clone());
+                    changed_channels.push(channel_name);
+                }
             }
-            id => {
-                error!(
-                    msg = "unrecognised packet received",
-                    id = id,
-                    length = packet_length
+            Packet::ClientChannelLeave(channel_name) => {
+                debug!(
+                    msg = "packet received",
+                    typ = "leave_channel",
+                    channel_name = channel_name.as_str()
                 );
-                in_buf.advance(packet_length as usize);
+                let channel_name = String::from(channel_name);
+                if let Some(i) = session
+                    .channels
+                    .iter()
+                    .position(|name| *name == channel_name)
+                {
+                    store.leave_channel(token, &channel_name).await?;
+                    session.channels.remove(i);
+                    changed_channels.push(channel_name);
+                }
+            }
+            Packet::ClientStartSpectating(host) => {
+                debug!(
+                    msg = "packet received",
+                    typ = "start_spectating",
+                    host = host
+                );
+                stop_spectating(&mut session, store).await?;
+                if let Some(host_token) = store.token_by_id(host).await? {
+                    let mut b = BytesMut::new();
+                    bancho_spectator_join(&mut b, session.id);
+                    store.enqueue(&[host_token], b.freeze()).await?;
+                    session.spectating = Some(host);
+                }
+            }
+            Packet::ClientStopSpectating => {
+                debug!(msg = "packet received", typ = "stop_spectating");
+                stop_spectating(&mut session, store).await?;
+            }
+            packet => {
+                debug!(msg = "unhandled packet received", id = ?packet.id());
             }
         }
-
-        length += packet_length as usize;
-        length += 1;
     }
-    let session_string = serde_json::to_string(&session).unwrap();
     // flush the buffer
-    if let Err(e) = redis_pool
-        .set::<_, _, ()>(format!("gamma::sessions::{}", token), session_string)
+    if let Err(e) = store
+        .save(&session)
         .instrument(info_span!("update_session", token = token))
         .await
     {
         // report the error, but still send back the packets
-        let _ = RequestError::from(InternalError::Redis(e));
+        let _ = RequestError::from(e);
     };
+    if !changed_channels.is_empty() {
+        let _ = sessions::announce_channel_counts(&changed_channels, data).await;
+    }
 
-    Ok(res.body(player_buffer))
+    Ok(player_buffer)
 }
 
-async fn flush_and_get(token: &str, redis: &mut deadpool_redis::Connection) -> Result<Vec<u8>> {
-    let out_vec = redis
-        .lpop(
-            format!("gamma::buffers::{}", token),
-            std::num::NonZeroUsize::new(9999999999),
-        )
-        .instrument(info_span!("update_buffer", uuid = token))
-        .await
-        .map_err(InternalError::Redis)?;
+/// Let the player being spectated know they've lost a spectator
+async fn stop_spectating(session: &mut sessions::Session, store: &dyn SessionStore) -> Result<()> {
+    let Some(host) = session.spectating.take() else {
+        return Ok(());
+    };
+    if let Some(host_token) = store.token_by_id(host).await? {
+        let mut b = BytesMut::new();
+        bancho_spectator_left(&mut b, session.id);
+        store.enqueue(&[host_token], b.freeze()).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::tests::session;
+
+    fn exit(updating: i32) -> Vec<Frame> {
+        let mut buf = BytesMut::new();
+        Packet::ClientExit(updating).encode(&mut buf);
+        PacketFramer::new(buf.freeze())
+            .map(Result::unwrap)
+            .collect()
+    }
 
-    Ok(out_vec)
+    #[actix_web::test]
+    async fn logging_out_right_after_logging_in_ends_the_session() {
+        let data = Databases::for_tests();
+        let store = data.sessions();
+        for (id, username, token) in [(1000, "peppy", "a"), (1001, "cookiezi", "b")] {
+            let session = sessions::Session {
+                login_time: unix_now(),
+                ..session(id, username, token)
+            };
+            store.insert(&session).await.unwrap();
+        }
+
+        // restarting to update, the client logs straight back in
+        handle_packets("a", exit(1), &data, None).await.unwrap();
+        assert!(store.get("a").await.unwrap().is_some());
+
+        // there's no mysql to record when they were last online, the session going is what matters
+        let _ = handle_packets("a", exit(0), &data, None).await;
+        assert!(store.get("a").await.unwrap().is_none());
+        let queued = store.drain("b").await.unwrap().freeze();
+        assert!(PacketFramer::new(queued)
+            .filter_map(|frame| Packet::from_frame(frame.ok()?).ok())
+            .any(|packet| packet == Packet::BanchoHandleUserQuit(1000)));
+    }
 }
@@ -1,11 +1,25 @@
+use std::{sync::Arc, time::Duration};
 
-use bancho_packet::{buffer::serialization::Buffer, packets::structures, packets::writer::*};
+use actix_web::web::{Bytes, BytesMut};
+use async_trait::async_trait;
+use bancho_packet::{
+    buffer::serialization::{BanchoPacket, BanchoSerialize, Buffer},
+    packets::framer::HEADER_LEN,
+    packets::mods::Mods,
+    packets::structures,
+    packets::writer::*,
+};
 use redis::AsyncCommands;
 use serde::{Deserialize, Serialize};
 use sqlx::{mysql::MySqlRow, Row};
+use tracing::{info, info_span, instrument, Instrument};
 
-
-use crate::{db::Databases, errors::InternalError};
+use crate::{
+    db::Databases,
+    errors::{InternalError, RequestError},
+    settings::{QueueOverflow, QueueSettings},
+    store::{unix_now, SessionStore},
+};
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Session {
@@ -13,8 +27,20 @@ pub struct Session {
     pub token: String,
     pub presence: structures::BanchoPresence,
     pub stats: structures::BanchoStats,
+    /// Negotiated at login, sessions from before it was stored speak the latest
+    #[serde(default)]
+    pub protocol: structures::ProtocolVersion,
     pub relax: bool,
     pub autopilot: bool,
+    /// The id of the player being spectated
+    #[serde(default)]
+    pub spectating: Option<i32>,
+    /// The channels the player is in, by name
+    #[serde(default)]
+    pub channels: Vec<String>,
+    /// When the player logged in, in seconds since the unix epoch
+    #[serde(default)]
+    pub login_time: u64,
 }
 const COUNTRY_CODES: [&str; 252] = [
     "oc", "eu", "ad", "ae", "af", "ag", "ai", "al", "am", "an", "ao", "aq", "ar", "as", "at", "au",
@@ -38,7 +64,12 @@ const COUNTRY_CODES: [&str; 252] = [
 /*
     Builds the session struct based on the information within the database
 */
-pub fn build_session(user_data: MySqlRow, stats: MySqlRow, uuid: String) -> Session {
+pub fn build_session(
+    user_data: MySqlRow,
+    stats: MySqlRow,
+    uuid: String,
+    protocol: structures::ProtocolVersion,
+) -> Session {
     let id = user_data.get(0_usize);
     let username = user_data.get(1_usize);
     let country: String = user_data.get(6_usize);
@@ -52,8 +83,8 @@ pub fn build_session(user_data: MySqlRow, stats: MySqlRow, uuid: String) -> Sess
         username,
         timezone: 0,
         country_code: (country_code as u8) + 1,
-        play_mode: 0,
-        permissions: 4,
+        play_mode: structures::GameMode::Osu,
+        permissions: structures::BanchoPrivileges::SUPPORTER,
         longitude: 0.,
         latitude: 0.,
         player_rank: 0,
@@ -67,11 +98,11 @@ pub fn build_session(user_data: MySqlRow, stats: MySqlRow, uuid: String) -> Sess
     let performance: i16 = stats.try_get(8).unwrap_or(0);
 
     let status = structures::ClientStatus {
-        status: 0,
-        status_text: "".to_string(),
-        beatmap_checksum: "".to_string(),
-        current_mods: 0,
-        play_mode: 0,
+        status: structures::Action::Idle,
+        status_text: "".into(),
+        beatmap_checksum: "".into(),
+        current_mods: Mods::empty(),
+        play_mode: structures::GameMode::Osu,
         beatmap_id: 0,
     };
 
@@ -90,173 +121,582 @@ pub fn build_session(user_data: MySqlRow, stats: MySqlRow, uuid: String) -> Sess
         token: uuid,
         presence,
         stats,
+        protocol,
         relax: false,
         autopilot: false,
+        spectating: None,
+        channels: Vec::new(),
+        login_time: unix_now(),
     }
 }
 
-pub async fn find_player_from_username(username: String, data: Databases) -> Session {
-    let all_online = data
-        .redis()
-        .await
-        .unwrap()
-        .keys::<_, Vec<String>>("gamma::buffers::*")
-        .await
-        .map_err(InternalError::Redis);
+/// Where a session is stored in redis
+pub fn session_key(token: &str) -> String {
+    format!("gamma::sessions::{}", token)
+}
 
-    for player in all_online {
-        let p = data
-            .redis()
-            .await
-            .unwrap()
-            .get::<_, String>(player)
-            .await
-            .map_err(InternalError::Redis);
-        if p.is_err() {
-            break;
-        }
-        let result = p.unwrap();
+/// Where the packets waiting to be sent to a session are queued up in redis
+pub fn buffer_key(token: &str) -> String {
+    format!("gamma::buffers::{}", token)
+}
+
+/// Where the tokens of everyone in a channel are kept in redis
+pub fn channel_key(channel: &str) -> String {
+    format!("gamma::channels::{}", channel)
+}
+
+/// The lowercase, underscored form of a username, the way `users.username_safe` stores it
+pub fn safe_username(username: &str) -> String {
+    username.replace(' ', "_").to_lowercase()
+}
+
+/// How many sessions are fetched in each pipeline, and asked for in each `SSCAN`
+const SCAN_BATCH: usize = 100;
+
+/// The online players, kept in redis next to the sessions themselves
+///
+/// Alongside each `gamma::sessions::{token}` there's a set of every online token,
+/// and hashes from user id and safe username to the token, so players can be found
+/// without looking through every session. When each token was last seen is kept in
+/// a sorted set, so the idle ones can be picked out by score. Each channel has a set of
+/// the tokens in it, and the names of every channel anyone has joined are kept in a set
+/// of their own.
+pub struct SessionRegistry {
+    pool: deadpool_redis::Pool,
+    queue: QueueSettings,
+    enqueue: redis::Script,
+    join_channel: redis::Script,
+    forget: redis::Script,
+}
+
+impl SessionRegistry {
+    const ONLINE: &'static str = "gamma::online";
+    const BY_ID: &'static str = "gamma::online::by_id";
+    const BY_NAME: &'static str = "gamma::online::by_name";
+    const LAST_SEEN: &'static str = "gamma::online::last_seen";
+    const CHANNELS: &'static str = "gamma::channels";
 
-        let session: Session = serde_json::from_str(result.as_str()).unwrap();
+    /// Pushes to the queue of each token still in the online set, then trims it
+    ///
+    /// KEYS are the online set followed by each token's queue, ARGV the packets, the
+    /// range to trim to, then the tokens themselves in the same order as their queues.
+    /// Checking and pushing in one script means a token taken offline in between
+    /// can't be left with a queue nobody will ever drain.
+    const ENQUEUE: &'static str = r#"
+        for i = 2, #KEYS do
+            if redis.call('SISMEMBER', KEYS[1], ARGV[i + 2]) == 1 then
+                redis.call('RPUSH', KEYS[i], ARGV[1])
+                redis.call('LTRIM', KEYS[i], ARGV[2], ARGV[3])
+            end
+        end
+    "#;
 
-        if session.presence.username == username {
-            return session;
+    /// Adds a token still in the online set to a channel
+    ///
+    /// KEYS are the online set, the channel's set and the set of channel names, ARGV the token and the channel.
+    const JOIN_CHANNEL: &'static str = r#"
+        if redis.call('SISMEMBER', KEYS[1], ARGV[1]) == 1 then
+            redis.call('SADD', KEYS[2], ARGV[1])
+            redis.call('SADD', KEYS[3], ARGV[2])
+        end
+    "#;
+
+    /// Drops every trace of a token whose session has gone missing
+    ///
+    /// Without the session there's no id or username to find its entries by, so the hashes are searched
+    /// for the token instead, along with every channel anyone has joined. KEYS are the online set, the last
+    /// seen set, the id and name hashes, the set of channel names and the token's queue, ARGV the token and
+    /// the prefix of the channels' keys.
+    const FORGET: &'static str = r#"
+        redis.call('SREM', KEYS[1], ARGV[1])
+        redis.call('ZREM', KEYS[2], ARGV[1])
+        for _, hash in ipairs({KEYS[3], KEYS[4]}) do
+            local entries = redis.call('HGETALL', hash)
+            for i = 1, #entries, 2 do
+                if entries[i + 1] == ARGV[1] then
+                    redis.call('HDEL', hash, entries[i])
+                end
+            end
+        end
+        for _, channel in ipairs(redis.call('SMEMBERS', KEYS[5])) do
+            redis.call('SREM', ARGV[2] .. channel, ARGV[1])
+        end
+        redis.call('DEL', KEYS[6])
+    "#;
+
+    pub fn new(pool: deadpool_redis::Pool, queue: QueueSettings) -> Self {
+        SessionRegistry {
+            pool,
+            queue,
+            enqueue: redis::Script::new(Self::ENQUEUE),
+            join_channel: redis::Script::new(Self::JOIN_CHANNEL),
+            forget: redis::Script::new(Self::FORGET),
         }
     }
-    Session {
-        id: 0,
-        token: "".to_string(),
-        presence: structures::BanchoPresence {
-            player_id: 0,
-            username: "".to_string(),
-            timezone: 0,
-            country_code: 0,
-            play_mode: 0,
-            permissions: 0,
-            longitude: 0.,
-            latitude: 0.,
-            player_rank: 0,
-        },
-        stats: structures::BanchoStats {
-            player_id: 0,
-            status: structures::ClientStatus {
-                status: 0,
-                status_text: "".to_string(),
-                beatmap_checksum: "".to_string(),
-                current_mods: 0,
-                play_mode: 0,
-                beatmap_id: 0,
-            },
-            ranked_score: 0,
-            total_score: 0,
-            play_count: 0,
-            accuracy: 0.,
-            rank: 0,
-            performance: 0,
-        },
-        relax: false,
-        autopilot: false,
+
+    async fn redis(&self) -> Result<deadpool_redis::Connection, InternalError> {
+        Ok(self.pool.get().await?)
+    }
+
+    /// Take a token offline when its session is already gone, see [`SessionRegistry::FORGET`]
+    ///
+    /// This only happens when the session's key went away without [`SessionStore::remove`], so it
+    /// doesn't matter that it's slower.
+    async fn forget(
+        &self,
+        token: &str,
+        redis: &mut deadpool_redis::Connection,
+    ) -> Result<(), InternalError> {
+        self.forget
+            .key(Self::ONLINE)
+            .key(Self::LAST_SEEN)
+            .key(Self::BY_ID)
+            .key(Self::BY_NAME)
+            .key(Self::CHANNELS)
+            .key(buffer_key(token))
+            .arg(token)
+            .arg(channel_key(""))
+            .invoke_async::<_, ()>(redis)
+            .await?;
+        Ok(())
     }
 }
 
-pub async fn all_online_status(buffer: &mut Buffer, redis: &mut deadpool_redis::Connection) {
-    let all_online = redis
-        .keys::<_, Vec<String>>("gamma::sessions::*")
-        .await
-        .map_err(InternalError::Redis);
-
-    if all_online.is_ok() {
-        for player in all_online.unwrap() {
-            let p = redis
-                .get::<_, String>(player)
-                .await
-                .map_err(InternalError::Redis);
-            let session: Session = serde_json::from_str(&p.unwrap()).unwrap();
-
-            bancho_user_presence(buffer, session.presence.clone());
-            bancho_handle_osu_update(buffer, session.stats.clone());
+#[async_trait]
+impl SessionStore for SessionRegistry {
+    async fn get(&self, token: &str) -> Result<Option<Session>, InternalError> {
+        let session: Option<String> = self.redis().await?.get(session_key(token)).await?;
+        Ok(session
+            .map(|session| serde_json::from_str(&session))
+            .transpose()?)
+    }
+
+    async fn insert(&self, session: &Session) -> Result<(), InternalError> {
+        if let Some(old) = self.token_by_id(session.id).await? {
+            if old != session.token {
+                self.remove(&old).await?;
+            }
         }
+
+        let mut pipe = redis::pipe();
+        pipe.atomic()
+            .set(
+                session_key(&session.token),
+                serde_json::to_string(session).unwrap(),
+            )
+            .ignore()
+            .sadd(Self::ONLINE, &session.token)
+            .ignore()
+            .hset(Self::BY_ID, session.id, &session.token)
+            .ignore()
+            .hset(
+                Self::BY_NAME,
+                safe_username(&session.presence.username),
+                &session.token,
+            )
+            .ignore()
+            .zadd(Self::LAST_SEEN, &session.token, unix_now())
+            .ignore();
+        for channel in &session.channels {
+            pipe.sadd(channel_key(channel), &session.token)
+                .ignore()
+                .sadd(Self::CHANNELS, channel)
+                .ignore();
+        }
+        pipe.query_async::<_, ()>(&mut self.redis().await?).await?;
+        Ok(())
     }
-}
 
-pub async fn announce_online(session: Session, redis: &mut deadpool_redis::Connection) {
-    let all_online = redis
-        .keys::<_, Vec<String>>("gamma::sessions::*")
-        .await
-        .map_err(InternalError::Redis);
+    async fn save(&self, session: &Session) -> Result<(), InternalError> {
+        // XX only overwrites, so a session which has been taken offline in the meantime stays offline
+        redis::cmd("SET")
+            .arg(session_key(&session.token))
+            .arg(serde_json::to_string(session).unwrap())
+            .arg("XX")
+            .query_async::<_, ()>(&mut self.redis().await?)
+            .await?;
+        Ok(())
+    }
 
-    if all_online.is_ok() {
-        let all_players = all_online.unwrap();
-        let mut b = Buffer::new();
-        bancho_user_presence(&mut b, session.clone().presence);
-        bancho_handle_osu_update(&mut b, session.clone().stats);
+    async fn remove(&self, token: &str) -> Result<Option<Session>, InternalError> {
+        let session = self.get(token).await?;
+
+        let mut pipe = redis::pipe();
+        pipe.atomic()
+            .del(&[session_key(token), buffer_key(token)])
+            .ignore()
+            .srem(Self::ONLINE, token)
+            .ignore()
+            .zrem(Self::LAST_SEEN, token)
+            .ignore();
+        if let Some(session) = &session {
+            pipe.hdel(Self::BY_ID, session.id)
+                .ignore()
+                .hdel(Self::BY_NAME, safe_username(&session.presence.username))
+                .ignore();
+            for channel in &session.channels {
+                pipe.srem(channel_key(channel), token).ignore();
+            }
+        }
+        pipe.query_async::<_, ()>(&mut self.redis().await?).await?;
 
-        for player in all_players {
-            let token = player.replace("gamma::sessions::", "");
+        Ok(session)
+    }
 
-            let mut cmd = redis::cmd("RPUSH");
-            cmd.arg(format!("gamma::buffers::{}", token));
+    async fn clear_spectating(&self, token: &str, host: i32) -> Result<bool, InternalError> {
+        let key = session_key(token);
+        let mut redis = self.redis().await?;
+        loop {
+            redis::cmd("WATCH")
+                .arg(&key)
+                .query_async::<_, ()>(&mut redis)
+                .await?;
+            let session: Option<String> = redis.get(&key).await?;
+            let mut session = match session
+                .map(|s| serde_json::from_str::<Session>(&s))
+                .transpose()
+            {
+                Ok(Some(session)) if session.spectating == Some(host) => session,
+                other => {
+                    redis::cmd("UNWATCH")
+                        .query_async::<_, ()>(&mut redis)
+                        .await?;
+                    return Ok(other.map(|_| false)?);
+                }
+            };
 
-            for &byte in b.iter() {
-                cmd.arg(byte as i32);
+            session.spectating = None;
+            // EXEC gives back nil if the session was changed after the WATCH, so go again with the new copy
+            let saved: Option<()> = redis::pipe()
+                .atomic()
+                .cmd("SET")
+                .arg(&key)
+                .arg(serde_json::to_string(&session).unwrap())
+                .arg("XX")
+                .ignore()
+                .query_async(&mut redis)
+                .await?;
+            if saved.is_some() {
+                return Ok(true);
             }
-            let _: () = cmd.query_async(redis).await.unwrap();
         }
     }
-}
 
-pub async fn update_stats(stats: structures::BanchoStats, redis: &mut deadpool_redis::Connection) {
-    let all_online = redis
-        .keys::<_, Vec<String>>("gamma::sessions::*")
-        .await
-        .map_err(InternalError::Redis);
+    async fn token_by_id(&self, id: i32) -> Result<Option<String>, InternalError> {
+        Ok(self.redis().await?.hget(Self::BY_ID, id).await?)
+    }
 
-    if all_online.is_ok() {
-        let all_players = all_online.unwrap();
-        let mut b = Buffer::new();
-        bancho_handle_osu_update(&mut b, stats);
+    async fn token_by_username(&self, username: &str) -> Result<Option<String>, InternalError> {
+        Ok(self
+            .redis()
+            .await?
+            .hget(Self::BY_NAME, safe_username(username))
+            .await?)
+    }
 
-        for player in all_players {
-            let token = player.replace("gamma::sessions::", "");
+    async fn touch(&self, token: &str) -> Result<(), InternalError> {
+        // XX only updates, so a session which has been taken offline isn't put back
+        redis::cmd("ZADD")
+            .arg(Self::LAST_SEEN)
+            .arg("XX")
+            .arg(unix_now())
+            .arg(token)
+            .query_async::<_, ()>(&mut self.redis().await?)
+            .await?;
+        Ok(())
+    }
 
-            let mut cmd = redis::cmd("RPUSH");
-            cmd.arg(format!("gamma::buffers::{}", token));
+    async fn idle(&self, since: u64) -> Result<Vec<String>, InternalError> {
+        Ok(self
+            .redis()
+            .await?
+            .zrangebyscore(Self::LAST_SEEN, "-inf", format!("({}", since))
+            .await?)
+    }
+
+    async fn tokens(&self) -> Result<Vec<String>, InternalError> {
+        let mut redis = self.redis().await?;
+        let mut tokens = Vec::new();
+        let mut cursor = 0u64;
+        loop {
+            let (next, batch): (u64, Vec<String>) = redis::cmd("SSCAN")
+                .arg(Self::ONLINE)
+                .cursor_arg(cursor)
+                .arg("COUNT")
+                .arg(SCAN_BATCH)
+                .query_async(&mut redis)
+                .await?;
+            tokens.extend(batch);
+
+            cursor = next;
+            if cursor == 0 {
+                break;
+            }
+        }
+
+        // a scan can hand back the same member more than once
+        tokens.sort_unstable();
+        tokens.dedup();
+        Ok(tokens)
+    }
+
+    /// Every online session, fetched a batch at a time
+    async fn sessions(&self) -> Result<Vec<Session>, InternalError> {
+        let tokens = self.tokens().await?;
+        let mut redis = self.redis().await?;
+        let mut sessions = Vec::with_capacity(tokens.len());
 
-            for &byte in b.iter() {
-                cmd.arg(byte as i32);
+        for batch in tokens.chunks(SCAN_BATCH) {
+            let mut pipe = redis::pipe();
+            for token in batch {
+                pipe.get(session_key(token));
             }
-            let _: () = cmd.query_async(redis).await.unwrap();
+            let found: Vec<Option<String>> = pipe.query_async(&mut redis).await?;
+
+            for (token, session) in batch.iter().zip(found) {
+                match session {
+                    Some(session) => sessions.push(serde_json::from_str(&session)?),
+                    // the session went away without being taken offline
+                    None => self.forget(token, &mut redis).await?,
+                }
+            }
+        }
+
+        Ok(sessions)
+    }
+
+    /// Each batch is a single list element, pushed to every online queue in one round trip
+    async fn enqueue(&self, tokens: &[String], packets: Bytes) -> Result<(), InternalError> {
+        if tokens.is_empty() {
+            return Ok(());
+        }
+
+        let max_len = self.queue.max_len.get() as isize;
+        // trimming right after the push, in the same script, means a queue never holds more than `max_len`
+        let (start, stop) = match self.queue.overflow {
+            QueueOverflow::DropOldest => (-max_len, -1),
+            QueueOverflow::DropNewest => (0, max_len - 1),
+        };
+
+        let mut invocation = self.enqueue.prepare_invoke();
+        invocation
+            .key(Self::ONLINE)
+            .arg(packets.as_ref())
+            .arg(start)
+            .arg(stop);
+        for token in tokens {
+            invocation.key(buffer_key(token)).arg(token);
+        }
+        invocation
+            .invoke_async::<_, ()>(&mut self.redis().await?)
+            .await?;
+        Ok(())
+    }
+
+    async fn drain(&self, token: &str) -> Result<BytesMut, InternalError> {
+        let (queue,): (Vec<Vec<u8>>,) = redis::pipe()
+            .atomic()
+            .lrange(buffer_key(token), 0, -1)
+            .del(buffer_key(token))
+            .ignore()
+            .query_async(&mut self.redis().await?)
+            .instrument(info_span!("update_buffer", uuid = token))
+            .await?;
+
+        let mut out = BytesMut::with_capacity(queue.iter().map(Vec::len).sum());
+        for packets in queue {
+            out.extend_from_slice(&packets);
+        }
+        Ok(out)
+    }
+
+    async fn join_channel(&self, token: &str, channel: &str) -> Result<(), InternalError> {
+        self.join_channel
+            .key(Self::ONLINE)
+            .key(channel_key(channel))
+            .key(Self::CHANNELS)
+            .arg(token)
+            .arg(channel)
+            .invoke_async::<_, ()>(&mut self.redis().await?)
+            .await?;
+        Ok(())
+    }
+
+    async fn leave_channel(&self, token: &str, channel: &str) -> Result<(), InternalError> {
+        Ok(self
+            .redis()
+            .await?
+            .srem(channel_key(channel), token)
+            .await?)
+    }
+
+    async fn channel_len(&self, channel: &str) -> Result<usize, InternalError> {
+        Ok(self.redis().await?.scard(channel_key(channel)).await?)
+    }
+}
+
+pub async fn all_online_status(
+    buffer: &mut Buffer,
+    protocol: structures::ProtocolVersion,
+    store: &dyn SessionStore,
+) {
+    if let Ok(all_online) = store.sessions().await {
+        for session in all_online {
+            bancho_user_presence(buffer, &session.presence, protocol);
+            bancho_handle_osu_update(buffer, &session.stats);
         }
     }
 }
 
-pub async fn send_pm(message: structures::BanchoMessage, redis: &mut deadpool_redis::Connection) {
-    let all_online = redis
-        .keys::<_, Vec<String>>("gamma::sessions::*")
+pub async fn announce_online(session: Session, store: &dyn SessionStore) {
+    if let Ok(all_online) = store.sessions().await {
+        // the presence has to be written in the layout each player's client expects, so every
+        // layout is encoded once up front and shared between the players that speak it
+        let layouts = structures::ProtocolVersion::ALL.map(|protocol| {
+            let mut b = Buffer::with_capacity(
+                HEADER_LEN + session.presence.encoded_len() + session.stats.packet_len(),
+            );
+            bancho_user_presence(&mut b, &session.presence, protocol);
+            bancho_handle_osu_update(&mut b, &session.stats);
+            (protocol, b.freeze())
+        });
+
+        for (protocol, b) in &layouts {
+            let tokens: Vec<String> = all_online
+                .iter()
+                .filter(|other| other.protocol == *protocol)
+                .map(|other| other.token.clone())
+                .collect();
+            let _ = store.enqueue(&tokens, b.clone()).await;
+        }
+    }
+}
+
+pub async fn update_stats(stats: structures::BanchoStats, store: &dyn SessionStore) {
+    if let Ok(all_online) = store.tokens().await {
+        let b = stats.to_packet_bytes();
+        let _ = store.enqueue(&all_online, b).await;
+    }
+}
+
+pub async fn send_pm(message: structures::BanchoMessage, store: &dyn SessionStore) {
+    if let Ok(Some(token)) = store.token_by_username(&message.target).await {
+        let b = message.to_packet_bytes();
+        let _ = store.enqueue(&[token], b).await;
+    }
+}
+
+/// Tell everyone how many players are now in each of `channels`
+pub async fn announce_channel_counts(
+    channels: &[String],
+    data: &Databases,
+) -> Result<(), RequestError> {
+    if channels.is_empty() {
+        return Ok(());
+    }
+    let store = data.sessions();
+    let topics = data.channel_topics().await?;
+
+    let mut b = Buffer::new();
+    // channels which aren't in the `channels` table aren't listed to begin with
+    for (name, topic) in channels
+        .iter()
+        .filter_map(|name| Some((name, topics.get(name)?)))
+    {
+        let connected = store.channel_len(name).await?;
+        let channel = structures::BanchoChannel {
+            name: name.clone(),
+            topic: topic.clone(),
+            connected: i16::try_from(connected).unwrap_or(i16::MAX),
+        };
+        bancho_channel_available(&mut b, &channel);
+    }
+    if b.is_empty() {
+        return Ok(());
+    }
+
+    let tokens = store.tokens().await?;
+    store.enqueue(&tokens, b.freeze()).await?;
+    Ok(())
+}
+
+/// Take a player offline and tell everyone else they've gone, returning their session if they were online
+///
+/// Whoever they were spectating loses a spectator, which the host and its other spectators are told about,
+/// and anyone spectating them stops. They leave every channel they were in, with everyone sent the new counts,
+/// there's nobody left to send a kick to. Gamma doesn't host multiplayer matches yet, so there are none to leave.
+#[instrument(skip(data))]
+pub async fn end_session(token: &str, data: &Databases) -> Result<Option<Session>, RequestError> {
+    let store = data.sessions();
+    let Some(session) = store.remove(token).await? else {
+        return Ok(None);
+    };
+
+    // nothing retries this once the session is gone, so everyone is told before anything else can fail
+    let mut b = Buffer::new();
+    bancho_handle_user_quit(&mut b, session.id);
+    store.enqueue(&store.tokens().await?, b.freeze()).await?;
+
+    let all_online = store.sessions().await?;
+
+    if let Some(host) = session.spectating {
+        if let Some(host_token) = store.token_by_id(host).await? {
+            let mut b = Buffer::new();
+            bancho_spectator_left(&mut b, session.id);
+            store.enqueue(&[host_token], b.freeze()).await?;
+        }
+
+        let fellows: Vec<String> = all_online
+            .iter()
+            .filter(|other| other.spectating == Some(host))
+            .map(|other| other.token.clone())
+            .collect();
+        let mut b = Buffer::new();
+        bancho_fellow_spectator_left(&mut b, session.id);
+        store.enqueue(&fellows, b.freeze()).await?;
+    }
+
+    let spectators = all_online
+        .iter()
+        .filter(|other| other.spectating == Some(session.id));
+    for spectator in spectators {
+        // only the one field, anything else they've changed since `sessions` was read is kept
+        store.clear_spectating(&spectator.token, session.id).await?;
+    }
+
+    // only the counts are out of date if this fails, it's logged when the error is made
+    let _ = announce_channel_counts(&session.channels, data).await;
+
+    sqlx::query("UPDATE `users` SET `last_online` = NOW() WHERE `id` = ?")
+        .bind(session.id)
+        .execute(&mut data.mysql().await?)
         .await
-        .map_err(InternalError::Redis);
-
-    if all_online.is_ok() {
-        let all_players = all_online.unwrap();
-        for player in all_players {
-            let p = redis
-                .get::<_, String>(&player)
-                .await
-                .map_err(InternalError::Redis);
-
-            let session: Session = serde_json::from_str(&p.unwrap()).unwrap();
-            if session.presence.username == message.target {
-                let token = player.replace("gamma::sessions::", "");
-                let mut b = Buffer::new();
-                bancho_send_message(&mut b, message.clone());
-
-                let mut cmd = redis::cmd("RPUSH");
-                cmd.arg(format!("gamma::buffers::{}", token));
-
-                for &byte in b.iter() {
-                    cmd.arg(byte as i32);
-                }
-                let _: () = cmd.query_async(redis).await.unwrap();
+        .map_err(InternalError::SqlPool)?;
+
+    Ok(Some(session))
+}
+
+/// How often sessions are checked for having timed out
+const REAP_INTERVAL: Duration = Duration::from_secs(10);
+
+/// End every session which hasn't sent a request in `timeout`, forever
+pub async fn reap_idle(data: Arc<Databases>, timeout: Duration) {
+    let mut interval = tokio::time::interval(REAP_INTERVAL);
+    loop {
+        interval.tick().await;
+
+        let since = unix_now().saturating_sub(timeout.as_secs());
+        let idle = match data.sessions().idle(since).await {
+            Ok(idle) => idle,
+            Err(e) => {
+                let _ = RequestError::from(e);
+                continue;
+            }
+        };
+        for token in idle {
+            // errors are logged when they're made, the rest can still be reaped
+            if let Ok(Some(session)) = end_session(&token, &data).await {
+                info!("{} timed out", session.presence.username);
             }
         }
     }
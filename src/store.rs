@@ -0,0 +1,502 @@
+//! Where sessions and the packets queued up for them are kept
+//!
+//! Gamma only talks to sessions through [`SessionStore`], so the backend is picked in the settings:
+//! redis through [`SessionRegistry`](crate::sessions::SessionRegistry), which lets several gamma processes share
+//! players, or [`MemoryStore`], which keeps everything in this process for single node deployments and tests.
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use actix_web::web::{Bytes, BytesMut};
+use async_trait::async_trait;
+
+use crate::{
+    errors::InternalError,
+    sessions::{safe_username, Session},
+    settings::{QueueOverflow, QueueSettings},
+};
+
+#[async_trait]
+pub trait SessionStore: Send + Sync {
+    async fn get(&self, token: &str) -> Result<Option<Session>, InternalError>;
+
+    /// Put a newly logged in session online in each of its channels, replacing any older session of the same player
+    async fn insert(&self, session: &Session) -> Result<(), InternalError>;
+
+    /// Store the changes made to a session which is already online
+    async fn save(&self, session: &Session) -> Result<(), InternalError>;
+
+    /// Take a session offline along with its queued packets and out of its channels, returning it if it was online
+    async fn remove(&self, token: &str) -> Result<Option<Session>, InternalError>;
+
+    /// Stop `token` spectating `host`, leaving the rest of its session as it's stored
+    ///
+    /// Returns whether it was spectating them, a session which has moved on is left alone.
+    async fn clear_spectating(&self, token: &str, host: i32) -> Result<bool, InternalError>;
+
+    async fn token_by_id(&self, id: i32) -> Result<Option<String>, InternalError>;
+
+    async fn token_by_username(&self, username: &str) -> Result<Option<String>, InternalError>;
+
+    /// Mark a session as seen just now
+    async fn touch(&self, token: &str) -> Result<(), InternalError>;
+
+    /// Every online token which hasn't been seen since `since`, in seconds since the unix epoch
+    async fn idle(&self, since: u64) -> Result<Vec<String>, InternalError>;
+
+    /// Every online token
+    async fn tokens(&self) -> Result<Vec<String>, InternalError>;
+
+    /// Every online session
+    async fn sessions(&self) -> Result<Vec<Session>, InternalError>;
+
+    /// Queue up a batch of packets to be sent to each of `tokens` the next time they're flushed
+    ///
+    /// Each player's queue holds at most [`QueueSettings::max_len`] batches, past that the
+    /// [`QueueOverflow`] policy decides which batch is thrown away.
+    async fn enqueue(&self, tokens: &[String], packets: Bytes) -> Result<(), InternalError>;
+
+    /// Take everything queued up for `token`, oldest first
+    async fn drain(&self, token: &str) -> Result<BytesMut, InternalError>;
+
+    /// Add `token` to the members of `channel`
+    async fn join_channel(&self, token: &str, channel: &str) -> Result<(), InternalError>;
+
+    /// Take `token` out of the members of `channel`
+    async fn leave_channel(&self, token: &str, channel: &str) -> Result<(), InternalError>;
+
+    /// How many players are in `channel`
+    async fn channel_len(&self, channel: &str) -> Result<usize, InternalError>;
+}
+
+/// The current time in seconds since the unix epoch, as sessions' last seen times are kept
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// Sessions kept in this process, nothing is shared with other gamma processes or survives a restart
+#[derive(Default)]
+pub struct MemoryStore {
+    online: Mutex<Online>,
+    queue: QueueSettings,
+}
+
+#[derive(Default)]
+struct Online {
+    sessions: HashMap<String, Session>,
+    by_id: HashMap<i32, String>,
+    by_name: HashMap<String, String>,
+    queues: HashMap<String, VecDeque<Bytes>>,
+    last_seen: HashMap<String, u64>,
+    channels: HashMap<String, HashSet<String>>,
+}
+
+impl MemoryStore {
+    pub fn new(queue: QueueSettings) -> Self {
+        MemoryStore {
+            online: Mutex::default(),
+            queue,
+        }
+    }
+}
+
+impl Online {
+    fn remove(&mut self, token: &str) -> Option<Session> {
+        self.queues.remove(token);
+        self.last_seen.remove(token);
+        for members in self.channels.values_mut() {
+            members.remove(token);
+        }
+        let session = self.sessions.remove(token)?;
+        self.by_id.remove(&session.id);
+        self.by_name
+            .remove(&safe_username(&session.presence.username));
+        Some(session)
+    }
+}
+
+#[async_trait]
+impl SessionStore for MemoryStore {
+    async fn get(&self, token: &str) -> Result<Option<Session>, InternalError> {
+        Ok(self.online.lock().unwrap().sessions.get(token).cloned())
+    }
+
+    async fn insert(&self, session: &Session) -> Result<(), InternalError> {
+        let mut online = self.online.lock().unwrap();
+        if let Some(old) = online.by_id.get(&session.id).cloned() {
+            online.remove(&old);
+        }
+
+        online.by_id.insert(session.id, session.token.clone());
+        online.by_name.insert(
+            safe_username(&session.presence.username),
+            session.token.clone(),
+        );
+        online.queues.insert(session.token.clone(), VecDeque::new());
+        online.last_seen.insert(session.token.clone(), unix_now());
+        for channel in &session.channels {
+            online
+                .channels
+                .entry(channel.clone())
+                .or_default()
+                .insert(session.token.clone());
+        }
+        online
+            .sessions
+            .insert(session.token.clone(), session.clone());
+        Ok(())
+    }
+
+    async fn save(&self, session: &Session) -> Result<(), InternalError> {
+        let mut online = self.online.lock().unwrap();
+        // a session which has been taken offline in the meantime stays offline
+        if let Some(stored) = online.sessions.get_mut(&session.token) {
+            *stored = session.clone();
+        }
+        Ok(())
+    }
+
+    async fn remove(&self, token: &str) -> Result<Option<Session>, InternalError> {
+        Ok(self.online.lock().unwrap().remove(token))
+    }
+
+    async fn clear_spectating(&self, token: &str, host: i32) -> Result<bool, InternalError> {
+        let mut online = self.online.lock().unwrap();
+        match online.sessions.get_mut(token) {
+            Some(session) if session.spectating == Some(host) => {
+                session.spectating = None;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn token_by_id(&self, id: i32) -> Result<Option<String>, InternalError> {
+        Ok(self.online.lock().unwrap().by_id.get(&id).cloned())
+    }
+
+    async fn token_by_username(&self, username: &str) -> Result<Option<String>, InternalError> {
+        let online = self.online.lock().unwrap();
+        Ok(online.by_name.get(&safe_username(username)).cloned())
+    }
+
+    async fn touch(&self, token: &str) -> Result<(), InternalError> {
+        if let Some(last_seen) = self.online.lock().unwrap().last_seen.get_mut(token) {
+            *last_seen = unix_now();
+        }
+        Ok(())
+    }
+
+    async fn idle(&self, since: u64) -> Result<Vec<String>, InternalError> {
+        let online = self.online.lock().unwrap();
+        Ok(online
+            .last_seen
+            .iter()
+            .filter(|(_, &last_seen)| last_seen < since)
+            .map(|(token, _)| token.clone())
+            .collect())
+    }
+
+    async fn tokens(&self) -> Result<Vec<String>, InternalError> {
+        Ok(self
+            .online
+            .lock()
+            .unwrap()
+            .sessions
+            .keys()
+            .cloned()
+            .collect())
+    }
+
+    async fn sessions(&self) -> Result<Vec<Session>, InternalError> {
+        Ok(self
+            .online
+            .lock()
+            .unwrap()
+            .sessions
+            .values()
+            .cloned()
+            .collect())
+    }
+
+    async fn enqueue(&self, tokens: &[String], packets: Bytes) -> Result<(), InternalError> {
+        let max_len = self.queue.max_len.get();
+        let mut online = self.online.lock().unwrap();
+        for token in tokens {
+            // nobody is going to drain the queue of a player who isn't online
+            let Some(queue) = online.queues.get_mut(token) else {
+                continue;
+            };
+            if queue.len() >= max_len {
+                match self.queue.overflow {
+                    QueueOverflow::DropOldest => drop(queue.pop_front()),
+                    QueueOverflow::DropNewest => continue,
+                }
+            }
+            queue.push_back(packets.clone());
+        }
+        Ok(())
+    }
+
+    async fn drain(&self, token: &str) -> Result<BytesMut, InternalError> {
+        let queue = match self.online.lock().unwrap().queues.get_mut(token) {
+            Some(queue) => std::mem::take(queue),
+            None => return Ok(BytesMut::new()),
+        };
+
+        let mut out = BytesMut::with_capacity(queue.iter().map(Bytes::len).sum());
+        for packets in queue {
+            out.extend_from_slice(&packets);
+        }
+        Ok(out)
+    }
+
+    async fn join_channel(&self, token: &str, channel: &str) -> Result<(), InternalError> {
+        let mut online = self.online.lock().unwrap();
+        // a session which has been taken offline in the meantime doesn't join
+        if online.sessions.contains_key(token) {
+            online
+                .channels
+                .entry(channel.to_string())
+                .or_default()
+                .insert(token.to_string());
+        }
+        Ok(())
+    }
+
+    async fn leave_channel(&self, token: &str, channel: &str) -> Result<(), InternalError> {
+        if let Some(members) = self.online.lock().unwrap().channels.get_mut(channel) {
+            members.remove(token);
+        }
+        Ok(())
+    }
+
+    async fn channel_len(&self, channel: &str) -> Result<usize, InternalError> {
+        let online = self.online.lock().unwrap();
+        Ok(online.channels.get(channel).map_or(0, HashSet::len))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::num::NonZeroUsize;
+
+    use bancho_packet::packets::{mods::Mods, structures::*};
+
+    use super::*;
+
+    pub(crate) fn session(id: i32, username: &str, token: &str) -> Session {
+        Session {
+            id,
+            token: token.to_string(),
+            presence: BanchoPresence {
+                player_id: id,
+                username: username.to_string(),
+                timezone: 0,
+                country_code: 0,
+                play_mode: GameMode::Osu,
+                permissions: BanchoPrivileges::SUPPORTER,
+                longitude: 0.,
+                latitude: 0.,
+                player_rank: 0,
+            },
+            stats: BanchoStats {
+                player_id: id,
+                status: ClientStatus {
+                    status: Action::Idle,
+                    status_text: "".into(),
+                    beatmap_checksum: "".into(),
+                    current_mods: Mods::empty(),
+                    play_mode: GameMode::Osu,
+                    beatmap_id: 0,
+                },
+                ranked_score: 0,
+                total_score: 0,
+                play_count: 0,
+                accuracy: 0.,
+                rank: 0,
+                performance: 0,
+            },
+            protocol: ProtocolVersion::LATEST,
+            relax: false,
+            autopilot: false,
+            spectating: None,
+            channels: Vec::new(),
+            login_time: 0,
+        }
+    }
+
+    #[actix_web::test]
+    async fn sessions_are_indexed() {
+        let store = MemoryStore::default();
+        store
+            .insert(&session(1000, "Cookie Zi", "a"))
+            .await
+            .unwrap();
+        store.insert(&session(1001, "peppy", "b")).await.unwrap();
+
+        assert_eq!(store.token_by_id(1000).await.unwrap().as_deref(), Some("a"));
+        assert_eq!(
+            store
+                .token_by_username("cookie_zi")
+                .await
+                .unwrap()
+                .as_deref(),
+            Some("a")
+        );
+        let mut tokens = store.tokens().await.unwrap();
+        tokens.sort();
+        assert_eq!(tokens, ["a", "b"]);
+
+        let removed = store.remove("a").await.unwrap().unwrap();
+        assert_eq!(removed.id, 1000);
+        assert_eq!(store.get("a").await.unwrap().map(|s| s.id), None);
+        assert_eq!(store.token_by_username("Cookie Zi").await.unwrap(), None);
+        assert_eq!(store.tokens().await.unwrap(), ["b"]);
+    }
+
+    #[actix_web::test]
+    async fn logging_in_again_replaces_the_old_session() {
+        let store = MemoryStore::default();
+        store.insert(&session(1000, "peppy", "old")).await.unwrap();
+        store
+            .enqueue(&["old".to_string()], Bytes::from_static(&[1, 2]))
+            .await
+            .unwrap();
+        store.insert(&session(1000, "peppy", "new")).await.unwrap();
+
+        assert_eq!(store.tokens().await.unwrap(), ["new"]);
+        assert_eq!(
+            store.token_by_id(1000).await.unwrap().as_deref(),
+            Some("new")
+        );
+        assert!(store.drain("old").await.unwrap().is_empty());
+
+        // saving the stale session doesn't bring it back
+        store.save(&session(1000, "peppy", "old")).await.unwrap();
+        assert_eq!(store.tokens().await.unwrap(), ["new"]);
+    }
+
+    #[actix_web::test]
+    async fn queues_drain_once() {
+        let store = MemoryStore::default();
+        store.insert(&session(1000, "peppy", "a")).await.unwrap();
+        store.insert(&session(1001, "cookiezi", "b")).await.unwrap();
+
+        let both = ["a".to_string(), "b".to_string()];
+        store
+            .enqueue(&both, Bytes::from_static(&[1, 2]))
+            .await
+            .unwrap();
+        store
+            .enqueue(&both[..1], Bytes::from_static(&[3]))
+            .await
+            .unwrap();
+        // nobody is online with this token
+        store
+            .enqueue(&["c".to_string()], Bytes::from_static(&[4]))
+            .await
+            .unwrap();
+
+        assert_eq!(store.drain("a").await.unwrap(), &[1, 2, 3][..]);
+        assert_eq!(store.drain("b").await.unwrap(), &[1, 2][..]);
+        assert!(store.drain("a").await.unwrap().is_empty());
+        assert!(store.drain("c").await.unwrap().is_empty());
+    }
+
+    #[actix_web::test]
+    async fn full_queues_overflow() {
+        for (overflow, kept) in [
+            (QueueOverflow::DropOldest, [2, 3]),
+            (QueueOverflow::DropNewest, [1, 2]),
+        ] {
+            let store = MemoryStore::new(QueueSettings {
+                max_len: NonZeroUsize::new(2).unwrap(),
+                overflow,
+            });
+            store.insert(&session(1000, "peppy", "a")).await.unwrap();
+            for batch in [1, 2, 3] {
+                store
+                    .enqueue(&["a".to_string()], Bytes::from(vec![batch]))
+                    .await
+                    .unwrap();
+            }
+
+            assert_eq!(store.drain("a").await.unwrap(), &kept[..], "{overflow:?}");
+        }
+    }
+
+    #[actix_web::test]
+    async fn idle_sessions() {
+        let store = MemoryStore::default();
+        let now = unix_now();
+        store.insert(&session(1000, "peppy", "a")).await.unwrap();
+
+        assert!(store.idle(now).await.unwrap().is_empty());
+        assert_eq!(store.idle(now + 60).await.unwrap(), ["a"]);
+
+        store.remove("a").await.unwrap();
+        store.touch("a").await.unwrap();
+        assert!(store.idle(now + 60).await.unwrap().is_empty());
+    }
+
+    #[actix_web::test]
+    async fn clearing_spectating_keeps_the_rest_of_the_session() {
+        let store = MemoryStore::default();
+        store
+            .insert(&Session {
+                spectating: Some(1001),
+                ..session(1000, "peppy", "a")
+            })
+            .await
+            .unwrap();
+        // changed after the spectated player's copy was read
+        store
+            .save(&Session {
+                spectating: Some(1001),
+                relax: true,
+                ..session(1000, "peppy", "a")
+            })
+            .await
+            .unwrap();
+
+        assert!(!store.clear_spectating("a", 1002).await.unwrap());
+        assert!(store.clear_spectating("a", 1001).await.unwrap());
+        let stored = store.get("a").await.unwrap().unwrap();
+        assert_eq!(stored.spectating, None);
+        assert!(stored.relax);
+
+        assert!(!store.clear_spectating("b", 1001).await.unwrap());
+    }
+
+    #[actix_web::test]
+    async fn channel_members() {
+        let store = MemoryStore::default();
+        store
+            .insert(&Session {
+                channels: vec!["#osu".to_string()],
+                ..session(1000, "peppy", "a")
+            })
+            .await
+            .unwrap();
+        store.insert(&session(1001, "cookiezi", "b")).await.unwrap();
+
+        assert_eq!(store.channel_len("#osu").await.unwrap(), 1);
+        store.join_channel("b", "#osu").await.unwrap();
+        store.join_channel("b", "#osu").await.unwrap();
+        // nobody is online as this token
+        store.join_channel("c", "#osu").await.unwrap();
+        assert_eq!(store.channel_len("#osu").await.unwrap(), 2);
+
+        store.leave_channel("b", "#osu").await.unwrap();
+        assert_eq!(store.channel_len("#osu").await.unwrap(), 1);
+        store.remove("a").await.unwrap();
+        assert_eq!(store.channel_len("#osu").await.unwrap(), 0);
+        assert_eq!(store.channel_len("#lobby").await.unwrap(), 0);
+    }
+}
@@ -0,0 +1,233 @@
+//! Bancho over a persistent TCP connection, spoken by older builds and some bots
+//!
+//! The login is the same plain text as the HTTP login, after that both sides just send packets back and forth.
+//! Everything goes through the same handlers as `server::bancho_server`, with packets queued for the player
+//! flushed every [`POLL_INTERVAL`], as if the client was polling over HTTP.
+//! Only packets from the client count as it being seen, so a quiet client is pinged to keep its session alive.
+use std::{net::SocketAddr, path::Path, sync::Arc, time::Duration};
+
+use actix_web::web::{Bytes, BytesMut};
+use bancho_packet::{
+    buffer::serialization::BytesMutExt,
+    bytes::BufMut,
+    error::{CodecError, DecodeError},
+    packets::{capture::RecordKind, codec::FrameCodec, framer::Frame, writer::bancho_ping},
+};
+use futures_util::{FutureExt, SinkExt, StreamExt};
+use thiserror::Error;
+use tokio::{
+    io::AsyncReadExt,
+    net::{TcpListener, TcpStream},
+    time::Instant,
+};
+use tokio_util::codec::{Framed, FramedParts};
+use tracing::{debug, error, info_span, Instrument};
+
+use crate::{
+    capture,
+    db::Databases,
+    errors::{ExternalError, RequestError},
+    server, sessions,
+    settings::Settings,
+};
+
+/// How often packets queued for a player are sent to them
+pub const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The login is three short lines, anything longer isn't a login
+const MAX_LOGIN_LEN: usize = 4096;
+
+#[derive(Debug, Error)]
+enum ConnectionError {
+    #[error(transparent)]
+    Codec(#[from] CodecError),
+
+    #[error(transparent)]
+    Request(#[from] RequestError),
+
+    #[error("connection closed during login")]
+    Closed,
+
+    #[error("login was over {} bytes", MAX_LOGIN_LEN)]
+    LoginTooLong,
+
+    #[error("login failed: {}", .0)]
+    LoginFailed(String),
+}
+
+impl From<std::io::Error> for ConnectionError {
+    fn from(err: std::io::Error) -> Self {
+        ConnectionError::Codec(err.into())
+    }
+}
+
+/// Accept connections forever, serving each on its own task
+pub async fn serve(listener: TcpListener, data: Arc<Databases>, settings: Arc<Settings>) {
+    loop {
+        let (stream, peer_addr) = match listener.accept().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                error!(msg = "could not accept tcp connection", err = e.to_string());
+                continue;
+            }
+        };
+
+        let data = data.clone();
+        let settings = settings.clone();
+        actix_web::rt::spawn(
+            async move {
+                let capture_dir = settings.capture_dir.as_deref();
+                // twice per timeout, so one lost ping doesn't end the session
+                let ping_every =
+                    (Duration::from_secs(settings.session_timeout) / 2).max(POLL_INTERVAL);
+                let connection =
+                    handle_connection(stream, peer_addr, &data, capture_dir, ping_every);
+                if let Err(e) = connection.await {
+                    debug!(msg = "tcp connection closed", err = e.to_string());
+                }
+            }
+            .instrument(info_span!("tcp_connection", peer_addr = %peer_addr)),
+        );
+    }
+}
+
+async fn handle_connection(
+    mut stream: TcpStream,
+    peer_addr: SocketAddr,
+    data: &Databases,
+    capture_dir: Option<&Path>,
+    ping_every: Duration,
+) -> Result<(), ConnectionError> {
+    let mut read_buf = BytesMut::new();
+    let login_len = read_login(&mut stream, &mut read_buf).await?;
+    let login_body = read_buf.split_to(login_len).freeze();
+
+    let peer_addr = peer_addr.to_string();
+    let login = server::handle_login(login_body.clone(), Some(&peer_addr), data).await?;
+    let session = login.success.then(|| login.token.clone());
+
+    // anything sent after the login is already packets
+    let mut parts = FramedParts::new::<Bytes>(stream, FrameCodec::new());
+    parts.read_buf = read_buf;
+    let framed = Framed::from_parts(parts);
+
+    let result = serve_session(framed, login_body, login, data, capture_dir, ping_every).await;
+    if let Some(token) = session {
+        // however the connection ended, don't leave the player online until they're reaped
+        let _ = sessions::end_session(&token, data).await;
+    }
+    result
+}
+
+/// Send the login response, then keep handling packets until the connection or the session ends
+async fn serve_session(
+    mut framed: Framed<TcpStream, FrameCodec>,
+    login_body: Bytes,
+    login: server::LoginResponse,
+    data: &Databases,
+    capture_dir: Option<&Path>,
+    ping_every: Duration,
+) -> Result<(), ConnectionError> {
+    let login_response = login.body.freeze();
+    if let (Some(dir), true) = (capture_dir, login.success) {
+        capture::record(dir, RecordKind::Login, &login.token, login_body).await;
+        capture::record(
+            dir,
+            RecordKind::Response,
+            &login.token,
+            login_response.clone(),
+        )
+        .await;
+    }
+    framed.send(login_response).await?;
+    if !login.success {
+        return Err(ConnectionError::LoginFailed(login.token));
+    }
+    let token = login.token;
+
+    let store = data.sessions();
+    let mut poll = tokio::time::interval(POLL_INTERVAL);
+    let mut ping = tokio::time::interval_at(Instant::now() + ping_every, ping_every);
+    loop {
+        let mut frames = Vec::new();
+        let mut ping_due = false;
+        tokio::select! {
+            frame = framed.next() => match frame {
+                Some(frame) => push_frame(&mut frames, frame?),
+                None => return Ok(()),
+            },
+            _ = poll.tick() => (),
+            _ = ping.tick() => ping_due = true,
+        }
+        // handle whatever else has already arrived in one go, rather than a round trip to redis each
+        while let Some(Some(frame)) = framed.next().now_or_never() {
+            push_frame(&mut frames, frame?);
+        }
+
+        let mut buffer = if frames.is_empty() {
+            // the player hasn't been seen, but whatever was queued for them still goes out
+            let session = store.get(&token).await.map_err(RequestError::from)?;
+            if session.is_none() {
+                return Err(RequestError::from(ExternalError::InvalidToken).into());
+            }
+            store.drain(&token).await.map_err(RequestError::from)?
+        } else {
+            let request_capture = capture_dir.map(|dir| (dir, reassemble(&frames)));
+            server::handle_packets(&token, frames, data, request_capture).await?
+        };
+        if ping_due {
+            bancho_ping(&mut buffer);
+        }
+
+        let buffer = buffer.freeze();
+        if !buffer.is_empty() {
+            if let Some(dir) = capture_dir {
+                capture::record(dir, RecordKind::Response, &token, buffer.clone()).await;
+            }
+            framed.send(buffer).await?;
+        }
+    }
+}
+
+/// Keep a frame to be handled, skipping over one whose payload couldn't be decompressed
+fn push_frame(frames: &mut Vec<Frame>, frame: Result<Frame, DecodeError>) {
+    match frame {
+        Ok(frame) => frames.push(frame),
+        Err(e) => error!(msg = "could not split frame", err = e.to_string()),
+    }
+}
+
+/// Read until the end of the three line login, returning its length
+async fn read_login(stream: &mut TcpStream, buf: &mut BytesMut) -> Result<usize, ConnectionError> {
+    let mut searched = 0;
+    let mut lines = 0;
+    loop {
+        for (i, &byte) in buf[searched..].iter().enumerate() {
+            if byte == b'\n' {
+                lines += 1;
+                if lines == 3 {
+                    return Ok(searched + i + 1);
+                }
+            }
+        }
+        searched = buf.len();
+
+        if buf.len() >= MAX_LOGIN_LEN {
+            return Err(ConnectionError::LoginTooLong);
+        }
+        if stream.read_buf(buf).await? == 0 {
+            return Err(ConnectionError::Closed);
+        }
+    }
+}
+
+/// Put frames back into a body like the client would have sent over HTTP, for captures
+///
+/// Payloads are written back uncompressed, the framer has already decompressed them.
+fn reassemble(frames: &[Frame]) -> Bytes {
+    let mut body = BytesMut::new();
+    for frame in frames {
+        body.with_header(frame.id, |buf| buf.put_slice(&frame.payload));
+    }
+    body.freeze()
+}